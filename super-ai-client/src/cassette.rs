@@ -0,0 +1,216 @@
+//! Recording HTTP exchanges into a file (a “cassette”) and replaying them later.
+//!
+//! A recording cassette keeps each request body along with the raw response:
+//! its status, headers and body, chunk by chunk as it arrived, so streams
+//! replay with the same event boundaries. A replaying cassette answers
+//! requests from the file without any network, matching them by body.
+//! Credentials aren’t recorded, request headers are left out entirely.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The file format.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CassetteFile {
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub url: String,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// As far as they were read, i.e. a stream stops where the client stopped reading it.
+    pub chunks: Vec<Chunk>,
+    /// The error reading the body failed with, e.g. a dropped connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A piece of a response body, as text unless it isn’t valid UTF-8 on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Chunk {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Chunk {
+    fn new(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.to_string()),
+            Err(_) => Self::Bytes(bytes.to_vec()),
+        }
+    }
+    fn to_bytes(&self) -> bytes::Bytes {
+        match self {
+            Self::Text(text) => bytes::Bytes::from(text.clone()),
+            Self::Bytes(bytes) => bytes::Bytes::from(bytes.clone()),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// CASSETTE
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// Shared by clones, so concurrent requests record into (or replay from) the same file.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Arc<Mutex<CassetteState>>,
+}
+
+#[derive(Debug, Default)]
+struct CassetteState {
+    file: CassetteFile,
+    /// Replaying: which interactions were already used.
+    used: Vec<bool>,
+}
+
+impl Cassette {
+    /// Starts an empty cassette, the file is (over)written after every exchange.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), mode: CassetteMode::Record, state: Arc::default() }
+    }
+    pub fn replay(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let source = std::fs::read_to_string(&path)?;
+        let file = serde_json::from_str::<CassetteFile>(&source)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        let used = vec![false; file.interactions.len()];
+        let state = CassetteState { file, used };
+        Ok(Self { path, mode: CassetteMode::Replay, state: Arc::new(Mutex::new(state)) })
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state().file.interactions.clone()
+    }
+    fn state(&self) -> std::sync::MutexGuard<'_, CassetteState> {
+        self.state.lock().unwrap_or_else(|x| x.into_inner())
+    }
+    /// The first unused interaction with the same request body, as a response.
+    ///
+    /// Repeated requests, e.g. retries, get the interactions in recorded order.
+    /// A body that failed while recording fails again, at the same point.
+    pub(crate) fn play(&self, body: &Value) -> Option<reqwest::Response> {
+        let mut state = self.state();
+        let CassetteState { file, used } = &mut *state;
+        let index = file.interactions
+            .iter()
+            .zip(used.iter())
+            .position(|(x, used)| !used && x.request.body == *body)?;
+        used[index] = true;
+        let recorded = &file.interactions[index].response;
+        let mut response = http::Response::builder().status(recorded.status);
+        for (name, value) in recorded.headers.iter() {
+            response = response.header(name, value);
+        }
+        let mut chunks = recorded.chunks.iter().map(|x| Ok(x.to_bytes())).collect::<Vec<_>>();
+        if let Some(error) = recorded.error.as_ref() {
+            chunks.push(Err(std::io::Error::new(std::io::ErrorKind::ConnectionAborted, error.clone())));
+        }
+        let body = reqwest::Body::wrap_stream(futures::stream::iter(chunks));
+        response.body(body).ok().map(reqwest::Response::from)
+    }
+    /// Passes `response` through, recording it once its body is consumed or dropped.
+    pub(crate) fn tape(&self, url: &str, body: &Value, response: reqwest::Response) -> reqwest::Response {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let recorded = RecordedResponse {
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                .collect(),
+            chunks: Vec::new(),
+            error: None,
+        };
+        let interaction = Interaction { request: RecordedRequest { url: url.to_string(), body: body.clone() }, response: recorded };
+        let body = RecordingBody {
+            inner: Box::pin(response.bytes_stream()),
+            interaction: Some(interaction),
+            cassette: self.clone(),
+        };
+        let mut response = http::Response::builder().status(status);
+        if let Some(response_headers) = response.headers_mut() {
+            *response_headers = headers;
+        }
+        let response = response
+            .body(reqwest::Body::wrap_stream(body))
+            .expect("status and headers come from a valid response");
+        reqwest::Response::from(response)
+    }
+    fn push(&self, interaction: Interaction) {
+        let mut state = self.state();
+        state.file.interactions.push(interaction);
+        let result = serde_json::to_string_pretty(&state.file)
+            .map_err(std::io::Error::other)
+            .and_then(|source| std::fs::write(&self.path, source));
+        if let Err(error) = result {
+            eprintln!("failed to write cassette {}: {error}", self.path.display());
+        }
+    }
+}
+
+/// Records the chunks passing through, the interaction is stored when dropped.
+struct RecordingBody {
+    inner: BoxStream<'static, reqwest::Result<bytes::Bytes>>,
+    interaction: Option<Interaction>,
+    cassette: Cassette,
+}
+
+impl Stream for RecordingBody {
+    type Item = reqwest::Result<bytes::Bytes>;
+    fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.as_mut().poll_next(cx);
+        if let Some(interaction) = self.interaction.as_mut() {
+            match &poll {
+                Poll::Ready(Some(Ok(bytes))) => interaction.response.chunks.push(Chunk::new(bytes)),
+                Poll::Ready(Some(Err(error))) => interaction.response.error = Some(error.to_string()),
+                _ => (),
+            }
+        }
+        poll
+    }
+}
+
+impl Drop for RecordingBody {
+    fn drop(&mut self) {
+        if let Some(interaction) = self.interaction.take() {
+            self.cassette.push(interaction);
+        }
+    }
+}
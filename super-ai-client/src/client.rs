@@ -1,14 +1,16 @@
-use std::{cell::RefCell, path::Path};
-use colored::Colorize;
-use futures::{StreamExt, TryFutureExt};
+use std::{borrow::Cow, collections::VecDeque, path::Path, sync::Arc};
+use futures::{Stream, StreamExt, stream::BoxStream};
 
 use super::response;
 
 use crate::log::Logger;
+use crate::sse::{ServerSentEvent, SseDecoder};
+use crate::provider::{ChatProvider, OpenAiCompatibleProvider};
+use crate::cassette::{Cassette, CassetteMode};
+use crate::ratelimit::{estimate_tokens, RateLimiter};
 
-thread_local! {
-    static RUNTIME: RefCell<tokio::runtime::Runtime> = RefCell::new(tokio::runtime::Runtime::new().unwrap());
-}
+/// The underlying HTTP client; clones share its connection pool.
+pub use reqwest::Client as HttpClient;
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 // TODO
@@ -16,26 +18,53 @@ thread_local! {
 /// This should be called ‘RequestBuilder’ but it’s already taken.
 #[derive(Default)]
 pub struct ClientBuilder {
-    pub api_url: Option<URL>,
+    pub provider: Option<Arc<dyn ChatProvider>>,
     pub api_key: Option<String>,
     pub request_body: Option<super::request::RequestBuilder>,
     pub timeout: Option<std::time::Duration>,
     pub logger: Option<Box<dyn Logger>>,
+    /// Sent with every request, in addition to the provider’s own headers.
+    pub headers: Vec<(String, String)>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub cassette: Option<Cassette>,
+    pub http_client: Option<HttpClient>,
 }
 
 impl ClientBuilder {
+    /// Shorthand for an OpenAI compatible provider with bearer authentication.
     pub fn with_api_url(mut self, api_url: URL) -> Self {
-        self.api_url = Some(api_url);
+        self.provider = Some(Arc::new(OpenAiCompatibleProvider::from(api_url)));
+        self
+    }
+    /// Shorthand for an OpenAI compatible provider at the given base URL, e.g. `http://localhost:8080/v1`.
+    pub fn with_base_url(self, base_url: impl AsRef<str>) -> Self {
+        self.with_api_url(URL::from_base_url(base_url))
+    }
+    pub fn with_provider(mut self, provider: Arc<dyn ChatProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.headers.extend(headers);
         self
     }
     pub fn with_api_key(mut self, api_key: impl AsRef<str>) -> Self {
         self.api_key = Some(api_key.as_ref().to_string());
         self
     }
-    pub fn with_api_key_path(mut self, path: impl AsRef<Path>) -> Self {
-        let contents = std::fs::read_to_string(path).expect("api key file path");
-        self.api_key = Some(contents);
-        self
+    /// Reads the API key from a file, ignoring surrounding whitespace such as a trailing newline.
+    pub fn with_api_key_path(mut self, path: impl AsRef<Path>) -> Result<Self, ClientError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|error| InvalidConfiguration::ApiKeyFile {
+            path: path.to_path_buf(),
+            error: error.to_string(),
+        })?;
+        self.api_key = Some(contents.trim().to_string());
+        Ok(self)
     }
     pub fn with_request_body(mut self, request_body: super::request::RequestBuilder) -> Self {
         self.request_body = Some(request_body);
@@ -45,25 +74,63 @@ impl ClientBuilder {
         self.timeout = Some(timeout);
         self
     }
+    /// Paces every attempt, see [`RateLimiter`].
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+    /// Records every exchange into the cassette, or replays them from it without sending anything.
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+    /// Sends requests via `http_client`, so connections and TLS sessions are
+    /// reused across clients; each client creates its own otherwise.
+    pub fn with_http_client(mut self, http_client: HttpClient) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
     pub fn with_logger(mut self, logger: impl Logger + 'static) -> Self {
         let logger: Box<dyn Logger> = Box::new(logger);
         self.logger = Some(logger);
         self
     }
-    fn build(self) -> Option<IClient> {
-        let api_url = self.api_url?;
-        let api_key = self.api_key?;
-        let request_body = self.request_body?.build()?;
+    /// The body as it is sent, e.g. asking for the usage of streams where the provider reports it.
+    pub fn build_request_body(&self) -> Result<super::request::Request, InvalidConfiguration> {
+        let provider = self.provider.as_ref().ok_or(InvalidConfiguration::MissingProvider)?;
+        let mut request_body = self.request_body
+            .clone()
+            .ok_or(InvalidConfiguration::MissingRequestBody)?;
+        if request_body.stream == Some(true) && request_body.stream_options.is_none() && provider.stream_usage() {
+            request_body = request_body.with_stream_options(super::request::StreamOptions { include_usage: true });
+        }
+        request_body
+            .build()
+            .ok_or(InvalidConfiguration::IncompleteRequestBody)
+    }
+    fn build(self) -> Result<IClient, InvalidConfiguration> {
+        let request_body = self.build_request_body()?;
+        let provider = self.provider.ok_or(InvalidConfiguration::MissingProvider)?;
+        let api_key = self.api_key.or_else(|| provider.api_key());
         let timeout = self.timeout;
         let logger: Option<Box<dyn Logger>> = self.logger;
-        let client = IClient { api_url, api_key, request_body, timeout, logger };
-        Some(client)
+        let headers = self.headers;
+        let rate_limiter = self.rate_limiter;
+        let cassette = self.cassette;
+        let http_client = match self.http_client {
+            Some(http_client) => http_client,
+            None => HttpClient::builder()
+                .build()
+                .map_err(|error| InvalidConfiguration::HttpClient(error.to_string()))?,
+        };
+        let client = IClient { provider, api_key, request_body, timeout, logger, headers, rate_limiter, cassette, http_client };
+        Ok(client)
     }
-    pub fn build_batch_api_call(self) -> Option<BatchClient> {
-        Some(BatchClient { client: self.build()? })
+    pub fn build_batch_api_call(self) -> Result<BatchClient, InvalidConfiguration> {
+        Ok(BatchClient { client: self.build()? })
     }
-    pub fn build_streaming_api_call(self) -> Option<StreamingClient> {
-        Some(StreamingClient { client: self.build()? })
+    pub fn build_streaming_api_call(self) -> Result<StreamingClient, InvalidConfiguration> {
+        Ok(StreamingClient { client: self.build()? })
     }
 }
 
@@ -73,29 +140,95 @@ impl ClientBuilder {
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 /// This should be called ‘Request but it’s already taken.
 struct IClient {
-    pub api_url: URL,
-    pub api_key: String,
+    pub provider: Arc<dyn ChatProvider>,
+    pub api_key: Option<String>,
     pub request_body: super::request::Request,
     pub timeout: Option<std::time::Duration>,
     pub logger: Option<Box<dyn Logger>>,
+    pub headers: Vec<(String, String)>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub cassette: Option<Cassette>,
+    pub http_client: HttpClient,
+}
+
+impl IClient {
+    /// A `POST` to the provider’s chat completions endpoint with all credentials and headers attached.
+    fn post(&self) -> reqwest::RequestBuilder {
+        let mut request = self.http_client.post(self.provider.chat_completions_url());
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let mut request = self.provider.authorize(request, self.api_key.as_deref());
+        let headers = self.provider.headers().into_iter().chain(self.headers.iter().cloned());
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+    /// Sends the request body once; retrying is up to the caller, see [`crate::retry`].
+    async fn send(&self) -> Result<reqwest::Response, ClientError> {
+        let provider = self.provider.name();
+        let model = self.request_body.model.as_str();
+        let body = self.cassette
+            .as_ref()
+            .map(|_| serde_json::to_value(&self.request_body).unwrap_or_default())
+            .unwrap_or_default();
+        if let Some(cassette) = self.cassette.as_ref().filter(|x| x.mode() == CassetteMode::Replay) {
+            let response = cassette.play(&body).ok_or_else(|| ClientError::Unrecorded {
+                cassette: cassette.path().to_path_buf(),
+            })?;
+            return check_status(response).await
+        }
+        if let Some(rate_limiter) = self.rate_limiter.as_ref() {
+            rate_limiter.acquire(provider, model, estimate_tokens(&self.request_body)).await;
+        }
+        let mut response = self.post()
+            .json(&self.request_body)
+            .send()
+            .await
+            .map_err(ClientError::Transport)?;
+        if let Some(rate_limiter) = self.rate_limiter.as_ref() {
+            rate_limiter.update(provider, model, response.headers());
+        }
+        if let Some(cassette) = self.cassette.as_ref() {
+            response = cassette.tape(&self.provider.chat_completions_url(), &body, response);
+        }
+        check_status(response).await
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum InvalidConfiguration {
     StreamFlag { should_be: bool, given: bool },
+    MissingProvider,
+    MissingRequestBody,
+    /// The request body has no model or no messages.
+    IncompleteRequestBody,
+    /// E.g. the TLS backend failed to initialize.
+    HttpClient(String),
+    ApiKeyFile { path: std::path::PathBuf, error: String },
 }
 
 impl std::fmt::Display for InvalidConfiguration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             InvalidConfiguration::StreamFlag { should_be, given } => {
-                let should_be = *should_be;
-                let given = *given;
-                let msg = format!(
-                    "Error: Invalid Configuration! Stream flag should be '{should_be}' given '{given}'."
-                );
-                let msg = msg.red();
-                write!(f, "{msg}")
+                write!(f, "invalid configuration: stream flag should be '{should_be}' given '{given}'")
+            }
+            InvalidConfiguration::MissingProvider => {
+                write!(f, "invalid configuration: no provider or API URL given")
+            }
+            InvalidConfiguration::MissingRequestBody => {
+                write!(f, "invalid configuration: no request body given")
+            }
+            InvalidConfiguration::IncompleteRequestBody => {
+                write!(f, "invalid configuration: the request needs a model and at least one message")
+            }
+            InvalidConfiguration::HttpClient(error) => {
+                write!(f, "invalid configuration: the HTTP client couldn’t be created: {error}")
+            }
+            InvalidConfiguration::ApiKeyFile { path, error } => {
+                write!(f, "invalid configuration: the API key file {} couldn’t be read: {error}", path.display())
             }
         }
     }
@@ -112,45 +245,15 @@ pub struct BatchClient {
 
 impl BatchClient {
     /// This calls the streaming client internally.
-    pub fn execute_blocking(self) -> Result<response::batch::Response, Box<dyn std::error::Error>> {
-        RUNTIME.with_borrow(|runtime| {
-            runtime.block_on(self.execute_async())
-        })
-    }
-    /// This calls the streaming client internally.
-    pub async fn execute_async(self) -> Result<response::batch::Response, Box<dyn std::error::Error>> {
-        let api_url = self.client.api_url.0;
-        let api_key = self.client.api_key.as_str();
-        let client = {
-            if let Some(timeout) = self.client.timeout.as_ref() {
-                reqwest::ClientBuilder::new()
-                    .timeout(timeout.clone())
-                    .build()
-                    .unwrap()
-            } else {
-                reqwest::ClientBuilder::new().build().unwrap()
-            }
-        };
+    pub async fn execute_async(self) -> Result<response::batch::Response, ClientError> {
         let stream_flag = self.client.request_body.stream.unwrap_or(false);
-        if stream_flag == true {
-            return Err(Box::new(InvalidConfiguration::StreamFlag { should_be: false, given: true }));
+        if stream_flag {
+            return Err(InvalidConfiguration::StreamFlag { should_be: false, given: true }.into());
         }
-        let json_data = serde_json::to_string(&self.client.request_body).unwrap();
-        let response = client
-            .post(api_url)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            // .json(&self.client.request_body)
-            .body(json_data)
-            .send()
-            .await?;
-        if let Some(error) = ApiError::from_code(response.status().as_u16()) {
-            return Err(Box::new(error))
-        }
-        let result = response.text().await?;
-        let result = serde_json::from_str::<response::batch::Response>(&result)?;
-        // let result = response.json::<response::batch::Response>().await?;
-        Ok(result)
+        let response = self.client.send().await?;
+        let body = response.text().await.map_err(ClientError::Transport)?;
+        serde_json::from_str::<response::batch::Response>(&body)
+            .map_err(|error| ClientError::Decode { body, error: error.to_string() })
     }
 }
 
@@ -162,99 +265,217 @@ pub struct StreamingClient {
     client: IClient
 }
 
-// #[derive(Debug, Clone)]
-// pub struct ServerSentEvent {
-//     pub message: String
-// }
-
 
 impl StreamingClient {
-    pub fn execute_blocking(self) -> Result<ResponseChunkCollection, Box<dyn std::error::Error>> {
-        RUNTIME.with_borrow(|runtime| {
-            runtime.block_on(self.execute_async())
-        })
-    }
-    pub async fn execute_async(self) -> Result<ResponseChunkCollection, Box<dyn std::error::Error>> {
-        let api_url = self.client.api_url.0;
-        let api_key = self.client.api_key.as_str();
-        let client = {
-            if let Some(timeout) = self.client.timeout.as_ref() {
-                reqwest::ClientBuilder::new()
-                    .timeout(timeout.clone())
-                    .build()
-                    .unwrap()
-            } else {
-                reqwest::Client::new()
+    /// Collects the whole stream, logging text deltas as they arrive.
+    pub async fn execute_async(mut self) -> Result<ResponseChunkCollection, ClientError> {
+        let mut logger = self.client.logger.take();
+        let stream = self.stream().await?;
+        tokio::pin!(stream);
+        let mut outputs = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if let Some(logger) = logger.as_mut() {
+                logger.log(&chunk.text());
             }
-        };
-        let response = client
-            .post(api_url)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .json(&self.client.request_body)
-            .send()
-            .map_err(Box::new)
-            .map_err(|x| vec![x])
-            .await
-            .unwrap();
-        if let Some(error) = ApiError::from_code(response.status().as_u16()) {
-            return Err(Box::new(error))
+            outputs.push(chunk);
         }
+        if let Some(logger) = logger.as_mut() {
+            logger.log("\n");
+        }
+        Ok(ResponseChunkCollection(outputs))
+    }
+    /// Sends the request and yields each chunk as it arrives.
+    ///
+    /// Failures before the stream starts (e.g. an HTTP error status) are
+    /// returned directly, mid-stream failures are yielded as the final item.
+    /// The stream ends after the `[DONE]` sentinel. Dropping it cancels the request.
+    pub async fn stream(self) -> Result<impl Stream<Item = Result<response::streaming::ResponseChunk, ClientError>> + Send + 'static, ClientError> {
         let stream = self.client.request_body.stream.unwrap_or(false);
         if !stream {
-            return Err(Box::new(InvalidConfiguration::StreamFlag { should_be: true, given: false }));
+            return Err(InvalidConfiguration::StreamFlag { should_be: true, given: false }.into());
         }
-        let mut outputs = Vec::new();
-        if let Some(error) = ApiError::from_code(response.status().as_u16()) {
-            return Err(Box::new(error));
-        }
-        let response = response.bytes_stream();
-        tokio::pin!(response);
-        let mut logger = self.client.logger;
-        while let Some(Ok(data)) = response.next().await {
-            let data = String::from_utf8_lossy(&data.to_vec()).to_string();
-            let mut results = data
-                .lines()
-                .filter(|line| {
-                    line.starts_with("data: ")
-                })
-                .filter_map(|line| {
-                    let json = &line["data: ".len()..];
-                    let json = serde_json::from_str::<response::streaming::ResponseChunk>(json).ok()?;
-                    let msg = json.choices
-                        .iter()
-                        .filter_map(|x| x.delta.content.clone())
-                        .collect::<String>();
-                    if let Some(logger) = logger.as_mut() {
-                        logger.log(&msg);
+        let response = self.client.send().await?;
+        let state = ChunkStreamState {
+            body: response.bytes_stream().boxed(),
+            decoder: SseDecoder::new(),
+            pending: VecDeque::new(),
+            body_exhausted: false,
+            finished: false,
+            done: false,
+        };
+        Ok(futures::stream::unfold(state, ChunkStreamState::next))
+    }
+    /// Like [`StreamingClient::stream`] but only yields the text content, per choice.
+    pub async fn text_deltas(self) -> Result<impl Stream<Item = Result<response::streaming::TextDelta, ClientError>> + Send + 'static, ClientError> {
+        let stream = self.stream().await?;
+        let stream = stream.flat_map(|chunk| {
+            let deltas = match chunk {
+                Ok(chunk) => chunk.text_deltas().into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(error) => vec![Err(error)],
+            };
+            futures::stream::iter(deltas)
+        });
+        Ok(stream)
+    }
+}
+
+struct ChunkStreamState {
+    body: BoxStream<'static, reqwest::Result<bytes::Bytes>>,
+    decoder: SseDecoder,
+    /// Decoded but not yet yielded events.
+    pending: VecDeque<ServerSentEvent>,
+    body_exhausted: bool,
+    /// Whether any chunk carried a `finish_reason`.
+    finished: bool,
+    /// Set after `[DONE]` or an error, nothing more is yielded.
+    done: bool,
+}
+
+impl ChunkStreamState {
+    async fn next(mut self) -> Option<(Result<response::streaming::ResponseChunk, ClientError>, Self)> {
+        loop {
+            if self.done {
+                return None
+            }
+            if let Some(event) = self.pending.pop_front() {
+                match decode_event(&event) {
+                    Ok(Some(chunk)) => {
+                        self.finished |= chunk.choices.iter().any(|x| x.finish_reason.is_some());
+                        return Some((Ok(chunk), self))
                     }
-                    Some(json)
-                })
-                .collect::<Vec<_>>();
-            outputs.append(&mut results);
-        }
-        if let Some(logger) = logger.as_mut() {
-            logger.log("\n");
+                    Ok(None) => {
+                        self.done = true;
+                        return None
+                    }
+                    Err(error) => {
+                        self.done = true;
+                        return Some((Err(error), self))
+                    }
+                }
+            }
+            if self.body_exhausted {
+                self.done = true;
+                if self.finished {
+                    return None
+                }
+                return Some((Err(ClientError::UnexpectedEnd), self))
+            }
+            match self.body.next().await {
+                Some(Ok(data)) => {
+                    let events = self.decoder.push(&data);
+                    self.pending.extend(events);
+                }
+                Some(Err(error)) => {
+                    self.done = true;
+                    return Some((Err(ClientError::Transport(error)), self))
+                }
+                None => {
+                    self.body_exhausted = true;
+                    self.pending.extend(self.decoder.finish());
+                }
+            }
         }
-        Ok(ResponseChunkCollection(outputs))
     }
 }
 
+/// Decodes a single event, returning `None` for the terminal `[DONE]` sentinel.
+fn decode_event(event: &ServerSentEvent) -> Result<Option<response::streaming::ResponseChunk>, ClientError> {
+    if event.is_done() {
+        return Ok(None)
+    }
+    if let Ok(error) = serde_json::from_str::<response::ErrorResponse>(&event.data) {
+        return Err(ClientError::Api(Box::new(error.error)))
+    }
+    if event.is_error() {
+        return Err(ClientError::Api(Box::new(response::ErrorPayload {
+            message: event.data.clone(),
+            r#type: None,
+            param: None,
+            code: None,
+        })))
+    }
+    serde_json::from_str::<response::streaming::ResponseChunk>(&event.data)
+        .map(Some)
+        .map_err(|error| ClientError::Decode { body: event.data.clone(), error: error.to_string() })
+}
+
+/// Passes successful responses through, otherwise reads the provider’s error body.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response)
+    }
+    let retry_after = crate::retry::retry_after(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    let payload = serde_json::from_str::<response::ErrorResponse>(&body)
+        .ok()
+        .map(|x| x.error);
+    Err(ClientError::Status(Box::new(StatusError {
+        status: status.as_u16(),
+        kind: ApiError::from_code(status.as_u16()),
+        payload,
+        body,
+        retry_after,
+    })))
+}
+
 #[derive(Debug, Clone)]
 pub struct ResponseChunkCollection(pub Vec<response::streaming::ResponseChunk>);
 
 impl ResponseChunkCollection {
     pub fn len(&self) -> usize { self.0.len() }
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+    /// The text of the choice at `index`; chunks of several choices carry them in any order.
     pub fn content(&self, index: usize) -> Option<String> {
         let output = self.0
             .iter()
-            .filter_map(|x| x.choices.get(index).cloned())
-            .filter_map(|x| x.delta.content)
+            .filter_map(|x| x.choices.iter().find(|x| x.index as usize == index))
+            .filter_map(|x| x.delta.content.clone())
             .collect::<Vec<_>>();
         if output.is_empty() {
             return None
         }
         Some(output.join(""))
     }
+    /// How many choices were streamed, e.g. for a request with `n`.
+    pub fn choice_count(&self) -> usize {
+        self.0
+            .iter()
+            .flat_map(|x| x.choices.iter())
+            .map(|x| x.index as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+    /// The token usage reported for the whole request, if any.
+    pub fn usage(&self) -> Option<response::batch::Usage> {
+        self.0.iter().rev().find_map(|x| x.usage)
+    }
+    /// The tool calls of the choice at `index`, assembled from their deltas.
+    pub fn tool_calls(&self, index: usize) -> Vec<response::ToolCall> {
+        let mut accumulator = response::streaming::ToolCallAccumulator::default();
+        let deltas = self.0
+            .iter()
+            .filter_map(|x| x.choices.iter().find(|x| x.index as usize == index))
+            .filter_map(|x| x.delta.tool_calls.as_ref())
+            .flatten();
+        for delta in deltas {
+            accumulator.push(delta);
+        }
+        accumulator.finish()
+    }
+    /// Why the choice at `index` ended, e.g. `stop` or `tool_calls`.
+    pub fn finish_reason(&self, index: usize) -> Option<String> {
+        self.0
+            .iter()
+            .filter_map(|x| x.choices.iter().find(|x| x.index as usize == index))
+            .find_map(|x| x.finish_reason.clone())
+    }
+    /// The choice at `index` as an assistant message, e.g. to continue the conversation after calling its tools.
+    pub fn message(&self, index: usize) -> super::request::Message {
+        let content = self.content(index).unwrap_or_default();
+        super::request::Message::assistant_tool_calls(content, self.tool_calls(index))
+    }
 }
 
 
@@ -262,18 +483,168 @@ impl ResponseChunkCollection {
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 // TODO
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
-#[derive(Debug, Clone, Copy)]
-pub struct URL(pub &'static str);
+/// A chat completions endpoint.
+#[derive(Debug, Clone)]
+pub struct URL(pub Cow<'static, str>);
 
 impl URL {
-    pub const OPEN_AI_CHAT_COMPLETIONS: Self = URL("https://api.openai.com/v1/chat/completions");
-    pub const OCTO_AI_CHAT_COMPLETIONS: Self = URL("https://text.octoai.run/v1/chat/completions");
-    pub const MISTRAL_AI_CHAT_COMPLETIONS: Self = URL("https://api.mistral.ai/v1/chat/completions");
+    pub const OPEN_AI_CHAT_COMPLETIONS: Self = URL(Cow::Borrowed("https://api.openai.com/v1/chat/completions"));
+    pub const OCTO_AI_CHAT_COMPLETIONS: Self = URL(Cow::Borrowed("https://text.octoai.run/v1/chat/completions"));
+    pub const MISTRAL_AI_CHAT_COMPLETIONS: Self = URL(Cow::Borrowed("https://api.mistral.ai/v1/chat/completions"));
+
+    /// The full URL of the chat completions endpoint.
+    pub fn new(url: impl Into<String>) -> Self {
+        URL(Cow::Owned(url.into()))
+    }
+    /// E.g. `http://localhost:8080/v1`, `/chat/completions` is appended.
+    pub fn from_base_url(base_url: impl AsRef<str>) -> Self {
+        let base_url = base_url.as_ref().trim_end_matches('/');
+        URL::new(format!("{base_url}/chat/completions"))
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    pub fn base_url(&self) -> &str {
+        let url = self.as_str();
+        url.strip_suffix("/chat/completions").unwrap_or(url)
+    }
+}
+
+impl std::fmt::Display for URL {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl From<URL> for OpenAiCompatibleProvider {
+    fn from(url: URL) -> Self {
+        let base_url = url.base_url();
+        OpenAiCompatibleProvider::new(crate::provider::ProviderConfig {
+            name: base_url.to_string(),
+            base_url: base_url.to_string(),
+            auth: Default::default(),
+            api_key_env: None,
+            shared_api_key: false,
+            headers: Default::default(),
+            models: Vec::new(),
+            default_model: None,
+            stream_usage: Some(!base_url.contains("api.mistral.ai")),
+            max_concurrency: None,
+        })
+    }
 }
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
-// TODO
+// ERRORS
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+#[derive(Debug)]
+pub enum ClientError {
+    /// Invalid client or request configuration, nothing was sent.
+    Configuration(InvalidConfiguration),
+    /// Connecting, sending, receiving or timing out, including mid-stream.
+    Transport(reqwest::Error),
+    /// A non-success HTTP status.
+    Status(Box<StatusError>),
+    /// The provider sent an error payload mid-stream.
+    Api(Box<response::ErrorPayload>),
+    /// A response body (or stream event) that couldn’t be decoded.
+    Decode { body: String, error: String },
+    /// The stream closed without `[DONE]` or a `finish_reason`.
+    UnexpectedEnd,
+    /// A replaying cassette has no (unused) response recorded for the request.
+    Unrecorded { cassette: std::path::PathBuf },
+}
+
+/// A non-success HTTP status along with the provider’s error body.
+#[derive(Debug, Clone)]
+pub struct StatusError {
+    pub status: u16,
+    pub kind: Option<ApiError>,
+    /// The parsed error body, if it has the usual `{"error": {...}}` shape.
+    pub payload: Option<response::ErrorPayload>,
+    /// The raw response body.
+    pub body: String,
+    /// The delay requested via `Retry-After` or the rate limit headers.
+    pub retry_after: Option<std::time::Duration>,
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Status(error) => Some(error.status),
+            _ => None,
+        }
+    }
+    /// Whether sending the same request again may succeed.
+    ///
+    /// Connection failures, timeouts, dropped streams and `408`, `429`
+    /// or `5xx` responses are retryable; invalid requests, authentication
+    /// failures and malformed responses are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport(error) => !error.is_builder() && !error.is_decode(),
+            Self::Status(error) => matches!(error.status, 408 | 429 | 500..=599),
+            Self::Api(error) => {
+                let kind = error.r#type.as_deref().unwrap_or_default();
+                matches!(kind, "server_error" | "rate_limit_error" | "overloaded_error" | "rate_limit_exceeded")
+            }
+            Self::UnexpectedEnd => true,
+            Self::Configuration(_) | Self::Decode { .. } | Self::Unrecorded { .. } => false,
+        }
+    }
+    /// The delay requested by the provider, if any.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::Status(error) => error.retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Configuration(error) => write!(f, "{error}"),
+            Self::Transport(error) if error.is_timeout() => write!(f, "request timed out: {error}"),
+            Self::Transport(error) => write!(f, "connection failed: {error}"),
+            Self::Status(error) => write!(f, "{error}"),
+            Self::Api(error) => write!(f, "provider error: {error}"),
+            Self::Decode { body, error } => write!(f, "invalid response payload ({error}): {body}"),
+            Self::UnexpectedEnd => write!(f, "the response stream ended unexpectedly"),
+            Self::Unrecorded { cassette } => write!(f, "no response recorded for the request in cassette {}", cassette.display()),
+        }
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = self.status;
+        match (self.kind.as_ref(), self.payload.as_ref()) {
+            (Some(kind), Some(payload)) => write!(f, "{kind} (HTTP {status}): {payload}"),
+            (Some(kind), None) if self.body.trim().is_empty() => write!(f, "{kind} (HTTP {status})"),
+            (Some(kind), None) => write!(f, "{kind} (HTTP {status}): {}", self.body.trim()),
+            (None, Some(payload)) => write!(f, "HTTP {status}: {payload}"),
+            (None, None) => write!(f, "HTTP {status}: {}", self.body.trim()),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Configuration(error) => Some(error),
+            Self::Transport(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<InvalidConfiguration> for ClientError {
+    fn from(value: InvalidConfiguration) -> Self {
+        Self::Configuration(value)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ApiError {
     /// # TODO
@@ -299,7 +670,7 @@ pub enum ApiError {
 }
 
 impl ApiError {
-    pub(crate) fn from_code(status: impl Into<u16>) -> Option<Self> {
+    pub fn from_code(status: impl Into<u16>) -> Option<Self> {
         match status.into() {
             400 => Some(ApiError::BadRequestError),
             401 => Some(ApiError::AuthenticationError),
@@ -308,6 +679,7 @@ impl ApiError {
             409 => Some(ApiError::ConflictError),
             422 => Some(ApiError::UnprocessableEntityError),
             429 => Some(ApiError::RateLimitError),
+            500..=599 => Some(ApiError::InternalServerError),
             _ => None,
         }
     }
@@ -316,16 +688,16 @@ impl ApiError {
 impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let label = match self {
-            ApiError::APIConnectionError => "API ERROR: api connection error.",
-            ApiError::APITimeoutError => "API ERROR: api timeout error.",
-            ApiError::InternalServerError => "API ERROR: internal server error.",
-            ApiError::AuthenticationError => "API ERROR: authentication error.",
-            ApiError::BadRequestError => "API ERROR: bad request error.",
-            ApiError::ConflictError => "API ERROR: conflict error.",
-            ApiError::NotFoundError => "API ERROR: not found error.",
-            ApiError::PermissionDeniedError => "API ERROR: permission denied error.",
-            ApiError::RateLimitError => "API ERROR: rate limit error.",
-            ApiError::UnprocessableEntityError => "API ERROR: unprocessable entity error.",
+            ApiError::APIConnectionError => "api connection error",
+            ApiError::APITimeoutError => "api timeout error",
+            ApiError::InternalServerError => "internal server error",
+            ApiError::AuthenticationError => "authentication error",
+            ApiError::BadRequestError => "bad request error",
+            ApiError::ConflictError => "conflict error",
+            ApiError::NotFoundError => "not found error",
+            ApiError::PermissionDeniedError => "permission denied error",
+            ApiError::RateLimitError => "rate limit error",
+            ApiError::UnprocessableEntityError => "unprocessable entity error",
         };
         write!(f, "{label}")
     }
@@ -1,4 +1,12 @@
 pub mod log;
 pub mod request;
 pub mod response;
-pub mod client;
\ No newline at end of file
+pub mod client;
+pub mod provider;
+pub mod sse;
+pub mod retry;
+pub mod limit;
+pub mod ratelimit;
+pub mod cassette;
+#[cfg(feature = "stand-in")]
+pub mod stand_in;
\ No newline at end of file
@@ -0,0 +1,64 @@
+//! Bounds how many requests are in flight at once, overall and per provider.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::provider::ChatProvider;
+
+/// Shared by clones, so one limiter can be handed to every concurrent invocation.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyLimiter {
+    global: Option<Arc<Semaphore>>,
+    /// Limits given via [`ConcurrencyLimiter::with_provider_limit`], taking
+    /// precedence over [`ChatProvider::max_concurrency`].
+    limits: HashMap<String, usize>,
+    providers: Arc<Mutex<HashMap<String, Option<Arc<Semaphore>>>>>,
+}
+
+impl ConcurrencyLimiter {
+    /// Without limits, except for the providers’ own [`ChatProvider::max_concurrency`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// At most `limit` requests across all providers.
+    pub fn with_global_limit(mut self, limit: usize) -> Self {
+        self.global = Some(Arc::new(Semaphore::new(limit.max(1))));
+        self
+    }
+    /// At most `limit` requests to the provider named `provider`.
+    pub fn with_provider_limit(mut self, provider: impl Into<String>, limit: usize) -> Self {
+        self.limits.insert(provider.into(), limit.max(1));
+        self
+    }
+    /// Waits until a request to `provider` may be sent; the slot is released when the permit is dropped.
+    pub async fn acquire(&self, provider: &dyn ChatProvider) -> ConcurrencyPermit {
+        // Acquire the provider slot first, so a busy provider doesn’t hold on to global slots.
+        let provider = match self.provider_semaphore(provider) {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        let global = match self.global.clone() {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        ConcurrencyPermit { _global: global, _provider: provider }
+    }
+    fn provider_semaphore(&self, provider: &dyn ChatProvider) -> Option<Arc<Semaphore>> {
+        let mut providers = self.providers.lock().unwrap_or_else(|x| x.into_inner());
+        providers
+            .entry(provider.name().to_string())
+            .or_insert_with(|| {
+                let limit = self.limits.get(provider.name()).copied().or(provider.max_concurrency())?;
+                Some(Arc::new(Semaphore::new(limit.max(1))))
+            })
+            .clone()
+    }
+}
+
+/// Holds a request slot until dropped.
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    _global: Option<OwnedSemaphorePermit>,
+    _provider: Option<OwnedSemaphorePermit>,
+}
@@ -4,7 +4,7 @@ use colored::Colorize;
 // ————————————————————————————————————————————————————————————————————————————
 // LOGGER
 // ————————————————————————————————————————————————————————————————————————————
-pub trait Logger {
+pub trait Logger: Send + Sync {
     fn log(&mut self, msg: &str);
 }
 
@@ -25,7 +25,6 @@ impl FileLogger {
         }
         let file = std::fs::OpenOptions::new()
             .create(true)
-            .write(true)
             .append(true)
             .open(file_path)
             .unwrap();
@@ -36,7 +35,7 @@ impl FileLogger {
 impl Logger for FileLogger {
     fn log(&mut self, msg: &str) {
         use std::io::Write;
-        let _ = write!(&self.file, "{msg}").unwrap();
+        write!(&self.file, "{msg}").unwrap();
     }
 }
 
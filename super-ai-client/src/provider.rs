@@ -0,0 +1,303 @@
+//! Chat completion backends.
+//!
+//! Every provider currently speaks the OpenAI chat completions protocol, they
+//! differ in where requests are sent, how they are authorized and which models
+//! they serve.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+
+use crate::request::{OctoAiModels, OpenAiModels};
+
+// ————————————————————————————————————————————————————————————————————————————
+// PROVIDER
+// ————————————————————————————————————————————————————————————————————————————
+
+pub trait ChatProvider: std::fmt::Debug + Send + Sync {
+    /// The name referenced via `provider="..."`.
+    fn name(&self) -> &str;
+    /// The full URL of the chat completions endpoint.
+    fn chat_completions_url(&self) -> String;
+    /// Attaches credentials to an outgoing request.
+    fn authorize(&self, request: reqwest::RequestBuilder, api_key: Option<&str>) -> reqwest::RequestBuilder;
+    /// The models served by this provider.
+    fn models(&self) -> &[String];
+    /// The model used when none is otherwise specified.
+    fn default_model(&self) -> Option<&str> {
+        None
+    }
+    /// A provider specific API key, used when no key is given explicitly.
+    fn api_key(&self) -> Option<String> {
+        None
+    }
+    /// Whether the runtime wide API key (e.g. `--key-file`) may be sent to this provider.
+    ///
+    /// Only the built-in `openai` provider accepts it, other providers have
+    /// to opt in so a key is never sent to a server it wasn’t issued for.
+    fn accepts_shared_api_key(&self) -> bool {
+        false
+    }
+    /// Additional headers sent with every request, e.g. for an internal gateway.
+    fn headers(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+    /// Whether streaming requests may ask for token usage via `stream_options`.
+    fn stream_usage(&self) -> bool {
+        true
+    }
+    /// How many requests may be in flight at once, unlimited by default.
+    fn max_concurrency(&self) -> Option<usize> {
+        None
+    }
+    fn supports_model(&self, model: &str) -> bool {
+        self.models().iter().any(|x| x == model)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// CONFIGURATION
+// ————————————————————————————————————————————————————————————————————————————
+
+/// How the API key is sent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthStyle {
+    /// `Authorization: Bearer <KEY>`
+    #[default]
+    Bearer,
+    /// `<HEADER>: <KEY>`, e.g. `x-api-key`.
+    Header(String),
+    /// No credentials are sent, e.g. for local servers.
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProviderConfig {
+    pub name: String,
+    /// E.g. `https://api.openai.com/v1`, `/chat/completions` is appended.
+    pub base_url: String,
+    #[serde(default)]
+    pub auth: AuthStyle,
+    /// Name of an environment variable holding the API key for this provider.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Whether the runtime wide API key (e.g. `--key-file`) may be sent to this provider.
+    #[serde(default)]
+    pub shared_api_key: bool,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    /// Set to `false` for servers that reject `stream_options`, defaults to `true`.
+    #[serde(default)]
+    pub stream_usage: Option<bool>,
+    /// How many requests may be in flight at once.
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+}
+
+/// Top-level shape of a providers config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProvidersConfig {
+    #[serde(default, rename = "provider")]
+    pub providers: Vec<ProviderConfig>,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// OPENAI COMPATIBLE PROVIDER
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatibleProvider {
+    pub config: ProviderConfig,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self { config }
+    }
+    /// An ad-hoc provider for an OpenAI compatible server, e.g. llama.cpp, vLLM or Ollama.
+    pub fn custom(name: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self::new(ProviderConfig {
+            name: name.into(),
+            base_url: base_url.into(),
+            auth: AuthStyle::Bearer,
+            api_key_env: None,
+            shared_api_key: false,
+            headers: BTreeMap::new(),
+            models: Vec::new(),
+            default_model: None,
+            stream_usage: None,
+            max_concurrency: None,
+        })
+    }
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.config.headers.extend(headers);
+        self
+    }
+    /// Lets the runtime wide API key be sent to this provider, see [`ChatProvider::accepts_shared_api_key`].
+    pub fn with_shared_api_key(mut self, shared_api_key: bool) -> Self {
+        self.config.shared_api_key = shared_api_key;
+        self
+    }
+    pub fn open_ai() -> Self {
+        Self::new(ProviderConfig {
+            name: String::from("openai"),
+            base_url: String::from("https://api.openai.com/v1"),
+            auth: AuthStyle::Bearer,
+            api_key_env: Some(String::from("OPENAI_API_KEY")),
+            shared_api_key: true,
+            headers: BTreeMap::new(),
+            models: OpenAiModels::ALL.iter().map(|x| x.as_ref().to_string()).collect(),
+            default_model: None,
+            stream_usage: None,
+            max_concurrency: None,
+        })
+    }
+    pub fn octo_ai() -> Self {
+        Self::new(ProviderConfig {
+            name: String::from("octoai"),
+            base_url: String::from("https://text.octoai.run/v1"),
+            auth: AuthStyle::Bearer,
+            api_key_env: Some(String::from("OCTOAI_TOKEN")),
+            shared_api_key: false,
+            headers: BTreeMap::new(),
+            models: OctoAiModels::ALL.iter().map(|x| x.as_ref().to_string()).collect(),
+            default_model: None,
+            stream_usage: None,
+            max_concurrency: None,
+        })
+    }
+    pub fn mistral_ai() -> Self {
+        let models = [
+            "mistral-tiny",
+            "mistral-small",
+            "mistral-medium",
+            "mistral-large-latest",
+            "open-mistral-7b",
+            "open-mixtral-8x7b",
+        ];
+        Self::new(ProviderConfig {
+            name: String::from("mistral"),
+            base_url: String::from("https://api.mistral.ai/v1"),
+            auth: AuthStyle::Bearer,
+            api_key_env: Some(String::from("MISTRAL_API_KEY")),
+            shared_api_key: false,
+            headers: BTreeMap::new(),
+            models: models.iter().map(|x| x.to_string()).collect(),
+            default_model: None,
+            // Mistral rejects unknown fields but reports usage regardless.
+            stream_usage: Some(false),
+            max_concurrency: None,
+        })
+    }
+}
+
+impl ChatProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+    fn chat_completions_url(&self) -> String {
+        format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'))
+    }
+    fn authorize(&self, request: reqwest::RequestBuilder, api_key: Option<&str>) -> reqwest::RequestBuilder {
+        let Some(api_key) = api_key else {
+            return request
+        };
+        let api_key = api_key.trim();
+        match &self.config.auth {
+            AuthStyle::Bearer => request.header("Authorization", format!("Bearer {api_key}")),
+            AuthStyle::Header(header) => request.header(header.as_str(), api_key),
+            AuthStyle::None => request,
+        }
+    }
+    fn models(&self) -> &[String] {
+        &self.config.models
+    }
+    fn default_model(&self) -> Option<&str> {
+        self.config.default_model.as_deref()
+    }
+    fn api_key(&self) -> Option<String> {
+        let variable = self.config.api_key_env.as_ref()?;
+        std::env::var(variable).ok()
+    }
+    fn accepts_shared_api_key(&self) -> bool {
+        self.config.shared_api_key
+    }
+    fn stream_usage(&self) -> bool {
+        self.config.stream_usage.unwrap_or(true)
+    }
+    fn max_concurrency(&self) -> Option<usize> {
+        self.config.max_concurrency
+    }
+    fn headers(&self) -> Vec<(String, String)> {
+        self.config.headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// REGISTRY
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A named collection of providers, later registrations replace earlier ones of the same name.
+#[derive(Debug, Clone)]
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn ChatProvider>>,
+}
+
+impl ProviderRegistry {
+    pub const DEFAULT_PROVIDER: &'static str = "openai";
+
+    pub fn empty() -> Self {
+        Self { providers: Vec::new() }
+    }
+    /// The OpenAI, OctoAI and Mistral providers.
+    pub fn with_builtin() -> Self {
+        Self::empty()
+            .with_provider(OpenAiCompatibleProvider::open_ai())
+            .with_provider(OpenAiCompatibleProvider::octo_ai())
+            .with_provider(OpenAiCompatibleProvider::mistral_ai())
+    }
+    pub fn with_provider(mut self, provider: impl ChatProvider + 'static) -> Self {
+        self.register(Arc::new(provider));
+        self
+    }
+    pub fn with_config(mut self, config: ProvidersConfig) -> Self {
+        for provider in config.providers {
+            self.register(Arc::new(OpenAiCompatibleProvider::new(provider)));
+        }
+        self
+    }
+    pub fn register(&mut self, provider: Arc<dyn ChatProvider>) {
+        self.providers.retain(|x| x.name() != provider.name());
+        self.providers.push(provider);
+    }
+    pub fn get(&self, name: impl AsRef<str>) -> Option<Arc<dyn ChatProvider>> {
+        self.providers
+            .iter()
+            .find(|x| x.name() == name.as_ref())
+            .cloned()
+    }
+    /// The first provider whose model catalog lists the given model.
+    pub fn find_by_model(&self, model: impl AsRef<str>) -> Option<Arc<dyn ChatProvider>> {
+        self.providers
+            .iter()
+            .find(|x| x.supports_model(model.as_ref()))
+            .cloned()
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.providers.iter().map(|x| x.name())
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::with_builtin()
+    }
+}
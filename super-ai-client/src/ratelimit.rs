@@ -0,0 +1,246 @@
+//! Pacing requests to stay within requests-per-minute and tokens-per-minute budgets.
+//!
+//! Each provider/model pair gets a token bucket for requests and one for
+//! tokens. A request waits until both have room for it, where its tokens are
+//! estimated up front (see [`estimate_tokens`]). The buckets are corrected from
+//! the `x-ratelimit-*` response headers, so limits the server reports are
+//! honored even if none were configured.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::request::Request;
+
+// ————————————————————————————————————————————————————————————————————————————
+// CONFIGURATION
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateLimit {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// A [`RateLimit`] for a provider, or for one of its models.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateLimitRule {
+    pub provider: String,
+    /// Without a model the limit applies to each of the provider’s models separately.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(flatten)]
+    pub limit: RateLimit,
+}
+
+/// E.g. loaded from TOML:
+///
+/// ```toml
+/// [[limit]]
+/// provider = "openai"
+/// model = "gpt-4o"
+/// requests-per-minute = 500
+/// tokens-per-minute = 30000
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default, rename = "limit")]
+    pub limits: Vec<RateLimitRule>,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// LIMITER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Shared by clones, so one limiter can pace every concurrent invocation.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    /// Keyed by provider and model, `None` for the provider wide default.
+    limits: HashMap<(String, Option<String>), RateLimit>,
+    buckets: Arc<Mutex<HashMap<(String, String), Buckets>>>,
+    /// Prints a line to stderr before waiting a second or more.
+    logging: bool,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_limit(mut self, provider: impl Into<String>, model: Option<String>, limit: RateLimit) -> Self {
+        self.limits.insert((provider.into(), model), limit);
+        self
+    }
+    pub fn with_logging(mut self, logging: bool) -> Self {
+        self.logging = logging;
+        self
+    }
+    pub fn with_config(self, config: RateLimitConfig) -> Self {
+        config.limits
+            .into_iter()
+            .fold(self, |limiter, rule| limiter.with_limit(rule.provider, rule.model, rule.limit))
+    }
+    fn limit(&self, provider: &str, model: &str) -> RateLimit {
+        self.limits
+            .get(&(provider.to_string(), Some(model.to_string())))
+            .or_else(|| self.limits.get(&(provider.to_string(), None)))
+            .copied()
+            .unwrap_or_default()
+    }
+    /// Waits until a request of about `tokens` tokens may be sent to `model` of `provider`.
+    pub async fn acquire(&self, provider: &str, model: &str, tokens: u32) {
+        let mut announced = false;
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap_or_else(|x| x.into_inner());
+                let buckets = buckets
+                    .entry((provider.to_string(), model.to_string()))
+                    .or_insert_with(|| Buckets::new(self.limit(provider, model)));
+                buckets.try_take(tokens as f64)
+            };
+            let Some(wait) = wait else {
+                return
+            };
+            if self.logging && !announced && wait >= Duration::from_secs(1) {
+                let message = format!("rate limit of {provider}/{model} reached; waiting {:.1}s", wait.as_secs_f64());
+                eprintln!("{}", message.yellow());
+                announced = true;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+    /// Corrects the buckets of `model` of `provider` from the `x-ratelimit-*` headers of a response.
+    ///
+    /// Headers that aren’t a positive limit and a remaining count, e.g. `0`, `NaN` or `inf`, are ignored.
+    pub fn update(&self, provider: &str, model: &str, headers: &reqwest::header::HeaderMap) {
+        let header = |name: &str| headers.get(name).and_then(|x| x.to_str().ok()).map(str::trim);
+        let observed = |kind: &str| {
+            let limit = header(&format!("x-ratelimit-limit-{kind}"))?.parse::<f64>().ok()?;
+            let remaining = header(&format!("x-ratelimit-remaining-{kind}"))?.parse::<f64>().ok()?;
+            if !TokenBucket::is_valid(limit, remaining) {
+                return None
+            }
+            let reset = header(&format!("x-ratelimit-reset-{kind}")).and_then(crate::retry::parse_reset_duration);
+            Some((limit, remaining, reset))
+        };
+        let requests = observed("requests");
+        let tokens = observed("tokens");
+        if requests.is_none() && tokens.is_none() {
+            return
+        }
+        let mut buckets = self.buckets.lock().unwrap_or_else(|x| x.into_inner());
+        let buckets = buckets
+            .entry((provider.to_string(), model.to_string()))
+            .or_insert_with(|| Buckets::new(self.limit(provider, model)));
+        if let Some((limit, remaining, reset)) = requests {
+            buckets.requests.get_or_insert_with(|| TokenBucket::per_minute(limit)).observe(limit, remaining, reset);
+        }
+        if let Some((limit, remaining, reset)) = tokens {
+            buckets.tokens.get_or_insert_with(|| TokenBucket::per_minute(limit)).observe(limit, remaining, reset);
+        }
+    }
+}
+
+/// A rough token count for pacing: about four characters per prompt token,
+/// plus the completion tokens the request may use, as providers count those
+/// against the budget up front.
+pub fn estimate_tokens(request: &Request) -> u32 {
+    let characters = request.messages
+        .iter()
+        .map(|x| x.content().chars().count() + x.role().len())
+        .sum::<usize>();
+    let prompt = characters.div_ceil(4);
+    let completion = request.max_tokens.unwrap_or(0).max(0) as usize * request.n.unwrap_or(1).max(1) as usize;
+    (prompt + completion).min(u32::MAX as usize) as u32
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// BUCKETS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+impl Buckets {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            requests: limit.requests_per_minute.map(|x| TokenBucket::per_minute(x as f64)),
+            tokens: limit.tokens_per_minute.map(|x| TokenBucket::per_minute(x as f64)),
+        }
+    }
+    /// Takes one request and `tokens` tokens, or returns how long to wait for them.
+    fn try_take(&mut self, tokens: f64) -> Option<Duration> {
+        let now = Instant::now();
+        let wait = [(&mut self.requests, 1.0), (&mut self.tokens, tokens)]
+            .into_iter()
+            .filter_map(|(bucket, amount)| bucket.as_mut().map(|x| x.wait(amount, now)))
+            .max()
+            .unwrap_or_default();
+        if !wait.is_zero() {
+            return Some(wait)
+        }
+        if let Some(bucket) = self.requests.as_mut() {
+            bucket.available -= 1.0;
+        }
+        if let Some(bucket) = self.tokens.as_mut() {
+            bucket.available -= tokens.min(bucket.capacity);
+        }
+        None
+    }
+}
+
+/// Timed by tokio’s clock, so pausing it in tests pauses the refill too.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    /// Per second.
+    refill_rate: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: f64) -> Self {
+        let limit = limit.max(1.0);
+        Self { capacity: limit, available: limit, refill_rate: limit / 60.0, updated: Instant::now() }
+    }
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_rate).min(self.capacity);
+        self.updated = now;
+    }
+    /// How long until `amount` is available; more than the capacity waits for a full bucket.
+    fn wait(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            return Duration::ZERO
+        }
+        // A bucket that doesn’t refill waits out the minute its limit is for.
+        Duration::try_from_secs_f64(missing / self.refill_rate).unwrap_or(Duration::from_secs(60))
+    }
+    fn is_valid(limit: f64, remaining: f64) -> bool {
+        limit.is_finite() && limit > 0.0 && remaining.is_finite() && remaining >= 0.0
+    }
+    /// Adopts the server’s view: `remaining` of `limit`, fully replenished after `reset`.
+    /// Values that can’t be a limit leave the bucket as it is.
+    fn observe(&mut self, limit: f64, remaining: f64, reset: Option<Duration>) {
+        if !Self::is_valid(limit, remaining) {
+            return
+        }
+        self.refill(Instant::now());
+        self.capacity = limit.max(1.0);
+        self.available = remaining.clamp(0.0, self.capacity);
+        self.refill_rate = match reset.map(|x| x.as_secs_f64()).filter(|x| *x > 0.0) {
+            Some(reset) if remaining < limit => (limit - remaining) / reset,
+            _ => self.capacity / 60.0,
+        };
+    }
+}
@@ -47,6 +47,8 @@ pub struct RequestBuilder {
     pub stop: Option<Vec<String>>,
     /// If set, partial message deltas will be sent, like in ChatGPT. Tokens will be sent as data-only [server-sent events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events/Using_server-sent_events#Event_stream_format) as they become available, with the stream terminated by a `data: [DONE]` message. [Example Python code.](https://cookbook.openai.com/examples/how_to_stream_completions)
     pub stream: Option<bool>,
+    /// Options for streaming responses, only set this when `stream` is `true`.
+    pub stream_options: Option<StreamOptions>,
     /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
     ///
     /// We generally recommend altering this or `top_p` but not both.
@@ -157,6 +159,10 @@ impl RequestBuilder {
         self.stream = Some(stream);
         self
     }
+    pub fn with_stream_options(mut self, stream_options: StreamOptions) -> Self {
+        self.stream_options = Some(stream_options);
+        self
+    }
     /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
     ///
     /// We generally recommend altering this or `top_p` but not both.
@@ -223,6 +229,7 @@ impl RequestBuilder {
             seed: self.seed,
             stop: self.stop,
             stream: self.stream,
+            stream_options: self.stream_options,
             temperature: self.temperature,
             top_p: self.top_p,
             tools: self.tools,
@@ -299,6 +306,10 @@ pub struct Request {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub stream: Option<bool>,
+    /// Options for streaming responses, only set this when `stream` is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub stream_options: Option<StreamOptions>,
     /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
     ///
     /// We generally recommend altering this or `top_p` but not both.
@@ -350,6 +361,12 @@ pub struct Request {
 }
 
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamOptions {
+    /// If set, an additional chunk will be streamed before the `data: [DONE]` message. The `usage` field on this chunk shows the token usage statistics for the entire request, and the `choices` field will always be an empty array.
+    pub include_usage: bool,
+}
+
 /// Use one of the constructors, i.e. `ResponseFormat::TEXT` or `ResponseFormat::JSON_OBJECT`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseFormat {
@@ -365,7 +382,7 @@ impl ResponseFormat {
     };
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "role")]
 #[serde(rename_all = "snake_case")]
 pub enum Message {
@@ -384,15 +401,19 @@ pub enum Message {
         name: Option<String>,
     },
     Assistant {
+        /// Empty (`null` over the wire) when the model only calls tools.
+        #[serde(default, deserialize_with = "deserialize_optional_content")]
         content: String,
         /// Currently we don’t serialize this field. It’s skipped.
         #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(default)]
         name: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
-        tool_calls: Option<Vec<()>>,
+        #[serde(default)]
+        tool_calls: Option<Vec<ToolCall>>,
         #[serde(skip_serializing_if = "Option::is_none")]
-        function_call: Option<()>,
+        #[serde(default)]
+        function_call: Option<FunctionCall>,
     },
     Tool {
         content: String,
@@ -451,6 +472,17 @@ impl Message {
             function_call: None,
         }
     }
+    /// An assistant message calling tools, to send back along with their results.
+    pub fn assistant_tool_calls(content: impl AsRef<str>, tool_calls: Vec<ToolCall>) -> Self {
+        let content = content.as_ref().to_string();
+        Message::Assistant {
+            content,
+            name: None,
+            tool_calls: Some(tool_calls).filter(|x| !x.is_empty()),
+            function_call: None,
+        }
+    }
+    /// The result of the tool call with the given id.
     pub fn tool(content: impl AsRef<str>, tool_call_id: impl AsRef<str>) -> Self {
         let content = content.as_ref().to_string();
         let tool_call_id = tool_call_id.as_ref().to_string();
@@ -463,11 +495,28 @@ impl Message {
     }
     pub fn content(&self) -> &str {
         match self {
-            Self::User { content, .. } => &content,
-            Self::Assistant { content, .. } => &content,
-            Self::System { content, .. } => &content,
-            Self::Tool { content, .. } => &content,
-            Self::Function { content, .. } => &content,
+            Self::User { content, .. } => content,
+            Self::Assistant { content, .. } => content,
+            Self::System { content, .. } => content,
+            Self::Tool { content, .. } => content,
+            Self::Function { content, .. } => content,
+        }
+    }
+    /// The tools an assistant message calls.
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        match self {
+            Self::Assistant { tool_calls: Some(tool_calls), .. } => tool_calls,
+            _ => &[],
+        }
+    }
+    /// The `role` as sent over the wire, e.g. `assistant`.
+    pub fn role(&self) -> &'static str {
+        match self {
+            Self::System { .. } => "system",
+            Self::User { .. } => "user",
+            Self::Assistant { .. } => "assistant",
+            Self::Tool { .. } => "tool",
+            Self::Function { .. } => "function",
         }
     }
 }
@@ -483,22 +532,145 @@ pub mod internal {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+fn deserialize_optional_content<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TOOLS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Use [`Tool::function`], functions are the only kind of tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Tool {
     pub r#type: String,
     pub function: Function,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl Tool {
+    pub fn function(function: Function) -> Self {
+        Self { r#type: String::from("function"), function }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Function {
-    pub description: Option<String>,
     pub name: String,
-    pub parameters: Option<String>,
+    /// What the function does, so the model knows when and how to call it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub description: Option<String>,
+    /// A JSON Schema object describing the arguments; omitting it means an empty parameter list.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub parameters: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl Function {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), description: None, parameters: None }
+    }
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+    pub fn with_parameters(mut self, parameters: serde_json::Value) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+}
+
+/// Whether the model may, must or mustn’t call tools.
+///
+/// Serialized as `"none"`, `"auto"`, `"required"` or `{"type": "function", "function": {"name": ...}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ToolChoice {
-    
+    /// Reply with a message instead of calling a tool.
+    None,
+    /// Reply with a message or call tools, the default when tools are given.
+    Auto,
+    /// Call at least one tool.
+    Required,
+    /// Call the function with the given name.
+    Function { name: String },
+}
+
+impl ToolChoice {
+    pub fn function(name: impl Into<String>) -> Self {
+        Self::Function { name: name.into() }
+    }
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::None => serializer.serialize_str("none"),
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::Required => serializer.serialize_str("required"),
+            Self::Function { name } => {
+                serde_json::json!({ "type": "function", "function": { "name": name } }).serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ToolChoice {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct NamedFunction {
+            name: String,
+        }
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Mode(String),
+            Function { function: NamedFunction },
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Mode(mode) => match mode.as_str() {
+                "none" => Ok(Self::None),
+                "auto" => Ok(Self::Auto),
+                "required" => Ok(Self::Required),
+                _ => Err(serde::de::Error::custom(format!("unknown tool choice `{mode}`"))),
+            },
+            Repr::Function { function } => Ok(Self::Function { name: function.name }),
+        }
+    }
+}
+
+/// A call the model made, see [`Message::assistant_tool_calls`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(default = "ToolCall::default_type")]
+    pub r#type: String,
+    pub function: FunctionCall,
+}
+
+impl ToolCall {
+    fn default_type() -> String {
+        String::from("function")
+    }
+    pub fn new(id: impl Into<String>, function: FunctionCall) -> Self {
+        Self { id: id.into(), r#type: Self::default_type(), function }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON as generated by the model, which isn’t necessarily valid.
+    pub arguments: String,
+}
+
+impl FunctionCall {
+    /// The arguments as JSON, if they are valid.
+    pub fn parse_arguments(&self) -> Result<serde_json::Value, serde_json::Error> {
+        if self.arguments.trim().is_empty() {
+            return Ok(serde_json::Value::Object(Default::default()))
+        }
+        serde_json::from_str(&self.arguments)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -572,6 +744,38 @@ pub enum OpenAiModels {
     gpt_3_5_turbo_instruct,
 }
 
+impl OctoAiModels {
+    pub const ALL: &'static [Self] = &[
+        Self::llama_2_13b_chat_fp16,
+        Self::llama_2_70b_chat_fp16,
+        Self::llama_2_70b_chat_int4,
+        Self::codellama_7b_instruct_fp16,
+        Self::codellama_13b_instruct_fp16,
+        Self::codellama_34b_instruct_fp16,
+        Self::codellama_34b_instruct_int4,
+        Self::codellama_70b_instruct_fp16,
+        Self::mistral_7b_instruct_fp16,
+        Self::mixtral_8x7b_instruct_fp16,
+        Self::llamaguard_7b_fp16,
+    ];
+}
+impl OpenAiModels {
+    pub const ALL: &'static [Self] = &[
+        Self::gpt_4_0125_preview,
+        Self::gpt_4_turbo_preview,
+        Self::gpt_4_1106_preview,
+        Self::gpt_4_vision_preview,
+        Self::gpt_4,
+        Self::gpt_4_0613,
+        Self::gpt_4_32k,
+        Self::gpt_4_32k_0613,
+        Self::gpt_3_5_turbo_0125,
+        Self::gpt_3_5_turbo,
+        Self::gpt_3_5_turbo_1106,
+        Self::gpt_3_5_turbo_instruct,
+    ];
+}
+
 impl AsRef<str> for OctoAiModels {
     fn as_ref(&self) -> &str {
         match self {
@@ -28,9 +28,37 @@ pub struct Message {
     pub function_call: Option<FunctionCall>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage {
     pub completion_tokens: Integer,
     pub prompt_tokens: Integer,
     pub total_tokens: Integer,
 }
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl From<Message> for crate::request::Message {
+    fn from(message: Message) -> Self {
+        crate::request::Message::Assistant {
+            content: message.content.unwrap_or_default(),
+            name: None,
+            tool_calls: message.tool_calls.filter(|x| !x.is_empty()),
+            function_call: message.function_call,
+        }
+    }
+}
@@ -2,23 +2,40 @@
 //!
 //! Supports both streaming and regular (batch) responses.
 use serde::{Deserialize, Serialize};
-pub use super::request::{Integer, Number};
+pub use super::request::{FunctionCall, Integer, Number, ToolCall};
 
 pub mod batch;
 pub mod streaming;
 
+/// The body of an error response (or mid-stream error event).
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolCall {
-    pub index: Integer,
-    pub id: String,
-    pub r#type: String,
-    pub function: FunctionCall,
+pub struct ErrorResponse {
+    pub error: ErrorPayload,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FunctionCall {
-    pub name: String,
-    pub arguments: String,
+pub struct ErrorPayload {
+    pub message: String,
+    #[serde(default)]
+    pub r#type: Option<String>,
+    #[serde(default)]
+    pub param: Option<String>,
+    /// Some providers send a string, others a number.
+    #[serde(default)]
+    pub code: Option<serde_json::Value>,
+}
+
+impl std::fmt::Display for ErrorPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(r#type) = self.r#type.as_ref() {
+            write!(f, " (type: {type})")?;
+        }
+        if let Some(code) = self.code.as_ref() {
+            write!(f, " (code: {code})")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +9,37 @@ pub struct ResponseChunk {
     pub model: String,
     pub system_fingerprint: Option<String>,
     pub object: String,
+    /// Only set on the final chunk, and only if requested via `stream_options`.
+    #[serde(default)]
+    pub usage: Option<super::batch::Usage>,
+}
+
+impl ResponseChunk {
+    /// The text content of all choices in this chunk.
+    pub fn text(&self) -> String {
+        self.choices
+            .iter()
+            .filter_map(|x| x.delta.content.as_deref())
+            .collect()
+    }
+    /// The non-empty text content of each choice in this chunk.
+    pub fn text_deltas(&self) -> Vec<TextDelta> {
+        self.choices
+            .iter()
+            .filter_map(|x| {
+                let content = x.delta.content.clone()?;
+                Some(TextDelta { index: x.index, content })
+            })
+            .filter(|x| !x.content.is_empty())
+            .collect()
+    }
+}
+
+/// A piece of generated text for the choice at `index`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextDelta {
+    pub index: Integer,
+    pub content: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +53,79 @@ pub struct Choice {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageDelta {
     pub content: Option<String>,
-    pub function_call: Option<FunctionCall>,
-    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default)]
+    pub function_call: Option<FunctionCallDelta>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
     pub role: Option<String>,
 }
+
+/// A piece of a tool call: the first one for an `index` carries the id and
+/// the function name, the following ones more of the arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: Integer,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub r#type: Option<String>,
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCallDelta {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+/// Assembles streamed [`ToolCallDelta`]s of one choice into complete calls.
+#[derive(Debug, Clone, Default)]
+pub struct ToolCallAccumulator {
+    calls: Vec<(Integer, ToolCall)>,
+}
+
+impl ToolCallAccumulator {
+    pub fn push(&mut self, delta: &ToolCallDelta) {
+        // Some providers send every call whole at index 0, so a new id starts a new call too.
+        let position = self.calls
+            .iter()
+            .rposition(|(index, _)| *index == delta.index)
+            .filter(|position| {
+                let id = self.calls[*position].1.id.as_str();
+                delta.id.as_deref().is_none_or(|x| x.is_empty() || x == id)
+            });
+        let position = match position {
+            Some(position) => position,
+            None => {
+                let call = ToolCall::new("", FunctionCall { name: String::new(), arguments: String::new() });
+                self.calls.push((delta.index, call));
+                self.calls.len() - 1
+            }
+        };
+        let call = &mut self.calls[position].1;
+        if let Some(id) = delta.id.as_ref().filter(|x| !x.is_empty()) {
+            call.id = id.clone();
+        }
+        if let Some(r#type) = delta.r#type.as_ref() {
+            call.r#type = r#type.clone();
+        }
+        if let Some(function) = delta.function.as_ref() {
+            if let Some(name) = function.name.as_ref() {
+                call.function.name.push_str(name);
+            }
+            if let Some(arguments) = function.arguments.as_ref() {
+                call.function.arguments.push_str(arguments);
+            }
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+    /// The calls in the order they were started.
+    pub fn finish(self) -> Vec<ToolCall> {
+        self.calls.into_iter().map(|(_, call)| call).collect()
+    }
+}
@@ -0,0 +1,159 @@
+//! Retrying failed requests with exponential backoff.
+//!
+//! Only failures that are safe to retry are retried, i.e. the request never
+//! reached the model (connection errors, timeouts) or the provider asked us to
+//! come back later (`408`, `429`, `5xx`). A server supplied delay
+//! (`Retry-After`, `retry-after-ms` or the `x-ratelimit-reset-*` headers) takes
+//! precedence over the computed backoff.
+//!
+//! The client sends each request once; the caller retries with a policy,
+//! e.g. the `xml-ai` runtime, which also retries streams cut off midway.
+use std::time::{Duration, SystemTime};
+
+// ————————————————————————————————————————————————————————————————————————————
+// POLICY
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for any single delay, including server supplied ones.
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Randomizes each delay to between half and all of the computed backoff.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt, failures are returned immediately.
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+    /// Whether another attempt may follow the given (1-based) attempt.
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+    /// The delay after the given (1-based) failed attempt.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff)
+        }
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let backoff = if self.jitter {
+            backoff * (0.5 + fastrand::f64() * 0.5)
+        } else {
+            backoff
+        };
+        // E.g. a negative multiplier.
+        Duration::try_from_secs_f64(backoff.max(0.0)).unwrap_or(self.max_backoff)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// HEADERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The longest delay taken from a response header, so a bogus value can’t stall a run for good.
+pub const MAX_SERVER_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A delay of `seconds`, clamped to `0..=MAX_SERVER_DELAY`; `None` if it isn’t a number, e.g. `inf` or `NaN`.
+fn server_delay(seconds: f64) -> Option<Duration> {
+    if !seconds.is_finite() {
+        return None
+    }
+    Duration::try_from_secs_f64(seconds.clamp(0.0, MAX_SERVER_DELAY.as_secs_f64())).ok()
+}
+
+/// The delay requested by the server, if any, at most [`MAX_SERVER_DELAY`].
+///
+/// Checks `retry-after-ms`, `Retry-After` (seconds or an HTTP date) and
+/// finally the later of `x-ratelimit-reset-requests` and `x-ratelimit-reset-tokens`.
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|x| x.to_str().ok()).map(str::trim);
+    let millis = header("retry-after-ms").and_then(|x| x.parse::<f64>().ok());
+    if let Some(delay) = millis.and_then(|x| server_delay(x / 1000.0)) {
+        return Some(delay)
+    }
+    if let Some(value) = header("retry-after") {
+        if let Some(delay) = value.parse::<f64>().ok().and_then(server_delay) {
+            return Some(delay)
+        }
+        if let Ok(date) = httpdate::parse_http_date(value) {
+            return Some(date.duration_since(SystemTime::now()).unwrap_or_default().min(MAX_SERVER_DELAY))
+        }
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+        .into_iter()
+        .filter_map(|name| header(name).and_then(parse_reset_duration))
+        .max()
+}
+
+/// Parses the Go style durations used by the `x-ratelimit-reset-*` headers, e.g. `1s`, `6m0s` or `20ms`,
+/// at most [`MAX_SERVER_DELAY`].
+pub fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|x: char| !(x.is_ascii_digit() || x == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number = number.parse::<f64>().ok()?;
+        let unit_len = tail
+            .find(|x: char| x.is_ascii_digit() || x == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let scale = match unit {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" | "" => 1.0,
+            "ms" => 0.001,
+            "us" | "µs" => 0.000_001,
+            "ns" => 0.000_000_001,
+            _ => return None,
+        };
+        total += number * scale;
+        rest = tail;
+    }
+    server_delay(total)
+}
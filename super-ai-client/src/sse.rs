@@ -0,0 +1,128 @@
+//! An incremental [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation) decoder.
+//!
+//! Bytes are fed in as they arrive from the network; events are only emitted
+//! once complete, so lines (and UTF-8 sequences) split across chunks are
+//! buffered until the rest arrives.
+
+// ————————————————————————————————————————————————————————————————————————————
+// EVENTS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerSentEvent {
+    /// The `event:` field, if any.
+    pub event: Option<String>,
+    /// All `data:` lines of the event joined by `\n`.
+    pub data: String,
+    /// The last `id:` seen on the stream (ids persist across events).
+    pub id: Option<String>,
+    /// The `retry:` reconnection time in milliseconds, if any.
+    pub retry: Option<u64>,
+}
+
+impl ServerSentEvent {
+    /// The terminal sentinel sent by OpenAI compatible APIs.
+    pub const DONE: &'static str = "[DONE]";
+
+    pub fn is_done(&self) -> bool {
+        self.data.trim() == Self::DONE
+    }
+    pub fn is_error(&self) -> bool {
+        self.event.as_deref() == Some("error")
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// DECODER
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Default)]
+pub struct SseDecoder {
+    /// Bytes of the current, incomplete line.
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    last_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Feeds the next chunk of the response body, returning all events completed by it.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<ServerSentEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut index = 0;
+        while index < self.buffer.len() {
+            let end = match self.buffer[index] {
+                b'\n' => index + 1,
+                // A trailing `\r` may be the first half of a `\r\n` split across chunks.
+                b'\r' if index + 1 == self.buffer.len() => break,
+                b'\r' if self.buffer[index + 1] == b'\n' => index + 2,
+                b'\r' => index + 1,
+                _ => {
+                    index += 1;
+                    continue
+                }
+            };
+            let line = String::from_utf8_lossy(&self.buffer[start..index]).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+            start = end;
+            index = end;
+        }
+        self.buffer.drain(..start);
+        events
+    }
+    /// Flushes a final event that wasn’t terminated by a blank line.
+    ///
+    /// Strictly speaking such an event should be discarded, but several
+    /// OpenAI compatible servers close the stream right after the last `data:` line.
+    pub fn finish(&mut self) -> Option<ServerSentEvent> {
+        let buffer = std::mem::take(&mut self.buffer);
+        let line = String::from_utf8_lossy(&buffer);
+        let line = line.trim_end_matches('\r');
+        if !line.is_empty() {
+            self.process_line(line);
+        }
+        self.dispatch()
+    }
+    fn process_line(&mut self, line: &str) -> Option<ServerSentEvent> {
+        if line.is_empty() {
+            return self.dispatch()
+        }
+        if line.starts_with(':') {
+            // Comment, e.g. `: keep-alive`.
+            return None
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse::<u64>() {
+                    self.retry = Some(retry);
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+    fn dispatch(&mut self) -> Option<ServerSentEvent> {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        if self.data.is_empty() {
+            return None
+        }
+        let data = std::mem::take(&mut self.data).join("\n");
+        Some(ServerSentEvent { event, data, id: self.last_id.clone(), retry })
+    }
+}
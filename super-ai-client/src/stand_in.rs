@@ -0,0 +1,385 @@
+//! A local stand-in for an OpenAI compatible server, so clients can be tested without network.
+//!
+//! It speaks just enough HTTP/1.1 to answer `POST …/chat/completions`, as a
+//! single JSON response or as a stream of server-sent events when the request
+//! asks for `stream`. Replies are scripted via [`StandInServer::push_reply`];
+//! once the script is exhausted the last message is echoed back.
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::request::ToolCall;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StandInReply {
+    /// A successful completion with the given content.
+    Text(String),
+    /// An error response, e.g. `429` to exercise retries.
+    Status { status: u16, message: String, headers: Vec<(String, String)> },
+    /// Streams the given content, then closes the connection without finishing the stream.
+    Interrupted(String),
+    /// Calls the given tools instead of replying, streamed with the arguments split across chunks.
+    ToolCalls(Vec<ToolCall>),
+    /// Several choices, e.g. for a request with `n`, streamed with their chunks interleaved.
+    Choices(Vec<String>),
+}
+
+impl StandInReply {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self::Text(content.into())
+    }
+    pub fn choices(contents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::Choices(contents.into_iter().map(Into::into).collect())
+    }
+    pub fn status(status: u16, message: impl Into<String>) -> Self {
+        Self::Status { status, message: message.into(), headers: Vec::new() }
+    }
+    /// Adds a response header to a [`StandInReply::Status`], e.g. `Retry-After`.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        if let Self::Status { headers, .. } = &mut self {
+            headers.push((name.into(), value.into()));
+        }
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    replies: VecDeque<StandInReply>,
+    /// The JSON bodies received so far, in order.
+    requests: Vec<Value>,
+    /// The `Authorization` header of each request, in order.
+    authorizations: Vec<Option<String>>,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SERVER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Listens on a random local port until dropped.
+#[derive(Debug)]
+pub struct StandInServer {
+    address: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl StandInServer {
+    /// Must be called from within a Tokio runtime.
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, state.clone()));
+                }
+            }
+        });
+        Ok(Self { address, state, task })
+    }
+    /// E.g. for [`crate::client::ClientBuilder::with_base_url`] or `--base-url`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v1", self.address)
+    }
+    /// Queues a reply for the next request that isn’t answered by an earlier one.
+    pub fn push_reply(&self, reply: StandInReply) {
+        self.state().replies.push_back(reply);
+    }
+    pub fn with_reply(self, reply: StandInReply) -> Self {
+        self.push_reply(reply);
+        self
+    }
+    pub fn with_replies(self, replies: impl IntoIterator<Item = StandInReply>) -> Self {
+        self.state().replies.extend(replies);
+        self
+    }
+    /// The JSON bodies of the requests received so far.
+    pub fn requests(&self) -> Vec<Value> {
+        self.state().requests.clone()
+    }
+    /// The `Authorization` header of each request received so far, if any.
+    pub fn authorizations(&self) -> Vec<Option<String>> {
+        self.state().authorizations.clone()
+    }
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|x| x.into_inner())
+    }
+}
+
+impl Drop for StandInServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// PROTOCOL
+// ————————————————————————————————————————————————————————————————————————————
+
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let Some((authorization, body)) = read_request(&mut stream).await else {
+        let _ = respond(&mut stream, 400, "application/json", &error_body("malformed request"), &[]).await;
+        return
+    };
+    let Ok(request) = serde_json::from_slice::<Value>(&body) else {
+        let _ = respond(&mut stream, 400, "application/json", &error_body("the body isn’t JSON"), &[]).await;
+        return
+    };
+    let reply = {
+        let mut state = state.lock().unwrap_or_else(|x| x.into_inner());
+        state.requests.push(request.clone());
+        state.authorizations.push(authorization);
+        state.replies.pop_front()
+    };
+    let reply = reply.unwrap_or_else(|| StandInReply::Text(last_message(&request)));
+    let _ = match reply {
+        StandInReply::Text(content) if is_streaming(&request) => {
+            stream_reply(&mut stream, &request, &[content], true).await
+        }
+        StandInReply::Text(content) => {
+            let body = completion(&request, &content).to_string();
+            respond(&mut stream, 200, "application/json", &body, &[]).await
+        }
+        StandInReply::Interrupted(content) => stream_reply(&mut stream, &request, &[content], false).await,
+        StandInReply::Choices(contents) if is_streaming(&request) => {
+            stream_reply(&mut stream, &request, &contents, true).await
+        }
+        StandInReply::Choices(contents) => {
+            let mut body = completion(&request, &contents.join(" "));
+            body["choices"] = contents
+                .iter()
+                .enumerate()
+                .map(|(index, content)| json!({
+                    "index": index,
+                    "message": { "role": "assistant", "content": content },
+                    "finish_reason": "stop",
+                    "logprobs": null,
+                }))
+                .collect();
+            respond(&mut stream, 200, "application/json", &body.to_string(), &[]).await
+        }
+        StandInReply::ToolCalls(tool_calls) if is_streaming(&request) => {
+            stream_tool_calls(&mut stream, &request, &tool_calls).await
+        }
+        StandInReply::ToolCalls(tool_calls) => {
+            let mut body = completion(&request, "");
+            body["choices"][0]["message"] = json!({ "role": "assistant", "content": null, "tool_calls": tool_calls });
+            body["choices"][0]["finish_reason"] = json!("tool_calls");
+            respond(&mut stream, 200, "application/json", &body.to_string(), &[]).await
+        }
+        StandInReply::Status { status, message, headers } => {
+            respond(&mut stream, status, "application/json", &error_body(&message), &headers).await
+        }
+    };
+}
+
+/// The body of a `POST`, or `None` if the request can’t be read.
+/// The `Authorization` header, if any, and the body.
+async fn read_request(stream: &mut TcpStream) -> Option<(Option<String>, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(position) = buffer.windows(4).position(|x| x == b"\r\n\r\n") {
+            break position + 4
+        }
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let header = |header: &str| {
+        head.lines()
+            .filter_map(|x| x.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case(header))
+            .map(|(_, value)| value.trim().to_string())
+    };
+    let content_length = header("content-length")
+        .and_then(|x| x.parse::<usize>().ok())
+        .unwrap_or(0);
+    let authorization = header("authorization");
+    while buffer.len() < header_end + content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    Some((authorization, buffer[header_end..header_end + content_length].to_vec()))
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &str,
+    headers: &[(String, String)],
+) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {status} {}\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n",
+        reason(status),
+        body.len(),
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Sends each choice word by word, a word of each in turn, followed by usage (if requested)
+/// and `[DONE]` unless `finish` is false.
+async fn stream_reply(stream: &mut TcpStream, request: &Value, contents: &[String], finish: bool) -> std::io::Result<()> {
+    let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\nconnection: close\r\n\r\n";
+    stream.write_all(head.as_bytes()).await?;
+    let model = request["model"].as_str().unwrap_or_default();
+    let deltas = contents.iter().map(|x| x.split_inclusive(' ').collect::<Vec<_>>()).collect::<Vec<_>>();
+    let rounds = deltas.iter().map(Vec::len).max().unwrap_or(0);
+    for round in 0..rounds {
+        for (choice, delta) in deltas.iter().enumerate().filter_map(|(choice, x)| Some((choice, x.get(round)?))) {
+            let mut delta = json!({ "content": delta });
+            if round == 0 {
+                delta["role"] = json!("assistant");
+            }
+            let chunk = chunk(model, json!([{ "index": choice, "delta": delta, "finish_reason": null }]), None);
+            stream.write_all(format!("data: {chunk}\n\n").as_bytes()).await?;
+            stream.flush().await?;
+        }
+    }
+    if !finish {
+        return stream.shutdown().await
+    }
+    let choices = (0..contents.len()).map(|index| json!({ "index": index, "delta": {}, "finish_reason": "stop" })).collect();
+    stream.write_all(format!("data: {}\n\n", chunk(model, choices, None)).as_bytes()).await?;
+    if request["stream_options"]["include_usage"].as_bool() == Some(true) {
+        let usage = usage(request, &contents.join(" "));
+        stream.write_all(format!("data: {}\n\n", chunk(model, json!([]), Some(usage))).as_bytes()).await?;
+    }
+    stream.write_all(b"data: [DONE]\n\n").await?;
+    stream.shutdown().await
+}
+
+/// Sends each call’s id and name first, then its arguments in two halves, like OpenAI does.
+async fn stream_tool_calls(stream: &mut TcpStream, request: &Value, tool_calls: &[ToolCall]) -> std::io::Result<()> {
+    let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\nconnection: close\r\n\r\n";
+    stream.write_all(head.as_bytes()).await?;
+    let model = request["model"].as_str().unwrap_or_default();
+    for (index, call) in tool_calls.iter().enumerate() {
+        let arguments = call.function.arguments.as_str();
+        let middle = arguments.char_indices().map(|(x, _)| x).nth(arguments.chars().count() / 2).unwrap_or(0);
+        let mut deltas = vec![json!({
+            "index": index,
+            "id": call.id,
+            "type": call.r#type,
+            "function": { "name": call.function.name, "arguments": "" },
+        })];
+        for part in [&arguments[..middle], &arguments[middle..]] {
+            deltas.push(json!({ "index": index, "function": { "arguments": part } }));
+        }
+        for delta in deltas {
+            let mut delta = json!({ "tool_calls": [delta] });
+            if index == 0 && delta["tool_calls"][0].get("id").is_some() {
+                delta["role"] = json!("assistant");
+            }
+            let chunk = chunk(model, json!([{ "index": 0, "delta": delta, "finish_reason": null }]), None);
+            stream.write_all(format!("data: {chunk}\n\n").as_bytes()).await?;
+            stream.flush().await?;
+        }
+    }
+    let choices = json!([{ "index": 0, "delta": {}, "finish_reason": "tool_calls" }]);
+    stream.write_all(format!("data: {}\n\n", chunk(model, choices, None)).as_bytes()).await?;
+    stream.write_all(b"data: [DONE]\n\n").await?;
+    stream.shutdown().await
+}
+
+fn chunk(model: &str, choices: Value, usage: Option<Value>) -> Value {
+    json!({
+        "id": "chatcmpl-stand-in",
+        "object": "chat.completion.chunk",
+        "created": 0,
+        "model": model,
+        "system_fingerprint": null,
+        "choices": choices,
+        "usage": usage,
+    })
+}
+
+fn completion(request: &Value, content: &str) -> Value {
+    json!({
+        "id": "chatcmpl-stand-in",
+        "object": "chat.completion",
+        "created": 0,
+        "model": request["model"].as_str().unwrap_or_default(),
+        "system_fingerprint": null,
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop",
+            "logprobs": null,
+        }],
+        "usage": usage(request, content),
+    })
+}
+
+/// Deterministic counts, one token per word.
+fn usage(request: &Value, content: &str) -> Value {
+    let prompt_tokens = request["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x["content"].as_str())
+        .map(|x| x.split_whitespace().count())
+        .sum::<usize>();
+    let completion_tokens = content.split_whitespace().count();
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    })
+}
+
+fn is_streaming(request: &Value) -> bool {
+    request["stream"].as_bool() == Some(true)
+}
+
+fn last_message(request: &Value) -> String {
+    request["messages"]
+        .as_array()
+        .and_then(|x| x.last())
+        .and_then(|x| x["content"].as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn error_body(message: &str) -> String {
+    json!({ "error": { "message": message, "type": "stand_in_error" } }).to_string()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,97 @@
+//! Recording exchanges with the stand-in server and replaying them without it.
+use std::path::PathBuf;
+
+use super_ai_client::cassette::{Cassette, Chunk};
+use super_ai_client::client::{ClientBuilder, ClientError};
+use super_ai_client::request::{Message, RequestBuilder};
+use super_ai_client::stand_in::{StandInReply, StandInServer};
+
+fn cassette_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("super-ai-client-{name}-{}.json", std::process::id()))
+}
+
+fn client(base_url: &str, question: &str, cassette: Cassette) -> ClientBuilder {
+    let request_body = RequestBuilder::default()
+        .with_model("stand-in")
+        .with_message(Message::user(question))
+        .with_stream(true);
+    ClientBuilder::default()
+        .with_base_url(base_url)
+        .with_request_body(request_body)
+        .with_cassette(cassette)
+}
+
+#[tokio::test]
+async fn replays_streams_chunk_by_chunk() {
+    let path = cassette_path("streams");
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::status(503, "overloaded"),
+        StandInReply::text("a streamed answer"),
+    ]);
+    let base_url = server.base_url();
+    let recording = Cassette::record(&path);
+    // The client sends once, so the failed attempt and its retry are two calls.
+    let error = client(&base_url, "question", recording.clone())
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap_err();
+    assert!(error.is_retryable(), "{error}");
+    let recorded = client(&base_url, "question", recording)
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap();
+    drop(server);
+    let cassette = Cassette::replay(&path).unwrap();
+    let interactions = cassette.interactions();
+    assert_eq!(interactions.len(), 2);
+    assert_eq!(interactions[0].response.status, 503);
+    assert!(interactions[1].response.error.is_none());
+    assert!(matches!(&interactions[1].response.chunks[0], Chunk::Text(x) if x.starts_with("data: ")));
+    // The server is gone, so everything comes from the cassette, including the failed attempt.
+    let error = client(&base_url, "question", cassette.clone())
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap_err();
+    assert!(matches!(&error, ClientError::Status(x) if x.status == 503), "{error}");
+    let replayed = client(&base_url, "question", cassette.clone())
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap();
+    assert_eq!(replayed.content(0), recorded.content(0));
+    assert_eq!(replayed.len(), recorded.len());
+    // Each recorded exchange is used once.
+    let error = client(&base_url, "question", cassette)
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap_err();
+    assert!(matches!(error, ClientError::Unrecorded { .. }), "{error}");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn replays_interrupted_streams() {
+    let path = cassette_path("interrupted");
+    let server = StandInServer::start().await.unwrap().with_replies([StandInReply::Interrupted("cut off".into())]);
+    let recorded = client(&server.base_url(), "question", Cassette::record(&path))
+        .build_streaming_api_call().unwrap()
+        .execute_async().await;
+    assert!(recorded.is_err());
+    let cassette = Cassette::replay(&path).unwrap();
+    let replayed = client(&server.base_url(), "question", cassette)
+        .build_streaming_api_call().unwrap()
+        .execute_async().await;
+    assert!(replayed.is_err());
+    assert_eq!(server.requests().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn unrecorded_requests_fail() {
+    let path = cassette_path("unrecorded");
+    let server = StandInServer::start().await.unwrap();
+    client(&server.base_url(), "question", Cassette::record(&path))
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap();
+    let error = client(&server.base_url(), "another question", Cassette::replay(&path).unwrap())
+        .build_streaming_api_call().unwrap()
+        .execute_async().await.unwrap_err();
+    assert!(matches!(error, ClientError::Unrecorded { .. }), "{error}");
+    assert!(!error.is_retryable());
+    assert_eq!(server.requests().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,68 @@
+//! How many requests the concurrency limiter lets through at once.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super_ai_client::limit::ConcurrencyLimiter;
+use super_ai_client::provider::{ChatProvider, OpenAiCompatibleProvider};
+
+fn provider(name: &str, max_concurrency: Option<usize>) -> Arc<dyn ChatProvider> {
+    let mut provider = OpenAiCompatibleProvider::custom(name, "http://localhost:8080/v1");
+    provider.config.max_concurrency = max_concurrency;
+    Arc::new(provider)
+}
+
+/// Sends `requests` requests to each provider at once, each taking a second,
+/// returning the most that were in flight at the same time per provider and overall.
+async fn peaks(limiter: ConcurrencyLimiter, providers: &[Arc<dyn ChatProvider>], requests: usize) -> (Vec<usize>, usize) {
+    let total = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+    let counters = providers.iter().map(|_| Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)))).collect::<Vec<_>>();
+    let mut tasks = Vec::new();
+    for (provider, counter) in providers.iter().zip(counters.iter()) {
+        for _ in 0..requests {
+            let (limiter, provider, counter, total) = (limiter.clone(), provider.clone(), counter.clone(), total.clone());
+            tasks.push(tokio::spawn(async move {
+                let _permit = limiter.acquire(provider.as_ref()).await;
+                for (in_flight, peak) in [(&counter.0, &counter.1), (&total.0, &total.1)] {
+                    peak.fetch_max(in_flight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                counter.0.fetch_sub(1, Ordering::SeqCst);
+                total.0.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+    let peaks = counters.iter().map(|x| x.1.load(Ordering::SeqCst)).collect();
+    (peaks, total.1.load(Ordering::SeqCst))
+}
+
+#[tokio::test(start_paused = true)]
+async fn providers_are_capped_separately() {
+    let providers = [provider("a", None), provider("b", Some(3)), provider("c", None)];
+    let limiter = ConcurrencyLimiter::new().with_provider_limit("a", 2);
+    let started = tokio::time::Instant::now();
+    let (peaks, total) = peaks(limiter, &providers, 6).await;
+    // `a` via the limiter, `b` via its own `max-concurrency`, `c` is unlimited.
+    assert_eq!(peaks, [2, 3, 6]);
+    assert_eq!(total, 11);
+    // Six requests to `a`, two at a time.
+    assert_eq!(started.elapsed().as_secs(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn limiter_limits_take_precedence() {
+    let limiter = ConcurrencyLimiter::new().with_provider_limit("b", 1);
+    let (peaks, _) = peaks(limiter, &[provider("b", Some(3))], 4).await;
+    assert_eq!(peaks, [1]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn global_limit_spans_providers() {
+    let limiter = ConcurrencyLimiter::new().with_global_limit(3).with_provider_limit("a", 2);
+    let (peaks, total) = peaks(limiter, &[provider("a", None), provider("b", None)], 4).await;
+    assert_eq!(total, 3);
+    assert!(peaks[0] <= 2 && peaks[1] <= 3, "{peaks:?}");
+}
@@ -0,0 +1,113 @@
+//! Pacing requests with the token buckets of the rate limiter, on tokio’s paused clock.
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderValue};
+use super_ai_client::ratelimit::{RateLimit, RateLimiter};
+use tokio::time::Instant;
+
+fn limiter(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> RateLimiter {
+    RateLimiter::new().with_limit("openai", None, RateLimit { requests_per_minute, tokens_per_minute })
+}
+
+/// How long acquiring `tokens` took, to the millisecond.
+async fn acquire(limiter: &RateLimiter, tokens: u32) -> Duration {
+    let start = Instant::now();
+    limiter.acquire("openai", "gpt-4o", tokens).await;
+    Duration::from_millis(start.elapsed().as_millis() as u64)
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_burst_up_to_the_limit() {
+    let limiter = limiter(Some(3), None);
+    for _ in 0..3 {
+        assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+    }
+    // One request every 20 seconds once the burst is spent.
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_secs(20));
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_secs(20));
+}
+
+#[tokio::test(start_paused = true)]
+async fn buckets_refill_over_time() {
+    let limiter = limiter(Some(60), None);
+    for _ in 0..60 {
+        assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+    }
+    tokio::time::advance(Duration::from_secs(5)).await;
+    for _ in 0..5 {
+        assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+    }
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_secs(1));
+    // Never beyond the capacity, however long it’s idle.
+    tokio::time::advance(Duration::from_secs(600)).await;
+    for _ in 0..60 {
+        assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+    }
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_secs(1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn waiting_on_an_empty_bucket() {
+    let limiter = limiter(None, Some(600));
+    assert_eq!(acquire(&limiter, 600).await, Duration::ZERO);
+    // Ten tokens a second.
+    assert_eq!(acquire(&limiter, 100).await, Duration::from_secs(10));
+    // More than the capacity waits for a full bucket rather than forever.
+    assert_eq!(acquire(&limiter, 10_000).await, Duration::from_secs(60));
+    // Other models have buckets of their own.
+    let start = Instant::now();
+    limiter.acquire("openai", "gpt-4o-mini", 600).await;
+    assert_eq!(start.elapsed(), Duration::ZERO);
+}
+
+#[tokio::test(start_paused = true)]
+async fn both_buckets_must_have_room() {
+    let limiter = limiter(Some(60), Some(60));
+    assert_eq!(acquire(&limiter, 60).await, Duration::ZERO);
+    // Plenty of requests left, but the tokens take a minute to come back.
+    assert_eq!(acquire(&limiter, 60).await, Duration::from_secs(60));
+    assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+}
+
+#[tokio::test(start_paused = true)]
+async fn headers_correct_the_buckets() {
+    // Nothing configured, the server’s limits apply.
+    let limiter = RateLimiter::new();
+    assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+    let mut headers = HeaderMap::new();
+    for (name, value) in [("x-ratelimit-limit-requests", "100"), ("x-ratelimit-remaining-requests", "0"), ("x-ratelimit-reset-requests", "2s")] {
+        headers.insert(name, HeaderValue::from_static(value));
+    }
+    limiter.update("openai", "gpt-4o", &headers);
+    // A hundred requests back within two seconds, one every 20 milliseconds.
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_millis(20));
+    // Only for the model the response came from.
+    let start = Instant::now();
+    limiter.acquire("openai", "gpt-4o-mini", 0).await;
+    assert_eq!(start.elapsed(), Duration::ZERO);
+}
+
+#[tokio::test(start_paused = true)]
+async fn malformed_headers_are_ignored() {
+    let headers = |limit: &'static str, remaining: &'static str, reset: &'static str| {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [("x-ratelimit-limit-requests", limit), ("x-ratelimit-remaining-requests", remaining), ("x-ratelimit-reset-requests", reset)] {
+            headers.insert(name, HeaderValue::from_static(value));
+        }
+        headers
+    };
+    let limiter = limiter(Some(60), None);
+    for (limit, remaining) in [("0", "0"), ("-1", "0"), ("NaN", "0"), ("inf", "0"), ("100", "NaN"), ("100", "-inf"), ("100", "-1")] {
+        limiter.update("openai", "gpt-4o", &headers(limit, remaining, "1s"));
+        // Nor does it add a bucket where none was configured.
+        RateLimiter::new().update("openai", "gpt-4o", &headers(limit, remaining, "1s"));
+    }
+    // Still the configured 60 a minute.
+    for _ in 0..60 {
+        assert_eq!(acquire(&limiter, 0).await, Duration::ZERO);
+    }
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_secs(1));
+    // A reset of zero falls back to refilling the limit over a minute.
+    limiter.update("openai", "gpt-4o", &headers("6", "0", "0s"));
+    assert_eq!(acquire(&limiter, 0).await, Duration::from_secs(10));
+}
@@ -0,0 +1,133 @@
+//! Which failures are retried and how long to wait before the next attempt.
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::{HeaderMap, HeaderValue};
+use super_ai_client::client::{ClientError, StatusError};
+use super_ai_client::response::ErrorPayload;
+use super_ai_client::retry::{MAX_SERVER_DELAY, RetryPolicy, parse_reset_duration, retry_after};
+
+fn status(status: u16, retry_after: Option<Duration>) -> ClientError {
+    ClientError::Status(Box::new(StatusError {
+        status,
+        kind: None,
+        payload: None,
+        body: String::new(),
+        retry_after,
+    }))
+}
+
+fn api(kind: &str) -> ClientError {
+    ClientError::Api(Box::new(ErrorPayload {
+        message: String::from("failed"),
+        r#type: Some(kind.to_string()),
+        param: None,
+        code: None,
+    }))
+}
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn retryable_failures() {
+    let retryable = [408, 429, 500, 502, 503, 599].map(|x| status(x, None).is_retryable());
+    assert_eq!(retryable, [true; 6]);
+    // A conflict or an invalid request fails the same way when sent again.
+    let permanent = [400, 401, 403, 404, 409, 422].map(|x| status(x, None).is_retryable());
+    assert_eq!(permanent, [false; 6]);
+    assert!(api("server_error").is_retryable());
+    assert!(api("rate_limit_exceeded").is_retryable());
+    assert!(!api("invalid_request_error").is_retryable());
+    assert!(ClientError::UnexpectedEnd.is_retryable());
+    assert!(!ClientError::Decode { body: String::from("{"), error: String::from("EOF") }.is_retryable());
+}
+
+#[test]
+fn requested_delays() {
+    assert_eq!(status(429, Some(Duration::from_secs(3))).retry_after(), Some(Duration::from_secs(3)));
+    assert_eq!(status(503, None).retry_after(), None);
+    assert_eq!(ClientError::UnexpectedEnd.retry_after(), None);
+}
+
+#[test]
+fn retry_after_headers() {
+    assert_eq!(retry_after(&headers(&[])), None);
+    assert_eq!(retry_after(&headers(&[("retry-after", "2")])), Some(Duration::from_secs(2)));
+    assert_eq!(retry_after(&headers(&[("retry-after", "1.5")])), Some(Duration::from_millis(1500)));
+    // `retry-after-ms` is the more precise one.
+    let both = headers(&[("retry-after-ms", "250"), ("retry-after", "2")]);
+    assert_eq!(retry_after(&both), Some(Duration::from_millis(250)));
+    // An HTTP date, in the past is as good as now.
+    let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+    let delay = retry_after(&headers(&[("retry-after", &later)])).unwrap();
+    assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(120), "{delay:?}");
+    let earlier = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(120));
+    assert_eq!(retry_after(&headers(&[("retry-after", &earlier)])), Some(Duration::ZERO));
+    // Otherwise the later of the rate limit resets.
+    let resets = headers(&[("x-ratelimit-reset-requests", "1s"), ("x-ratelimit-reset-tokens", "6m0s")]);
+    assert_eq!(retry_after(&resets), Some(Duration::from_secs(360)));
+    let invalid = headers(&[("retry-after", "soon"), ("x-ratelimit-reset-tokens", "20ms")]);
+    assert_eq!(retry_after(&invalid), Some(Duration::from_millis(20)));
+}
+
+#[test]
+fn reset_durations() {
+    assert_eq!(parse_reset_duration("1s"), Some(Duration::from_secs(1)));
+    assert_eq!(parse_reset_duration("6m0s"), Some(Duration::from_secs(360)));
+    assert_eq!(parse_reset_duration("20ms"), Some(Duration::from_millis(20)));
+    assert_eq!(parse_reset_duration("1h2m3.5s"), Some(Duration::from_millis(3_723_500)));
+    assert_eq!(parse_reset_duration(" 7 "), Some(Duration::from_secs(7)));
+    assert_eq!(parse_reset_duration("150us"), Some(Duration::from_micros(150)));
+    assert_eq!(parse_reset_duration(""), None);
+    assert_eq!(parse_reset_duration("soon"), None);
+    assert_eq!(parse_reset_duration("5d"), None);
+}
+
+#[test]
+fn exponential_backoff() {
+    let policy = RetryPolicy::default()
+        .with_initial_backoff(Duration::from_secs(1))
+        .with_max_backoff(Duration::from_secs(10))
+        .with_jitter(false);
+    let delays = (1..=5).map(|x| policy.delay(x, None).as_secs()).collect::<Vec<_>>();
+    assert_eq!(delays, [1, 2, 4, 8, 10]);
+    // A requested delay wins, up to the maximum.
+    assert_eq!(policy.delay(1, Some(Duration::from_secs(5))), Duration::from_secs(5));
+    assert_eq!(policy.delay(1, Some(Duration::from_secs(60))), Duration::from_secs(10));
+    let jittered = policy.with_jitter(true);
+    for _ in 0..20 {
+        let delay = jittered.delay(3, None);
+        assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4), "{delay:?}");
+    }
+    assert!(RetryPolicy::default().with_max_attempts(2).should_retry(1));
+    assert!(!RetryPolicy::default().with_max_attempts(2).should_retry(2));
+    assert!(!RetryPolicy::none().should_retry(1));
+}
+
+#[test]
+fn malformed_delays() {
+    // Not numbers, so the next header is used.
+    for value in ["inf", "-inf", "NaN"] {
+        assert_eq!(retry_after(&headers(&[("retry-after-ms", value), ("retry-after", "2")])), Some(Duration::from_secs(2)), "{value}");
+        assert_eq!(retry_after(&headers(&[("retry-after", value), ("x-ratelimit-reset-tokens", "1s")])), Some(Duration::from_secs(1)), "{value}");
+    }
+    assert_eq!(retry_after(&headers(&[("retry-after", "-5")])), Some(Duration::ZERO));
+    // Too long to be meant, or to fit a `Duration`.
+    assert_eq!(retry_after(&headers(&[("retry-after", "1e300")])), Some(MAX_SERVER_DELAY));
+    assert_eq!(retry_after(&headers(&[("retry-after-ms", "1e300")])), Some(MAX_SERVER_DELAY));
+    let far = "9".repeat(400);
+    assert_eq!(parse_reset_duration(&format!("{far}h")), None);
+    assert_eq!(parse_reset_duration("99999999999999999999h"), Some(MAX_SERVER_DELAY));
+    let distant = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(400 * 24 * 60 * 60));
+    assert_eq!(retry_after(&headers(&[("retry-after", &distant)])), Some(MAX_SERVER_DELAY));
+    // Nor does a policy with odd settings fail.
+    let policy = RetryPolicy::default().with_max_backoff(Duration::MAX).with_jitter(false);
+    assert_eq!(policy.clone().with_multiplier(-3.0).delay(2, None), Duration::ZERO);
+    assert_eq!(policy.clone().with_multiplier(f64::NAN).delay(2, None), Duration::MAX);
+    assert_eq!(policy.with_multiplier(f64::INFINITY).delay(2, None), Duration::MAX);
+}
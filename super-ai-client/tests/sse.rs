@@ -0,0 +1,79 @@
+//! Decoding server-sent events from a response body that arrives in arbitrary chunks.
+use super_ai_client::sse::{ServerSentEvent, SseDecoder};
+
+/// Feeds `chunks` in order, collecting every event including a final unterminated one.
+fn decode<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<ServerSentEvent> {
+    let mut decoder = SseDecoder::new();
+    let mut events = chunks.into_iter().flat_map(|x| decoder.push(x)).collect::<Vec<_>>();
+    events.extend(decoder.finish());
+    events
+}
+
+fn data(events: &[ServerSentEvent]) -> Vec<&str> {
+    events.iter().map(|x| x.data.as_str()).collect()
+}
+
+#[test]
+fn events_split_across_chunks() {
+    let mut decoder = SseDecoder::new();
+    assert_eq!(decoder.push(b"data: {\"a\""), []);
+    assert_eq!(decoder.push(b": 1}\n"), []);
+    let events = decoder.push(b"\ndata: {\"b\": 2}\n\nda");
+    assert_eq!(data(&events), ["{\"a\": 1}", "{\"b\": 2}"]);
+    assert_eq!(data(&decoder.push(b"ta: c\n\n")), ["c"]);
+    // Every possible split point of a stream yields the same events.
+    let stream = b"event: message\ndata: one\n\ndata: two\n\n";
+    for split in 0..stream.len() {
+        let events = decode([&stream[..split], &stream[split..]]);
+        assert_eq!(data(&events), ["one", "two"], "split at {split}");
+        assert_eq!(events[0].event.as_deref(), Some("message"));
+        assert_eq!(events[1].event, None);
+    }
+}
+
+#[test]
+fn utf8_sequences_split_across_chunks() {
+    let stream = "data: héllo ✓ 👋\n\n".as_bytes();
+    let bytes = stream.iter().map(std::slice::from_ref);
+    assert_eq!(data(&decode(bytes)), ["héllo ✓ 👋"]);
+}
+
+#[test]
+fn line_endings() {
+    assert_eq!(data(&decode([b"data: a\r\n\r\ndata: b\r\n\r\n".as_slice()])), ["a", "b"]);
+    // A `\r\n` split across chunks is a single line break, not an empty line.
+    assert_eq!(data(&decode([b"data: a\r".as_slice(), b"\ndata: b\r", b"\n\r", b"\n"])), ["a\nb"]);
+    // A lone `\r` ends a line as well.
+    assert_eq!(data(&decode([b"data: a\r\rdata: b\n\n".as_slice()])), ["a", "b"]);
+}
+
+#[test]
+fn multi_line_data() {
+    let events = decode([b"data: one\ndata:two\ndata\ndata:  three\n\n".as_slice()]);
+    // Only a single leading space is part of the field syntax.
+    assert_eq!(data(&events), ["one\ntwo\n\n three"]);
+}
+
+#[test]
+fn comments_and_fields() {
+    let stream = b": keep-alive\n\nid: 7\nretry: 1500\n: between lines\ndata: a\n\ndata: b\nunknown: x\n\n";
+    let events = decode([stream.as_slice()]);
+    assert_eq!(data(&events), ["a", "b"]);
+    // Ids persist across events, `retry` doesn’t.
+    assert_eq!((events[0].id.as_deref(), events[0].retry), (Some("7"), Some(1500)));
+    assert_eq!((events[1].id.as_deref(), events[1].retry), (Some("7"), None));
+}
+
+#[test]
+fn done_and_errors() {
+    let events = decode([b"data: {}\n\nevent: error\ndata: {\"error\": {}}\n\ndata: [DONE]\n\n".as_slice()]);
+    assert_eq!(events.iter().map(|x| (x.is_error(), x.is_done())).collect::<Vec<_>>(), [(false, false), (true, false), (false, true)]);
+}
+
+#[test]
+fn unterminated_final_event() {
+    let mut decoder = SseDecoder::new();
+    assert_eq!(decoder.push(b"data: [DONE]"), []);
+    assert!(decoder.finish().unwrap().is_done());
+    assert_eq!(SseDecoder::new().finish(), None);
+}
@@ -0,0 +1,198 @@
+//! The client against a local stand-in server, see `super_ai_client::stand_in`.
+use std::time::Duration;
+
+use futures::StreamExt;
+use super_ai_client::client::{ClientBuilder, ClientError, HttpClient, InvalidConfiguration};
+use serde_json::json;
+use super_ai_client::request::{Function, FunctionCall, Message, RequestBuilder, Tool, ToolCall, ToolChoice};
+use super_ai_client::stand_in::{StandInReply, StandInServer};
+
+fn client(server: &StandInServer, stream: bool) -> ClientBuilder {
+    let request_body = RequestBuilder::default()
+        .with_model("stand-in")
+        .with_message(Message::user("Hello there"))
+        .with_stream(stream);
+    ClientBuilder::default()
+        .with_base_url(server.base_url())
+        .with_request_body(request_body)
+}
+
+#[tokio::test]
+async fn batch_response() {
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::text("General Kenobi"));
+    let response = client(&server, false).build_batch_api_call().unwrap().execute_async().await.unwrap();
+    assert_eq!(response.choices[0].message.content.as_deref(), Some("General Kenobi"));
+    assert_eq!(response.usage.completion_tokens, 2);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["model"], "stand-in");
+    assert_eq!(requests[0]["messages"][0]["content"], "Hello there");
+}
+
+#[tokio::test]
+async fn streamed_response_with_usage() {
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::text("one two three"));
+    let output = client(&server, true).build_streaming_api_call().unwrap().execute_async().await.unwrap();
+    assert!(output.len() > 3);
+    assert_eq!(output.content(0).as_deref(), Some("one two three"));
+    let usage = output.usage().expect("usage is requested via `stream_options`");
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens), (2, 3));
+}
+
+#[tokio::test]
+async fn clients_share_an_http_client() {
+    let server = StandInServer::start().await.unwrap().with_replies([StandInReply::text("one"), StandInReply::text("two")]);
+    let http_client = HttpClient::new();
+    let mut outputs = Vec::new();
+    for _ in 0..2 {
+        let client = client(&server, false).with_http_client(http_client.clone()).with_timeout(Duration::from_secs(5));
+        let response = client.build_batch_api_call().unwrap().execute_async().await.unwrap();
+        outputs.push(response.choices[0].message.content.clone().unwrap());
+    }
+    assert_eq!(outputs, ["one", "two"]);
+}
+
+#[tokio::test]
+async fn echoes_once_the_script_is_exhausted() {
+    let server = StandInServer::start().await.unwrap();
+    let deltas = client(&server, true).build_streaming_api_call().unwrap().text_deltas().await.unwrap();
+    let text = deltas.map(|x| x.unwrap().content).collect::<Vec<_>>().await.concat();
+    assert_eq!(text, "Hello there");
+}
+
+#[tokio::test]
+async fn server_errors_are_retryable() {
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::status(503, "overloaded"),
+        StandInReply::status(429, "slow down").with_header("retry-after", "0"),
+        StandInReply::text("finally"),
+    ]);
+    // The client sends once, retrying is up to the caller.
+    let error = client(&server, true).build_streaming_api_call().unwrap().execute_async().await.unwrap_err();
+    assert!(matches!(&error, ClientError::Status(x) if x.status == 503), "{error}");
+    assert!(error.is_retryable());
+    let error = client(&server, true).build_streaming_api_call().unwrap().execute_async().await.unwrap_err();
+    assert!(error.is_retryable());
+    assert_eq!(error.retry_after(), Some(Duration::ZERO));
+    let output = client(&server, true).build_streaming_api_call().unwrap().execute_async().await.unwrap();
+    assert_eq!(output.content(0).as_deref(), Some("finally"));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn authentication_errors_are_not_retryable() {
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::status(401, "invalid api key"));
+    let error = client(&server, false).build_batch_api_call().unwrap().execute_async().await.unwrap_err();
+    let ClientError::Status(error) = error else {
+        panic!("expected a status error, given {error}")
+    };
+    assert_eq!(error.status, 401);
+    assert!(!ClientError::Status(error).is_retryable());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn api_key_from_a_file() {
+    let server = StandInServer::start().await.unwrap();
+    let path = std::env::temp_dir().join(format!("super-ai-client-api-key-{}", std::process::id()));
+    std::fs::write(&path, "sk-from-file\n").unwrap();
+    let builder = client(&server, false).with_api_key_path(&path).unwrap();
+    builder.build_batch_api_call().unwrap().execute_async().await.unwrap();
+    assert_eq!(server.authorizations(), [Some(String::from("Bearer sk-from-file"))]);
+    std::fs::remove_file(&path).unwrap();
+    let error = client(&server, false).with_api_key_path(&path).err().unwrap();
+    assert!(matches!(error, ClientError::Configuration(InvalidConfiguration::ApiKeyFile { .. })), "{error}");
+}
+
+#[tokio::test]
+async fn interrupted_stream_is_an_error() {
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::Interrupted("cut off".into()));
+    let stream = client(&server, true).build_streaming_api_call().unwrap().stream().await.unwrap();
+    let chunks = stream.collect::<Vec<_>>().await;
+    assert!(chunks.last().unwrap().is_err(), "the stream should end with an error");
+}
+
+fn weather_tool() -> Tool {
+    let parameters = json!({
+        "type": "object",
+        "properties": { "city": { "type": "string" } },
+        "required": ["city"],
+    });
+    Tool::function(Function::new("get_weather").with_description("The current weather in a city").with_parameters(parameters))
+}
+
+fn weather_call(id: &str, city: &str) -> ToolCall {
+    let arguments = json!({ "city": city }).to_string();
+    ToolCall::new(id, FunctionCall { name: "get_weather".into(), arguments })
+}
+
+#[tokio::test]
+async fn streamed_tool_calls_are_assembled() {
+    let calls = vec![weather_call("call_1", "Paris"), weather_call("call_2", "Zürich")];
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::ToolCalls(calls.clone()));
+    let request_body = RequestBuilder::default()
+        .with_model("stand-in")
+        .with_message(Message::user("Weather in Paris and Zürich?"))
+        .with_tools(vec![weather_tool()])
+        .with_tool_choice(ToolChoice::Required)
+        .with_stream(true);
+    let output = ClientBuilder::default()
+        .with_base_url(server.base_url())
+        .with_request_body(request_body)
+        .build_streaming_api_call()
+        .unwrap()
+        .execute_async()
+        .await
+        .unwrap();
+    assert_eq!(output.tool_calls(0), calls);
+    assert_eq!(output.finish_reason(0).as_deref(), Some("tool_calls"));
+    assert_eq!(output.tool_calls(0)[1].function.parse_arguments().unwrap()["city"], "Zürich");
+    let request = &server.requests()[0];
+    assert_eq!(request["tool_choice"], "required");
+    assert_eq!(request["tools"][0]["type"], "function");
+    assert_eq!(request["tools"][0]["function"]["parameters"]["required"][0], "city");
+}
+
+#[tokio::test]
+async fn tool_results_round_trip() {
+    let call = weather_call("call_1", "Paris");
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::ToolCalls(vec![call.clone()]),
+        StandInReply::text("Sunny in Paris"),
+    ]);
+    let mut messages = vec![Message::user("Weather in Paris?")];
+    let request_body = |messages: &[Message]| {
+        RequestBuilder::default()
+            .with_model("stand-in")
+            .with_messages(messages.to_vec())
+            .with_tools(vec![weather_tool()])
+            .with_tool_choice(ToolChoice::function("get_weather"))
+    };
+    let response = ClientBuilder::default()
+        .with_base_url(server.base_url())
+        .with_request_body(request_body(&messages))
+        .build_batch_api_call()
+        .unwrap()
+        .execute_async()
+        .await
+        .unwrap();
+    let reply = Message::from(response.choices[0].message.clone());
+    assert_eq!(reply.tool_calls(), std::slice::from_ref(&call));
+    messages.push(reply);
+    messages.push(Message::tool("{\"sky\": \"sunny\"}", &call.id));
+    let response = ClientBuilder::default()
+        .with_base_url(server.base_url())
+        .with_request_body(request_body(&messages))
+        .build_batch_api_call()
+        .unwrap()
+        .execute_async()
+        .await
+        .unwrap();
+    assert_eq!(response.choices[0].message.content.as_deref(), Some("Sunny in Paris"));
+    let request = &server.requests()[1];
+    assert_eq!(request["tool_choice"], json!({ "type": "function", "function": { "name": "get_weather" } }));
+    assert_eq!(request["messages"][1]["tool_calls"][0]["function"]["arguments"], call.function.arguments);
+    assert_eq!(request["messages"][2], json!({ "role": "tool", "content": "{\"sky\": \"sunny\"}", "tool_call_id": "call_1" }));
+    let messages = serde_json::from_value::<Vec<Message>>(request["messages"].clone()).unwrap();
+    assert_eq!(messages[1].tool_calls(), [call]);
+}
@@ -1,13 +1,15 @@
 use std::fmt::Debug;
 use std::slice::{Iter, IterMut};
 use std::ops::{Index, IndexMut};
-use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, TagBuf};
+use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, Span, TagBuf};
 
 // ————————————————————————————————————————————————————————————————————————————
 // DATA MODEL — XML NODES
 // ————————————————————————————————————————————————————————————————————————————
 
 
+// Elements are the common case, boxing them (for their spans) would only add indirection.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone)]
 pub enum Node {
     Text(String),
@@ -28,6 +30,7 @@ impl Node {
             tag: tag.into(),
             attributes: attributes.into(),
             children: children.into(),
+            span: None,
         })
     }
     pub fn as_text(&self) -> Option<&str> {
@@ -71,6 +74,7 @@ impl Node {
             Node::Text(_) => Vec::new(),
         }
     }
+    #[allow(clippy::result_unit_err)]
     pub fn extract_text_strict(self) -> Result<Vec<String>, ()> {
         match self {
             Node::Element(_) => Err(()),
@@ -78,6 +82,14 @@ impl Node {
             Node::Text(x) => Ok(vec![x]),
         }
     }
+    /// Where an element (or the elements of a fragment) came from, text isn’t tracked.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Text(_) => None,
+            Self::Element(element) => element.span,
+            Self::Fragment(fragment) => fragment.span(),
+        }
+    }
     pub fn flatten(self) -> Vec<Node> {
         match self {
             Self::Text(text) => vec![Self::Text(text)],
@@ -112,11 +124,21 @@ pub struct Element {
     pub tag: TagBuf,
     pub attributes: AttributeMap,
     pub children: Fragment,
+    /// From the start tag through the end tag, if parsed from source.
+    pub span: Option<Span>,
 }
 
 impl Element {
     pub fn new(tag: impl Into<TagBuf>) -> Self {
-        Element { tag: tag.into(), attributes: Default::default(), children: Default::default() }
+        Element { tag: tag.into(), attributes: Default::default(), children: Default::default(), span: None }
+    }
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+    /// Where the attribute was given, falling back to the element.
+    pub fn attribute_span(&self, key: impl AsRef<str>) -> Option<Span> {
+        self.attributes.span(key).or(self.span)
     }
     pub fn with_attributes(mut self, attributes: AttributeMap) -> Self {
         self.attributes.extend(attributes);
@@ -133,6 +155,7 @@ impl Element {
     pub fn extract_child_elements(self) -> Vec<Element> {
         self.children.extract_elements()
     }
+    #[allow(clippy::result_unit_err)]
     pub fn extract_child_text_strict(self) -> Result<Vec<String>, ()> {
         self.children.extract_text_strict()
     }
@@ -180,6 +203,7 @@ impl Fragment {
             })
             .collect::<Vec<_>>()
     }
+    #[allow(clippy::result_unit_err)]
     pub fn extract_text_strict(self) -> Result<Vec<String>, ()> {
         let mut results = Vec::<String>::with_capacity(self.len());
         for node in self.to_vec() {
@@ -195,6 +219,13 @@ impl Fragment {
         }
         Ok(results)
     }
+    /// From the first through the last node with a span.
+    pub fn span(&self) -> Option<Span> {
+        self.nodes
+            .iter()
+            .filter_map(Node::span)
+            .reduce(Span::to)
+    }
     pub fn flatten(self) -> Vec<Node> {
         self
             .to_vec()
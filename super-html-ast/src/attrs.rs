@@ -6,6 +6,8 @@ use std::iter::IntoIterator;
 use std::ops::{Deref, Index, IndexMut};
 use indexmap::IndexMap;
 
+use crate::Span;
+
 // NOTE: keep indexmap dependencies internal (so it can be swapped if necessary) — prefer newtypes.
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -21,6 +23,7 @@ impl AttributeKeyStr {
     /// 
     /// # Safety
     /// `AttributeKeyStr` is a transparent wrapper over `str`
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> &Self {
         unsafe { &*(s as *const str as *const Self) }
     }
@@ -36,6 +39,7 @@ impl AttributeKeyStr {
     }
 
     /// Returns the key as an owned `String`.
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         self.as_str().to_string()
     }
@@ -64,7 +68,7 @@ impl<'a> AttributeKeyRef<'a> {
     /// Creates an owned reference from any string-like value.
     pub fn owned(value: impl Into<String>) -> Self {
         let buf = AttributeKeyBuf::new(value);
-        Self(Cow::Owned(buf.into()))
+        Self(Cow::Owned(buf))
     }
 
     /// Returns the `AttributeKeyStr` representation.
@@ -86,7 +90,7 @@ impl<'a> From<&'a AttributeKeyStr> for AttributeKeyRef<'a> {
 
 impl From<AttributeKeyBuf> for AttributeKeyRef<'_> {
     fn from(value: AttributeKeyBuf) -> Self {
-        Self(Cow::Owned(value.into()))
+        Self(Cow::Owned(value))
     }
 }
 
@@ -208,7 +212,7 @@ impl Eq for AttributeKeyBuf {}
 
 impl PartialOrd for AttributeKeyBuf {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.as_str().partial_cmp(other.as_str())
+        Some(self.cmp(other))
     }
 }
 
@@ -235,7 +239,7 @@ impl Eq for AttributeKeyStr {}
 
 impl PartialOrd for AttributeKeyStr {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.as_str().partial_cmp(other.as_str())
+        Some(self.cmp(other))
     }
 }
 
@@ -324,11 +328,12 @@ impl Display for AttributeValueBuf {
 // ATTRIBUTE MAP
 // ————————————————————————————————————————————————————————————————————————————
 
+/// The values, along with where each was given if parsed from source.
 #[derive(Clone, Default)]
-pub struct AttributeMap(IndexMap<AttributeKeyBuf, AttributeValueBuf>);
+pub struct AttributeMap(IndexMap<AttributeKeyBuf, AttributeValueBuf>, IndexMap<AttributeKeyBuf, Span>);
 
 impl AttributeMap {
-    pub fn map_mut(&mut self, mut apply: impl FnMut(&AttributeKeyBuf, &mut AttributeValueBuf) -> ()) {
+    pub fn map_mut(&mut self, mut apply: impl FnMut(&AttributeKeyBuf, &mut AttributeValueBuf)) {
         for (key, value) in self.0.iter_mut() {
             apply(key, value)
         }
@@ -351,7 +356,7 @@ impl<K: Into<AttributeKeyBuf>, V: Into<AttributeValueBuf>> FromIterator<(K, V)>
             .into_iter()
             .map(|(k, v)| (k.into(), v.into()))
             .collect();
-        Self(map)
+        Self(map, IndexMap::default())
     }
 }
 
@@ -405,26 +410,35 @@ impl AttributeMap {
     }
 
     pub fn remove<Q: AsRef<str>>(&mut self, key: Q) -> Option<AttributeValueBuf> {
+        self.1.swap_remove(AttributeKeyStr::from_str(key.as_ref()));
         self.0
             .swap_remove(AttributeKeyStr::from_str(key.as_ref()))
     }
 
+    /// From the attribute’s name through its value.
+    pub fn span<Q: AsRef<str>>(&self, key: Q) -> Option<Span> {
+        self.1.get(AttributeKeyStr::from_str(key.as_ref())).copied()
+    }
+
+    pub fn set_span<K: Into<AttributeKeyBuf>>(&mut self, key: K, span: Span) {
+        self.1.insert(key.into(), span);
+    }
+
     pub fn contains_key<Q: AsRef<str>>(&self, key: Q) -> bool {
         self.0.contains_key(AttributeKeyStr::from_str(key.as_ref()))
     }
 
     pub fn contains_key_value(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
         let target_key = AttributeKeyStr::from_str(key.as_ref());
-        if let Some(result) = self.0.get(target_key) {
-            if result.as_str() == value.as_ref() {
-                return true
-            }
-        }
-        false
+        self.0
+            .get(target_key)
+            .map(|result| result.as_str() == value.as_ref())
+            .unwrap_or(false)
     }
 
     pub fn clear(&mut self) {
         self.0.clear();
+        self.1.clear();
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &AttributeKeyBuf> {
@@ -463,9 +477,11 @@ impl AttributeMap {
         for (key, value) in other.iter() {
             self.0.insert(key.clone(), value.clone());
         }
+        self.1.extend(other.1.iter().map(|(key, span)| (key.clone(), *span)));
     }
 
     pub fn extend(&mut self, other: AttributeMap) {
+        self.1.extend(other.1.clone());
         self.0.extend(other);
     }
 
@@ -474,6 +490,9 @@ impl AttributeMap {
         for (key, value) in other.iter() {
             self.0.entry(key.clone()).or_insert_with(|| value.clone());
         }
+        for (key, span) in other.1.iter() {
+            self.1.entry(key.clone()).or_insert(*span);
+        }
     }
 }
 
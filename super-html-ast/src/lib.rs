@@ -1,9 +1,11 @@
 mod attrs;
 mod tag;
 mod ast;
+mod span;
 
 pub use attrs::*;
 pub use tag::*;
 pub use ast::*;
+pub use span::*;
 
 pub mod parser;
@@ -1,4 +1,4 @@
-use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, Fragment, Node, TagBuf};
+use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, Fragment, LineIndex, Node, Span, TagBuf};
 
 #[derive(Debug, Clone)]
 pub struct ParseResult<T> {
@@ -23,6 +23,14 @@ impl<T> ParseResult<T> {
         }
         self.output
     }
+    /// The output, or the parse errors if there were any.
+    pub fn into_result(self) -> Result<T, Vec<String>> {
+        if self.errors.is_empty() {
+            Ok(self.output)
+        } else {
+            Err(self.errors)
+        }
+    }
 }
 
 impl ParseResult<Node> {
@@ -38,9 +46,10 @@ impl ParseResult<Node> {
     }
 }
 
+/// Parses `source`, recording the [`Span`]s of elements and their attributes.
 pub fn parse_from_fragment(source: impl AsRef<str>) -> ParseResult<Node> {
     let result = scraper::Html::parse_fragment(source.as_ref());
-    transform_scraper_html(result).transform(|node| {
+    transform_scraper_html(result, source.as_ref()).transform(|node| {
         let nodes = node
             .flatten()
             .into_iter()
@@ -58,22 +67,24 @@ pub fn parse_from_fragment(source: impl AsRef<str>) -> ParseResult<Node> {
     })
 }
 
+/// Parses `source`, recording the [`Span`]s of elements and their attributes.
 pub fn parse_from_document(source: impl AsRef<str>) -> ParseResult<Node> {
     let result = scraper::Html::parse_document(source.as_ref());
-    transform_scraper_html(result)
+    transform_scraper_html(result, source.as_ref())
 }
 
-fn transform_scraper_html(html: scraper::Html) -> ParseResult<Node> {
+fn transform_scraper_html(html: scraper::Html, source: &str) -> ParseResult<Node> {
     let errors = html.errors
         .iter()
         .map(|x| x.to_string())
         .collect::<Vec<_>>();
     let root = html.tree.root();
-    let converted = convert_ego_tree(root);
+    let mut spans = SourceTags::scan(source);
+    let converted = convert_ego_tree(root, &mut spans);
     ParseResult { output: converted, errors }
 }
 
-fn convert_ego_tree(node: ego_tree::NodeRef<'_, scraper::node::Node>) -> Node {
+fn convert_ego_tree(node: ego_tree::NodeRef<'_, scraper::node::Node>, spans: &mut SourceTags) -> Node {
     match node.value() {
         scraper::node::Node::Text(text) => {
             Node::text(text.to_string())
@@ -81,8 +92,10 @@ fn convert_ego_tree(node: ego_tree::NodeRef<'_, scraper::node::Node>) -> Node {
 
         scraper::node::Node::Element(element) => {
             let tag = TagBuf::new(element.name.local.to_string());
+            // Elements are visited in the order of their start tags, implied ones have none.
+            let source_tag = spans.next(tag.as_normalized());
 
-            let attributes: AttributeMap = element.attrs.iter()
+            let mut attributes: AttributeMap = element.attrs.iter()
                 .map(|(key, value)| {
                     (
                         AttributeKeyBuf::from(key.local.to_string()),
@@ -90,17 +103,25 @@ fn convert_ego_tree(node: ego_tree::NodeRef<'_, scraper::node::Node>) -> Node {
                     )
                 })
                 .collect();
+            for (key, span) in source_tag.iter().flat_map(|x| x.attributes.iter()) {
+                if attributes.contains_key(key) {
+                    attributes.set_span(key.as_str(), *span);
+                }
+            }
 
             let children: Fragment = Fragment::from_iter(
-                node.children().map(convert_ego_tree)
+                node.children().map(|x| convert_ego_tree(x, spans))
             );
 
-            Node::element(tag, attributes, children)
+            match (Node::element(tag, attributes, children), source_tag) {
+                (Node::Element(element), Some(source_tag)) => Node::Element(element.with_span(source_tag.span)),
+                (node, _) => node,
+            }
         }
 
         scraper::node::Node::Comment(_) | scraper::node::Node::Doctype(_) | scraper::node::Node::Document | scraper::node::Node::Fragment => {
             let children: Fragment = Fragment::from_iter(
-                node.children().map(convert_ego_tree)
+                node.children().map(|x| convert_ego_tree(x, spans))
             );
             Node::Fragment(children)
         }
@@ -112,3 +133,161 @@ fn convert_ego_tree(node: ego_tree::NodeRef<'_, scraper::node::Node>) -> Node {
 
     }
 }
+
+// ————————————————————————————————————————————————————————————————————————————
+// SOURCE SPANS
+// ————————————————————————————————————————————————————————————————————————————
+
+// The HTML parser doesn’t report source locations, so start tags are scanned
+// separately and matched up with the parsed elements by name and order.
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Their content is text, even if it looks like tags.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes"];
+
+#[derive(Debug, Clone)]
+struct SourceTag {
+    /// Lowercase, like the parsed tags.
+    name: String,
+    span: Span,
+    /// Lowercase names, from the name through the value.
+    attributes: Vec<(String, Span)>,
+}
+
+/// The start tags of a source in order, with a cursor for matching them up.
+#[derive(Debug, Default)]
+struct SourceTags {
+    tags: Vec<SourceTag>,
+    next: usize,
+}
+
+impl SourceTags {
+    fn scan(source: &str) -> Self {
+        let index = LineIndex::new(source);
+        let bytes = source.as_bytes();
+        let mut tags = Vec::<(String, usize, usize, Vec<(String, usize, usize)>)>::new();
+        // Open elements as indices into `tags`, along with where they end once closed.
+        let mut open = Vec::<usize>::new();
+        let mut ends = Vec::<Option<usize>>::new();
+        let mut position = 0;
+        while let Some(start) = source[position..].find('<').map(|x| x + position) {
+            let rest = &source[start..];
+            if rest.starts_with("<!--") {
+                position = rest.find("-->").map(|x| start + x + 3).unwrap_or(source.len());
+                continue
+            }
+            if rest.starts_with("<!") || rest.starts_with("<?") {
+                position = rest.find('>').map(|x| start + x + 1).unwrap_or(source.len());
+                continue
+            }
+            if let Some(name) = rest.strip_prefix("</") {
+                let name = tag_name(name).to_lowercase();
+                let end = rest.find('>').map(|x| start + x + 1).unwrap_or(source.len());
+                // Closing an element closes the ones opened inside it.
+                if let Some(depth) = open.iter().rposition(|x| tags[*x].0 == name) {
+                    for (index, tag) in open.drain(depth..).enumerate().rev() {
+                        ends[tag] = Some(if index == 0 { end } else { start });
+                    }
+                }
+                position = end;
+                continue
+            }
+            let name = tag_name(&rest[1..]);
+            if name.is_empty() {
+                position = start + 1;
+                continue
+            }
+            let mut cursor = start + 1 + name.len();
+            let mut attributes = Vec::new();
+            loop {
+                while cursor < bytes.len() && (bytes[cursor].is_ascii_whitespace() || bytes[cursor] == b'/') {
+                    cursor += 1;
+                }
+                if cursor >= bytes.len() || bytes[cursor] == b'>' {
+                    break
+                }
+                let key_start = cursor;
+                while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() && !b"=>/".contains(&bytes[cursor]) {
+                    cursor += 1;
+                }
+                if cursor == key_start {
+                    cursor += 1;
+                    continue
+                }
+                let key = source[key_start..cursor].to_lowercase();
+                let mut value = cursor;
+                while value < bytes.len() && bytes[value].is_ascii_whitespace() {
+                    value += 1;
+                }
+                if value < bytes.len() && bytes[value] == b'=' {
+                    value += 1;
+                    while value < bytes.len() && bytes[value].is_ascii_whitespace() {
+                        value += 1;
+                    }
+                    cursor = match bytes.get(value) {
+                        Some(quote @ (b'"' | b'\'')) => source[value + 1..]
+                            .find(*quote as char)
+                            .map(|x| value + 1 + x + 1)
+                            .unwrap_or(source.len()),
+                        _ => source[value..]
+                            .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                            .map(|x| value + x)
+                            .unwrap_or(source.len()),
+                    };
+                }
+                attributes.push((key, key_start, cursor));
+            }
+            let end = (cursor + 1).min(source.len());
+            let name = name.to_lowercase();
+            let tag = tags.len();
+            position = end;
+            if VOID_ELEMENTS.contains(&name.as_str()) {
+                ends.push(Some(end));
+            } else {
+                ends.push(None);
+                open.push(tag);
+            }
+            if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+                let closing = format!("</{name}");
+                position = source[end..]
+                    .to_lowercase()
+                    .find(&closing)
+                    .map(|x| end + x)
+                    .unwrap_or(source.len());
+            }
+            tags.push((name, start, end, attributes));
+        }
+        let tags = tags
+            .into_iter()
+            .zip(ends)
+            .map(|((name, start, _, attributes), end)| SourceTag {
+                name,
+                span: index.span(start..end.unwrap_or(source.len())),
+                attributes: attributes
+                    .into_iter()
+                    .map(|(key, start, end)| (key, index.span(start..end)))
+                    .collect(),
+            })
+            .collect();
+        Self { tags, next: 0 }
+    }
+    /// The next start tag with the given name, skipping any others before it.
+    fn next(&mut self, name: &str) -> Option<SourceTag> {
+        let position = self.tags[self.next..].iter().position(|x| x.name == name)?;
+        self.next += position + 1;
+        Some(self.tags[self.next - 1].clone())
+    }
+}
+
+fn tag_name(source: &str) -> &str {
+    let end = source
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':' || c == '.'))
+        .unwrap_or(source.len());
+    match source.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        true => &source[..end],
+        false => "",
+    }
+}
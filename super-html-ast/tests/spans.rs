@@ -0,0 +1,57 @@
+//! Source locations recorded while parsing.
+use super_html_ast::{Element, LineIndex, Node, parser};
+
+const SOURCE: &str = "<prompt name=\"a\">\n    <!-- <msg role=\"ignored\"> -->\n    <msg role='user'>Hi <b>there</b></msg>\n    <br>\n    <script>if (a < b) {}</script>\n    <msg ROLE=user>Bye</msg>\n</prompt>\n";
+
+fn elements(node: Node) -> Vec<Element> {
+    let mut elements = Vec::new();
+    for element in node.extract_elements() {
+        let children = elements_of(&element);
+        elements.push(element);
+        elements.extend(children);
+    }
+    elements
+}
+
+fn elements_of(element: &Element) -> Vec<Element> {
+    element.children.iter().cloned().flat_map(elements).collect()
+}
+
+fn text(element: &Element) -> &str {
+    &SOURCE[element.span.expect("parsed elements have spans").byte_range()]
+}
+
+#[test]
+fn elements_and_attributes_have_spans() {
+    let node = parser::parse_from_fragment(SOURCE).into_result().unwrap();
+    let elements = elements(node);
+    let tags = elements.iter().map(|x| x.tag.as_normalized()).collect::<Vec<_>>();
+    assert_eq!(tags, ["prompt", "msg", "b", "br", "script", "msg"]);
+    assert!(text(&elements[0]).starts_with("<prompt") && text(&elements[0]).ends_with("</prompt>"));
+    assert_eq!(text(&elements[1]), "<msg role='user'>Hi <b>there</b></msg>");
+    assert_eq!(text(&elements[2]), "<b>there</b>");
+    assert_eq!(text(&elements[3]), "<br>");
+    assert_eq!(text(&elements[5]), "<msg ROLE=user>Bye</msg>");
+    let role = elements[1].attributes.span("role").unwrap();
+    assert_eq!(&SOURCE[role.byte_range()], "role='user'");
+    assert_eq!((role.start.line, role.start.column), (3, 10));
+    let role = elements[5].attribute_span("role").unwrap();
+    assert_eq!(&SOURCE[role.byte_range()], "ROLE=user");
+    assert_eq!(role.to_string(), "6:10");
+}
+
+#[test]
+fn line_index() {
+    let source = "ab\r\nçd\n\nlast";
+    let index = LineIndex::new(source);
+    assert_eq!(index.position(0).to_string(), "1:1");
+    assert_eq!(index.position(4).to_string(), "2:1");
+    assert_eq!(index.position(6).to_string(), "2:2");
+    // Inside `ç`, clamped to its start.
+    assert_eq!(index.position(5).to_string(), "2:1");
+    assert_eq!(index.position(100).to_string(), "4:5");
+    assert_eq!(index.line(1), Some("ab"));
+    assert_eq!(index.line(3), Some(""));
+    assert_eq!(index.line(4), Some("last"));
+    assert_eq!(index.line(5), None);
+}
@@ -4,3 +4,5 @@ pub mod prompt;
 pub mod document;
 pub mod breakpoint;
 pub mod set;
+pub mod schema;
+pub mod tool;
@@ -3,9 +3,39 @@ use crate::common::message::MessageRole;
 #[derive(Debug, Clone)]
 pub struct BreakpointNode {
     pub role: MessageRole,
+    /// How the output is checked, via `verification="json|schema"`.
+    pub verification: Option<Verification>,
+    /// The schema for [`Verification::Schema`].
+    pub schema: Option<SchemaReference>,
+    /// How often the output is generated before failing verification is an error, via `max-attempts="N"`.
+    pub max_attempts: u32,
+    /// The prompt that repairs failed output, via `repair="NAME"`; otherwise the
+    /// errors are sent back to the model as part of the conversation.
+    pub repair: Option<String>,
+    /// The `<tool>`s the model may call before answering, via `tools="NAME ..."`.
+    pub tools: Vec<String>,
+    /// Via `tool-choice="auto|none|required|NAME"`, applies to the first request only
+    /// so a forced call doesn’t repeat forever.
+    pub tool_choice: Option<ai_client::request::ToolChoice>,
+    /// How often the model may call tools before answering, via `max-tool-rounds="N"`.
+    pub max_tool_rounds: u32,
+    /// Which choice continues the conversation when the model generates several (see `n`),
+    /// via `select="..."` or `judge="NAME"`.
+    pub select: SelectionPolicy,
+    /// Where the element is in the document, for diagnostics.
+    pub span: Option<html_ast::Span>,
 }
 
 impl BreakpointNode {
+    /// Without `max-attempts`, failed output is only repaired if there is a `repair` prompt.
+    pub const DEFAULT_MAX_ATTEMPTS: u32 = 1;
+    pub const DEFAULT_REPAIR_MAX_ATTEMPTS: u32 = 3;
+    pub const DEFAULT_MAX_TOOL_ROUNDS: u32 = 8;
+    /// The attributes a `<breakpoint>` takes, others are ignored.
+    pub const ATTRIBUTES: &[&str] = &[
+        "role", "verification", "schema", "max-attempts", "repair", "tools", "tool-choice", "max-tool-rounds",
+        "select", "judge",
+    ];
     pub fn tag_type() -> html_ast::TagBuf {
         html_ast::TagBuf::new("breakpoint")
     }
@@ -13,3 +43,107 @@ impl BreakpointNode {
         Self::tag_type().matches(tag)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The output must be valid JSON.
+    Json,
+    /// The output must be valid JSON conforming to a schema.
+    Schema,
+}
+
+impl std::str::FromStr for Verification {
+    type Err = InvalidVerification;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(Self::Json),
+            "schema" => Ok(Self::Schema),
+            _ => Err(InvalidVerification { given: value.to_string() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidVerification {
+    pub given: String,
+}
+
+impl std::fmt::Display for InvalidVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected `json` or `schema`, given {:?}", self.given)
+    }
+}
+
+impl std::error::Error for InvalidVerification {}
+
+/// How one of several choices is selected, see [`crate::common::selection`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SelectionPolicy {
+    /// The choice the provider returned first.
+    #[default]
+    First,
+    /// The longest choice.
+    Longest,
+    /// The first choice that passes verification, or the first if none does.
+    PassesSchema,
+    /// The most common answer.
+    Majority,
+    /// The choice named by another prompt of the document.
+    Judge(String),
+}
+
+impl std::str::FromStr for SelectionPolicy {
+    type Err = InvalidSelectionPolicy;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "first" => Ok(Self::First),
+            "longest" => Ok(Self::Longest),
+            "passes-schema" => Ok(Self::PassesSchema),
+            "majority" => Ok(Self::Majority),
+            _ => Err(InvalidSelectionPolicy { given: value.to_string() }),
+        }
+    }
+}
+
+impl std::fmt::Display for SelectionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::First => write!(f, "first"),
+            Self::Longest => write!(f, "longest"),
+            Self::PassesSchema => write!(f, "passes-schema"),
+            Self::Majority => write!(f, "majority"),
+            Self::Judge(name) => write!(f, "judge:{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidSelectionPolicy {
+    pub given: String,
+}
+
+impl std::fmt::Display for InvalidSelectionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected `first`, `longest`, `passes-schema` or `majority`, given {:?}", self.given)
+    }
+}
+
+impl std::error::Error for InvalidSelectionPolicy {}
+
+/// The target of `<breakpoint schema="...">`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaReference {
+    /// The `id` of a `<schema>` in the same document.
+    Document(String),
+    /// A prompt input of type `Schema`.
+    Input(String),
+}
+
+impl std::fmt::Display for SchemaReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Document(id) => write!(f, "{id}"),
+            Self::Input(name) => write!(f, "{}{name}", crate::common::input::InputType::ATTRIBUTE_PREFIX),
+        }
+    }
+}
@@ -1,11 +1,36 @@
 use crate::ast::prompt::PromptNode;
+use crate::ast::schema::SchemaNode;
+use crate::ast::tool::ToolNode;
 
 #[derive(Debug, Clone)]
 pub enum DocumentChildCode {
     Prompt(PromptNode),
+    Schema(SchemaNode),
+    Tool(ToolNode),
 }
 
 #[derive(Debug, Clone)]
 pub struct DocumentNode {
     pub children: Vec<DocumentChildCode>,
 }
+
+impl DocumentNode {
+    pub fn prompts(&self) -> impl Iterator<Item = &PromptNode> {
+        self.children.iter().filter_map(|x| match x {
+            DocumentChildCode::Prompt(prompt) => Some(prompt),
+            _ => None,
+        })
+    }
+    pub fn schemas(&self) -> impl Iterator<Item = &SchemaNode> {
+        self.children.iter().filter_map(|x| match x {
+            DocumentChildCode::Schema(schema) => Some(schema),
+            _ => None,
+        })
+    }
+    pub fn tools(&self) -> impl Iterator<Item = &ToolNode> {
+        self.children.iter().filter_map(|x| match x {
+            DocumentChildCode::Tool(tool) => Some(tool),
+            _ => None,
+        })
+    }
+}
@@ -8,6 +8,8 @@ pub struct MsgNode {
 }
 
 impl MsgNode {
+    /// The attributes a `<msg>` takes, others are ignored.
+    pub const ATTRIBUTES: &[&str] = &["role"];
     pub fn tag_type() -> html_ast::TagBuf {
         html_ast::TagBuf::new("msg")
     }
@@ -1,33 +1,434 @@
-use html_ast::{Element, Node};
+//! Rendering the markup of a `<msg>` as the text of a message.
+//!
+//! Every token of a prompt should be deliberate, so the rendering follows
+//! fixed rules instead of whatever whitespace the document happens to
+//! contain. It runs in two passes: the markup is read into [`Block`]s and
+//! [`Inline`]s, which are then written as plain text or as Markdown, see
+//! [`TextFormatType`] and the `text-format` attribute of `<prompt>` and `<set>`.
+//!
+//! - Blocks are `<p>`, `<h1>` to `<h6>`, `<ul>` and `<ol>` (of `<li>`),
+//!   `<pre>` and `<blockquote>`; `<div>`, `<section>` and `<article>` only
+//!   group blocks, as does any other element around blocks. Text between
+//!   blocks forms a paragraph of its own.
+//! - Inlines are `<b>`/`<strong>`, `<i>`/`<em>`, `<code>` and `<br>`; any
+//!   other element renders as its content.
+//! - Within a block, whitespace (including line breaks) collapses to a
+//!   single space, and is dropped at the start and end of the block and
+//!   of each line. Spaces inside emphasis move outside of it.
+//! - `<pre>` keeps its lines, minus the first and last if blank, their
+//!   common indentation and any trailing whitespace. Markup inside it is
+//!   ignored, except for `from`.
+//! - Blocks are separated by a line break in plain text and by a blank line
+//!   in Markdown. List items are on consecutive lines, marked `- ` or `N. `
+//!   in either format; their continuation lines are indented to line up.
+//! - Plain text drops emphasis, code spans and heading markers, and indents
+//!   quotes by two spaces. Markdown writes `**`, `*`, backticks, `#`, fenced
+//!   code blocks (with `<pre lang="...">` as their language) and `> `.
+//! - Text is never escaped, so Markdown written by hand comes through as is.
+//! - An element with `from="NAME"` is replaced by the input’s value verbatim,
+//!   as a block for block elements and inline otherwise.
+//! - A `<msg>` of only text keeps its lines, minus the leading and trailing
+//!   blank ones, their common indentation and any trailing whitespace.
+use std::str::FromStr;
+
+use html_ast::{Element, Fragment, Node};
 
 use crate::ast::message::MsgNode;
+use crate::common::input::{InputFormat, InputValues};
+use crate::common::prompt::TextFormatType;
+use crate::runtime::InvocationError;
 
 impl MsgNode {
-    pub fn text_content(&self) -> String {
+    /// The message text, with every `from="..."` binding replaced by its input value.
+    pub fn render(&self, inputs: &InputValues, format: TextFormatType) -> Result<String, InvocationError> {
         if let Ok(text_only) = self.children.clone().extract_text_strict() {
-            return text_only.join("")
+            return Ok(dedent(&text_only.concat()))
         }
-        self.children
-            .iter()
-            .flat_map(|x| x.to_owned().flatten())
-            .filter_map(|x| {
-                match x {
-                    Node::Element(element) => {
-                        Some(to_text_content(element))
-                    },
-                    Node::Text(_) => {
-                        None
+        let blocks = read_blocks(&self.children, inputs)?;
+        Ok(Writer { format }.blocks(&blocks))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// MARKUP
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+enum Block {
+    Paragraph(Vec<Inline>),
+    Heading(usize, Vec<Inline>),
+    /// Numbered from `start`, or unordered if `None`.
+    List { start: Option<usize>, items: Vec<Vec<Block>> },
+    Pre { language: Option<String>, text: String },
+    Quote(Vec<Block>),
+    /// An input value.
+    Verbatim(String),
+}
+
+#[derive(Debug, Clone)]
+enum Inline {
+    Text(String),
+    Strong(Vec<Inline>),
+    Emphasis(Vec<Inline>),
+    Code(String),
+    Break,
+    /// An input value.
+    Verbatim(String),
+}
+
+fn is_block(tag: &str) -> bool {
+    matches!(
+        tag,
+        "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "li" | "pre" | "blockquote" | "div" | "section" | "article"
+    )
+}
+
+/// Unknown elements around blocks group them, e.g. `<x-note><p>..</p></x-note>`.
+fn is_group(element: &Element) -> bool {
+    let inline = matches!(element.tag.as_normalized(), "b" | "strong" | "i" | "em" | "code" | "br");
+    !inline && element.children.iter().any(|x| match x {
+        Node::Element(child) => is_block(child.tag.as_normalized()) || is_group(child),
+        _ => false,
+    })
+}
+
+fn read_blocks(fragment: &Fragment, inputs: &InputValues) -> Result<Vec<Block>, InvocationError> {
+    let mut blocks = Vec::new();
+    let mut run = Vec::new();
+    for node in fragment.clone().flatten() {
+        match node {
+            Node::Element(element) if is_block(element.tag.as_normalized()) || is_group(&element) => {
+                if !is_blank(&run) {
+                    blocks.push(Block::Paragraph(std::mem::take(&mut run)));
+                }
+                run.clear();
+                blocks.extend(read_block(&element, inputs)?);
+            }
+            node => run.extend(read_inline(node, inputs)?),
+        }
+    }
+    if !is_blank(&run) {
+        blocks.push(Block::Paragraph(run));
+    }
+    Ok(blocks)
+}
+
+/// A block element, groups are spliced into their parent.
+fn read_block(element: &Element, inputs: &InputValues) -> Result<Vec<Block>, InvocationError> {
+    if let Some(value) = input_value(element, inputs)? {
+        return Ok(vec![Block::Verbatim(value)])
+    }
+    let tag = element.tag.as_normalized();
+    let block = match tag {
+        "p" => Block::Paragraph(read_inlines(&element.children, inputs)?),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = tag[1..].parse::<usize>().unwrap_or(1);
+            Block::Heading(level, read_inlines(&element.children, inputs)?)
+        }
+        "ul" | "ol" => {
+            let start = match tag {
+                "ol" => Some(element.attributes.get("start").and_then(|x| x.as_str().trim().parse().ok()).unwrap_or(1)),
+                _ => None,
+            };
+            let mut items = Vec::new();
+            for node in element.children.clone().flatten() {
+                match node {
+                    Node::Element(item) if item.tag.as_normalized() == "li" => items.push(read_blocks(&item.children, inputs)?),
+                    // Anything else forms an item of its own.
+                    node => {
+                        let item = read_blocks(&Fragment::from_nodes(vec![node]), inputs)?;
+                        if !item.is_empty() {
+                            items.push(item);
+                        }
                     }
-                    Node::Fragment(_) => {
-                        panic!("NOT POSSIBLE")
-                    },
                 }
-            })
+            }
+            Block::List { start, items }
+        }
+        "pre" => {
+            let language = element.attributes.get("lang").map(|x| x.as_str().trim().to_string()).filter(|x| !x.is_empty());
+            let mut text = String::new();
+            read_text(&element.children, inputs, &mut text)?;
+            Block::Pre { language, text: dedent(&text) }
+        }
+        "blockquote" => Block::Quote(read_blocks(&element.children, inputs)?),
+        _ => return read_blocks(&element.children, inputs),
+    };
+    Ok(vec![block])
+}
+
+fn read_inlines(fragment: &Fragment, inputs: &InputValues) -> Result<Vec<Inline>, InvocationError> {
+    let mut inlines = Vec::new();
+    for node in fragment.clone().flatten() {
+        inlines.extend(read_inline(node, inputs)?);
+    }
+    Ok(inlines)
+}
+
+/// Block elements in inline context, e.g. `<span><p>..</p></span>`, render as their content.
+fn read_inline(node: Node, inputs: &InputValues) -> Result<Vec<Inline>, InvocationError> {
+    let element = match node {
+        Node::Text(text) => return Ok(vec![Inline::Text(text)]),
+        Node::Fragment(fragment) => return read_inlines(&fragment, inputs),
+        Node::Element(element) => element,
+    };
+    if let Some(value) = input_value(&element, inputs)? {
+        return Ok(vec![Inline::Verbatim(value)])
+    }
+    let inline = match element.tag.as_normalized() {
+        "b" | "strong" => Inline::Strong(read_inlines(&element.children, inputs)?),
+        "i" | "em" => Inline::Emphasis(read_inlines(&element.children, inputs)?),
+        "code" => {
+            let mut text = String::new();
+            read_text(&element.children, inputs, &mut text)?;
+            Inline::Code(text.split_whitespace().collect::<Vec<_>>().join(" "))
+        }
+        "br" => Inline::Break,
+        _ => return read_inlines(&element.children, inputs),
+    };
+    Ok(vec![inline])
+}
+
+/// The text of a fragment, ignoring its markup.
+fn read_text(fragment: &Fragment, inputs: &InputValues, out: &mut String) -> Result<(), InvocationError> {
+    for node in fragment.clone().flatten() {
+        match node {
+            Node::Text(text) => out.push_str(&text),
+            Node::Element(element) => match input_value(&element, inputs)? {
+                Some(value) => out.push_str(&value),
+                None if element.tag.as_normalized() == "br" => out.push('\n'),
+                None => read_text(&element.children, inputs, out)?,
+            },
+            Node::Fragment(fragment) => read_text(&fragment, inputs, out)?,
+        }
+    }
+    Ok(())
+}
+
+/// The value bound via `from`, if any.
+fn input_value(element: &Element, inputs: &InputValues) -> Result<Option<String>, InvocationError> {
+    let Some(name) = element.attributes.get("from") else {
+        return Ok(None)
+    };
+    let name = name.as_str();
+    let value = inputs
+        .get(name)
+        .ok_or_else(|| InvocationError::MissingInput { name: name.to_string(), r#type: None })?;
+    let format = element.attributes
+        .get("format")
+        .and_then(|x| InputFormat::from_str(x.as_str()).ok())
+        .unwrap_or_default();
+    Ok(Some(format.render(value)))
+}
+
+fn is_blank(inlines: &[Inline]) -> bool {
+    inlines.iter().all(|x| match x {
+        Inline::Text(text) => text.trim().is_empty(),
+        Inline::Strong(xs) | Inline::Emphasis(xs) => is_blank(xs),
+        Inline::Code(_) | Inline::Break | Inline::Verbatim(_) => false,
+    })
+}
+
+/// Lines without the leading and trailing blank ones, their common indentation and trailing whitespace.
+fn dedent(text: &str) -> String {
+    let lines = text.lines().map(str::trim_end).collect::<Vec<_>>();
+    let first = lines.iter().position(|x| !x.is_empty()).unwrap_or(lines.len());
+    let last = lines.iter().rposition(|x| !x.is_empty()).map_or(first, |x| x + 1);
+    let lines = &lines[first..last];
+    let indent = lines
+        .iter()
+        .filter(|x| !x.is_empty())
+        .map(|x| x.len() - x.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        // Whitespace is ASCII or at least as wide, `get` keeps a mix of both from panicking.
+        .map(|x| x.get(indent..).unwrap_or_else(|| x.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TEXT
+// ————————————————————————————————————————————————————————————————————————————
+
+struct Writer {
+    format: TextFormatType,
+}
+
+impl Writer {
+    fn markdown(&self) -> bool {
+        self.format == TextFormatType::Markdown
+    }
+    fn separator(&self) -> &'static str {
+        if self.markdown() { "\n\n" } else { "\n" }
+    }
+    fn blocks(&self, blocks: &[Block]) -> String {
+        blocks
+            .iter()
+            .map(|x| self.block(x))
+            .filter(|x| !x.is_empty())
             .collect::<Vec<_>>()
-            .join("\n")
+            .join(self.separator())
+    }
+    fn block(&self, block: &Block) -> String {
+        match block {
+            Block::Paragraph(inlines) => self.inlines(inlines),
+            Block::Heading(level, inlines) => {
+                let text = self.inlines(inlines);
+                match self.markdown() && !text.is_empty() {
+                    true => format!("{} {text}", "#".repeat(*level)),
+                    false => text,
+                }
+            }
+            Block::List { start, items } => {
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        let marker = match start {
+                            Some(start) => format!("{}. ", start + index),
+                            None => String::from("- "),
+                        };
+                        let indent = " ".repeat(marker.len());
+                        indent_lines(&self.list_item(item), &marker, &indent)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Block::Pre { language, text } if self.markdown() => {
+                let fence = "`".repeat(longest_run(text, '`').max(2) + 1);
+                format!("{fence}{}\n{text}\n{fence}", language.as_deref().unwrap_or_default())
+            }
+            Block::Pre { text, .. } => text.clone(),
+            Block::Quote(blocks) => {
+                let text = self.blocks(blocks);
+                match self.markdown() {
+                    true => text.split('\n').map(|x| format!("> {x}").trim_end().to_string()).collect::<Vec<_>>().join("\n"),
+                    false => indent_lines(&text, "  ", "  "),
+                }
+            }
+            Block::Verbatim(value) => value.clone(),
+        }
+    }
+    /// Like [`Writer::blocks`], but nested lists follow their item without a blank line.
+    fn list_item(&self, blocks: &[Block]) -> String {
+        let mut out = String::new();
+        for block in blocks {
+            let text = self.block(block);
+            if text.is_empty() {
+                continue
+            }
+            if !out.is_empty() {
+                out.push_str(if matches!(block, Block::List { .. }) { "\n" } else { self.separator() });
+            }
+            out.push_str(&text);
+        }
+        out
     }
+    fn inlines(&self, inlines: &[Inline]) -> String {
+        let mut line = Line::default();
+        self.write_inlines(inlines, &mut line);
+        line.out
+    }
+    fn write_inlines(&self, inlines: &[Inline], line: &mut Line) {
+        for inline in inlines {
+            match inline {
+                Inline::Text(text) => {
+                    if text.starts_with(char::is_whitespace) {
+                        line.space();
+                    }
+                    let mut words = text.split_whitespace().peekable();
+                    while let Some(word) = words.next() {
+                        line.text(word);
+                        if words.peek().is_some() {
+                            line.space();
+                        }
+                    }
+                    if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+                        line.space();
+                    }
+                }
+                Inline::Strong(inlines) => self.write_marked(inlines, "**", line),
+                Inline::Emphasis(inlines) => self.write_marked(inlines, "*", line),
+                Inline::Code(code) if self.markdown() && !code.is_empty() => {
+                    let ticks = "`".repeat(longest_run(code, '`') + 1);
+                    // A code span starting or ending with a backtick needs padding.
+                    let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+                    line.text(&format!("{ticks}{pad}{code}{pad}{ticks}"));
+                }
+                Inline::Code(code) => line.text(code),
+                Inline::Break => line.line_break(),
+                Inline::Verbatim(value) => line.text(value),
+            }
+        }
+    }
+    fn write_marked(&self, inlines: &[Inline], marker: &'static str, line: &mut Line) {
+        if !self.markdown() {
+            return self.write_inlines(inlines, line)
+        }
+        line.open(marker);
+        self.write_inlines(inlines, line);
+        line.close(marker);
+    }
+}
+
+/// A block’s text as it is written, applying the whitespace rules.
+#[derive(Default)]
+struct Line {
+    out: String,
+    /// Written before the next text unless at the start of a line.
+    space: bool,
+    /// Opening markers not yet followed by text, so spaces go before them.
+    open: String,
+}
+
+impl Line {
+    fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return
+        }
+        if self.space && !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push(' ');
+        }
+        self.space = false;
+        self.out.push_str(&std::mem::take(&mut self.open));
+        self.out.push_str(text);
+    }
+    fn space(&mut self) {
+        self.space = true;
+    }
+    fn open(&mut self, marker: &str) {
+        self.open.push_str(marker);
+    }
+    /// Empty emphasis is dropped, a pending space is written after the marker.
+    fn close(&mut self, marker: &str) {
+        match self.open.strip_suffix(marker) {
+            Some(rest) => self.open = rest.to_string(),
+            None => self.out.push_str(marker),
+        }
+    }
+    /// Opening markers move to the next line.
+    fn line_break(&mut self) {
+        self.out.push('\n');
+        self.space = false;
+    }
+}
+
+/// Prefixes the first line with `first` and every other non-empty line with `rest`.
+fn indent_lines(text: &str, first: &str, rest: &str) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(index, line)| match (index, line.is_empty()) {
+            (0, _) => format!("{first}{line}"),
+            (_, true) => String::new(),
+            (_, false) => format!("{rest}{line}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn to_text_content(element: Element) -> String {
-    element.children.extract_text_strict().unwrap().join("")
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|x| x != c).map(str::len).max().unwrap_or(0)
 }
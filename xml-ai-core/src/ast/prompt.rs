@@ -1,4 +1,4 @@
-use crate::{ast::{breakpoint::BreakpointNode, message::MsgNode, set::SetNode}, common::prompt::PromptArguments};
+use crate::{ast::{breakpoint::BreakpointNode, message::MsgNode, set::SetNode}, common::{input::InputDeclaration, prompt::PromptArguments}};
 
 // ————————————————————————————————————————————————————————————————————————————
 // PROMPT CHILD NODE
@@ -18,7 +18,11 @@ pub enum PromptChildNode {
 #[derive(Debug, Clone)]
 pub struct PromptNode {
     pub settings: PromptArguments,
+    /// Declared via `input:NAME="of type TYPE"`.
+    pub inputs: Vec<InputDeclaration>,
     pub children: Vec<PromptChildNode>,
+    /// Where the element is in the document, for diagnostics.
+    pub span: Option<html_ast::Span>,
 }
 
 impl PromptNode {
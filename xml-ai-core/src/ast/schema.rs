@@ -0,0 +1,37 @@
+use serde_json::Value;
+
+/// Where a `<schema>`'s JSON Schema comes from.
+#[derive(Debug, Clone)]
+pub enum SchemaSource {
+    /// Given as the element’s text content.
+    Inline(Value),
+    /// Given via `src="..."`, relative to the document; see [`crate::ast::document::DocumentNode::resolve_schemas`].
+    External(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaNode {
+    /// Referenced by `<breakpoint schema="...">`.
+    pub id: String,
+    pub source: SchemaSource,
+    /// Where the element is in the document, for diagnostics.
+    pub span: Option<html_ast::Span>,
+}
+
+impl SchemaNode {
+    /// The attributes a `<schema>` takes, others are ignored.
+    pub const ATTRIBUTES: &[&str] = &["id", "src"];
+    pub fn tag_type() -> html_ast::TagBuf {
+        html_ast::TagBuf::new("schema")
+    }
+    pub fn matches(tag: &html_ast::TagBuf) -> bool {
+        Self::tag_type().matches(tag)
+    }
+    /// The schema, if it is inline or has been resolved.
+    pub fn value(&self) -> Option<&Value> {
+        match &self.source {
+            SchemaSource::Inline(value) => Some(value),
+            SchemaSource::External(_) => None,
+        }
+    }
+}
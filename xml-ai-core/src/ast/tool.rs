@@ -0,0 +1,39 @@
+use serde_json::Value;
+
+/// A function the model may call at a `<breakpoint tools="...">`.
+#[derive(Debug, Clone)]
+pub struct ToolNode {
+    /// How breakpoints and the model refer to the tool.
+    pub name: String,
+    /// Tells the model what the tool does, via `description="..."`.
+    pub description: Option<String>,
+    /// A JSON Schema of the arguments; omitted for tools without arguments.
+    pub parameters: Option<ToolParameters>,
+    /// The executable and its arguments, via `command="..."` split with shell-style
+    /// quoting (no expansion or pipes); the call’s arguments
+    /// are written to its stdin as JSON and its stdout is the result. Tools
+    /// without a command are bound via [`crate::runtime::RuntimeEnvironment::tools`].
+    pub command: Option<Vec<String>>,
+    /// Where the element is in the document, for diagnostics.
+    pub span: Option<html_ast::Span>,
+}
+
+/// Where a `<tool>`’s parameter schema comes from.
+#[derive(Debug, Clone)]
+pub enum ToolParameters {
+    /// Given as the element’s text content.
+    Inline(Value),
+    /// The `id` of a `<schema>`, via `parameters="..."`.
+    Schema(String),
+}
+
+impl ToolNode {
+    /// The attributes a `<tool>` takes, others are ignored.
+    pub const ATTRIBUTES: &[&str] = &["name", "description", "command", "parameters"];
+    pub fn tag_type() -> html_ast::TagBuf {
+        html_ast::TagBuf::new("tool")
+    }
+    pub fn matches(tag: &html_ast::TagBuf) -> bool {
+        Self::tag_type().matches(tag)
+    }
+}
@@ -0,0 +1,160 @@
+//! Evaluating a prompt once per input row, e.g. to generate a dataset.
+use std::collections::BTreeSet;
+
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ast::document::DocumentNode;
+use crate::common::input::InputValues;
+use crate::runtime::{DocumentInvocation, PromptContext, RuntimeEnvironment};
+use crate::snapshot::ConversationSnapshot;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+pub struct BatchRow {
+    /// Identifies the row’s result, see [`BatchRow::parse_jsonl`].
+    pub id: String,
+    pub inputs: InputValues,
+}
+
+impl BatchRow {
+    pub const DEFAULT_ID_FIELD: &'static str = "id";
+    /// Parses one JSON object per line, blank lines are skipped.
+    ///
+    /// A row’s id is its `id_field` (a string or a number), or else its line number.
+    pub fn parse_jsonl(source: &str, id_field: &str) -> Result<Vec<Self>, String> {
+        let mut rows = Vec::<Self>::new();
+        let mut ids = BTreeSet::<String>::new();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            if line.trim().is_empty() {
+                continue
+            }
+            let inputs = serde_json::from_str::<InputValues>(line)
+                .map_err(|error| format!("line {line_number}: expected a JSON object: {error}"))?;
+            let id = match inputs.get(id_field) {
+                Some(Value::String(id)) => id.clone(),
+                Some(Value::Number(id)) => id.to_string(),
+                Some(_) => return Err(format!("line {line_number}: `{id_field}` must be a string or a number")),
+                None => line_number.to_string(),
+            };
+            if !ids.insert(id.clone()) {
+                return Err(format!("line {line_number}: duplicate id {id:?}"))
+            }
+            rows.push(Self { id, inputs });
+        }
+        Ok(rows)
+    }
+}
+
+/// One line of the results file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub id: String,
+    /// The content of the last message, unless the row failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub snapshot: ConversationSnapshot,
+}
+
+impl BatchResult {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+    /// The rows that already succeeded according to a results file.
+    ///
+    /// Only their lines are retained, so failed rows are evaluated again and
+    /// every id ends up in the file once. A trailing line that was cut off,
+    /// e.g. by an interrupted run, is dropped as well.
+    pub fn completed(source: &str) -> Result<CompletedRows, String> {
+        let lines = source.lines().enumerate().filter(|(_, x)| !x.trim().is_empty()).collect::<Vec<_>>();
+        let mut completed = CompletedRows::default();
+        for (position, (index, line)) in lines.iter().enumerate() {
+            match serde_json::from_str::<Self>(line) {
+                Ok(result) if result.is_ok() => {
+                    if completed.ids.insert(result.id) {
+                        completed.lines.push_str(line);
+                        completed.lines.push('\n');
+                    }
+                }
+                Ok(_) => completed.failed += 1,
+                Err(_) if position + 1 == lines.len() && !source.ends_with('\n') => (),
+                Err(error) => return Err(format!("line {}: {error}", index + 1)),
+            }
+        }
+        Ok(completed)
+    }
+}
+
+/// See [`BatchResult::completed`].
+#[derive(Debug, Clone, Default)]
+pub struct CompletedRows {
+    pub ids: BTreeSet<String>,
+    /// The results of these rows, one per line, to replace the results file with.
+    pub lines: String,
+    /// How many failed results were dropped.
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchInvocation {
+    pub runtime_environment: RuntimeEnvironment,
+    pub target_prompt: String,
+    /// Shared by all rows, a row’s own values take precedence.
+    pub inputs: InputValues,
+    /// How many rows are evaluated at once.
+    pub concurrency: usize,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INVOCATION
+// ————————————————————————————————————————————————————————————————————————————
+
+impl DocumentNode {
+    /// Evaluates the prompt for each row, yielding the results as they complete.
+    ///
+    /// A failed row doesn’t stop the others, its result carries the error and
+    /// whatever was evaluated before it.
+    pub fn invoke_batch<'a>(
+        &'a self,
+        batch_invocation: &'a BatchInvocation,
+        rows: Vec<BatchRow>,
+    ) -> impl Stream<Item = BatchResult> + 'a {
+        futures::stream::iter(rows)
+            .map(move |row| self.invoke_row(batch_invocation, row))
+            .buffer_unordered(batch_invocation.concurrency.max(1))
+    }
+    async fn invoke_row(&self, batch_invocation: &BatchInvocation, row: BatchRow) -> BatchResult {
+        let mut inputs = batch_invocation.inputs.clone();
+        inputs.extend(row.inputs);
+        let document_invocation = DocumentInvocation {
+            runtime_environment: batch_invocation.runtime_environment.clone(),
+            target_prompt: batch_invocation.target_prompt.clone(),
+            inputs,
+            resume_from: None,
+        };
+        let mut prompt_context = PromptContext::new(document_invocation.runtime_environment.clone());
+        let result = self.invoke_into(&document_invocation, &mut prompt_context).await;
+        let snapshot = prompt_context.to_snapshot();
+        match result {
+            Ok(()) => BatchResult {
+                id: row.id,
+                output: snapshot.messages.last().map(|x| x.message_payload.content().to_string()),
+                error: None,
+                snapshot,
+            },
+            Err(error) => BatchResult {
+                id: row.id,
+                output: None,
+                error: Some(error.to_string()),
+                snapshot,
+            },
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! An on-disk cache of completions, so re-runs only pay for requests that changed.
+//!
+//! Entries are content addressed: the file name is a SHA-256 of the provider
+//! and the full request (messages, model and sampling parameters), so editing
+//! a message only misses the cache from that message on.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use ai_client::request::{Message, Request, ToolCall};
+use ai_client::response::batch::Usage;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Reuse cached completions and store new ones.
+    #[default]
+    ReadWrite,
+    /// Ignore cached completions but store new ones, replacing the old.
+    Refresh,
+}
+
+/// One cache file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub provider: String,
+    /// The request the completion answers, kept for inspection.
+    pub request: Value,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Option<Usage>,
+    pub latency_ms: u64,
+    /// The choices after the first, for requests with `n`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_choices: Vec<Message>,
+}
+
+impl CacheEntry {
+    /// An entry for the given choices, the first is kept as `content` and `tool_calls`.
+    pub fn new(provider: String, request: Value, choices: &[Message], usage: Option<Usage>, latency_ms: u64) -> Self {
+        let (content, tool_calls) = choices
+            .first()
+            .map(|x| (x.content().to_string(), x.tool_calls().to_vec()))
+            .unwrap_or_default();
+        let other_choices = choices.iter().skip(1).cloned().collect();
+        Self { provider, request, content, tool_calls, usage, latency_ms, other_choices }
+    }
+    /// Every choice as an assistant message.
+    pub fn choices(&self) -> Vec<Message> {
+        let first = Message::assistant_tool_calls(self.content.clone(), self.tool_calls.clone());
+        std::iter::once(first).chain(self.other_choices.iter().cloned()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Entries written, a failed write is reported but not counted.
+    pub stored: usize,
+}
+
+impl std::fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "  {} hit(s) · {} miss(es) · {} stored", self.hits, self.misses, self.stored)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// CACHE
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Shared by clones, including the statistics.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    directory: PathBuf,
+    mode: CacheMode,
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    stored: AtomicUsize,
+}
+
+impl ResponseCache {
+    pub const DEFAULT_DIRECTORY: &'static str = ".xml-ai-cache";
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into(), mode: CacheMode::default(), counters: Arc::default() }
+    }
+    pub fn with_mode(mut self, mode: CacheMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    pub fn mode(&self) -> CacheMode {
+        self.mode
+    }
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            stored: self.counters.stored.load(Ordering::Relaxed),
+        }
+    }
+    /// The hex encoded SHA-256 of the provider and the request, with object keys sorted.
+    pub fn key(provider: &str, request: &Request) -> String {
+        let request = serde_json::to_value(request).unwrap_or_default();
+        let source = serde_json::json!({ "provider": provider, "request": canonical(request) });
+        Sha256::digest(source.to_string().as_bytes())
+            .iter()
+            .map(|x| format!("{x:02x}"))
+            .collect()
+    }
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(&key[..2]).join(format!("{key}.json"))
+    }
+    /// The cached completion for `request`, counting a hit or a miss.
+    ///
+    /// Unreadable entries count as misses and are replaced once the request completes.
+    pub fn get(&self, provider: &str, request: &Request) -> Option<CacheEntry> {
+        let entry = match self.mode {
+            CacheMode::ReadWrite => {
+                let path = self.path(&Self::key(provider, request));
+                std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|source| serde_json::from_str::<CacheEntry>(&source).ok())
+            }
+            CacheMode::Refresh => None,
+        };
+        let counter = if entry.is_some() { &self.counters.hits } else { &self.counters.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+    /// Stores a completion; failures are reported as warnings since the run itself succeeded.
+    pub fn put(&self, request: &Request, entry: &CacheEntry) {
+        let path = self.path(&Self::key(&entry.provider, request));
+        match write_atomically(&path, entry) {
+            Ok(()) => {
+                self.counters.stored.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(error) => {
+                eprintln!("failed to write cache entry {}: {error}", path.display());
+            }
+        }
+    }
+}
+
+/// Writes to a temporary file first, so concurrent runs never read half an entry.
+fn write_atomically(path: &Path, entry: &CacheEntry) -> std::io::Result<()> {
+    static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let source = serde_json::to_string_pretty(entry).map_err(std::io::Error::other)?;
+    let suffix = TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed);
+    let temporary = path.with_extension(format!("{}-{suffix}.tmp", std::process::id()));
+    std::fs::write(&temporary, source)?;
+    std::fs::rename(&temporary, path)
+}
+
+/// Sorts object keys recursively, e.g. of `logit_bias`, which is serialized in hash map order.
+fn canonical(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries = object.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(x, _), (y, _)| x.cmp(y));
+            Value::Object(entries.into_iter().map(|(key, value)| (key, canonical(value))).collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonical).collect()),
+        value => value,
+    }
+}
@@ -0,0 +1,270 @@
+//! Checking documents without evaluating them, see `xml-ai check`.
+//!
+//! Besides the errors [`DocumentNode::load`] reports, a check warns about
+//! mistakes the parser lets through: attributes it ignores, prompts that
+//! shadow each other, requests without any messages and breakpoints that
+//! are never evaluated or have nothing new to continue from.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ast::breakpoint::{BreakpointNode, SelectionPolicy};
+use crate::ast::document::DocumentNode;
+use crate::ast::message::MsgNode;
+use crate::ast::prompt::{PromptChildNode, PromptNode};
+use crate::ast::schema::SchemaNode;
+use crate::ast::set::SetNode;
+use crate::ast::tool::ToolNode;
+use crate::common::input::InputDeclaration;
+use crate::common::prompt::PromptAttributeEntry;
+use crate::error::Error;
+use crate::parser::{DslFormatError, DslFormatErrorList, SourceLocation};
+
+// ————————————————————————————————————————————————————————————————————————————
+// REPORT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The outcome of checking a document.
+#[derive(Debug)]
+pub struct CheckReport {
+    /// Why the document can’t be used, if it can’t.
+    pub error: Option<Error>,
+    /// Ordered by position.
+    pub warnings: Vec<Warning>,
+}
+
+impl CheckReport {
+    /// Checks a document given as source, like [`DocumentNode::parse`].
+    pub fn check(source: &str) -> Self {
+        Self::new(source, DocumentNode::parse(source))
+    }
+    /// Checks the document at `path`, like [`DocumentNode::load`].
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(source) => Self::new(&source, DocumentNode::load(path)),
+            Err(error) => Self { error: Some(Error::Io { path: path.to_path_buf(), error }), warnings: Vec::new() },
+        }
+    }
+    fn new(source: &str, document: Result<DocumentNode, Error>) -> Self {
+        let mut warnings = lint_source(source);
+        let error = match document {
+            Ok(document) => {
+                warnings.extend(document.lint());
+                None
+            }
+            Err(error) => Some(error),
+        };
+        warnings.sort_by_key(|x| x.location.span.map(|x| x.start.offset));
+        Self { error, warnings }
+    }
+    /// Whether the document can be used, and has no warnings unless `allow_warnings`.
+    pub fn passed(&self, allow_warnings: bool) -> bool {
+        self.error.is_none() && (allow_warnings || self.warnings.is_empty())
+    }
+}
+
+/// A likely mistake, which doesn’t keep the document from being evaluated.
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub message: String,
+    pub hint: Option<String>,
+    pub location: SourceLocation,
+}
+
+impl Warning {
+    fn new(message: impl Into<String>, location: SourceLocation) -> Self {
+        Self { message: message.into(), hint: None, location }
+    }
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for Warning {}
+impl DslFormatError for Warning {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn location(&self) -> Option<&SourceLocation> {
+        Some(&self.location)
+    }
+    fn hint(&self) -> Option<String> {
+        self.hint.clone()
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SOURCE
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Warnings about what the parser drops, which isn’t in the [`DocumentNode`].
+fn lint_source(source: &str) -> Vec<Warning> {
+    let Ok(node) = html_ast::parser::parse_from_fragment(source).into_result() else {
+        // Reported by the parser.
+        return Vec::new()
+    };
+    let mut warnings = Vec::new();
+    for element in node.flatten().iter().filter_map(html_ast::Node::as_element) {
+        lint_attributes(element, &mut warnings);
+        if PromptNode::matches(&element.tag) {
+            for child in element.children.iter().filter_map(html_ast::Node::as_element) {
+                lint_attributes(child, &mut warnings);
+                lint_nested_breakpoints(&child.children, &mut warnings);
+            }
+        }
+    }
+    warnings
+}
+
+fn lint_attributes(element: &html_ast::Element, warnings: &mut Vec<Warning>) {
+    let tag = &element.tag;
+    let known = if MsgNode::matches(tag) {
+        MsgNode::ATTRIBUTES
+    } else if BreakpointNode::matches(tag) {
+        BreakpointNode::ATTRIBUTES
+    } else if SchemaNode::matches(tag) {
+        SchemaNode::ATTRIBUTES
+    } else if ToolNode::matches(tag) {
+        ToolNode::ATTRIBUTES
+    } else if PromptNode::matches(tag) || SetNode::matches(tag) {
+        PromptAttributeEntry::KEYS
+    } else {
+        return
+    };
+    for (key, value) in element.attributes.iter() {
+        let key = key.as_str();
+        let is_input = PromptNode::matches(tag) && InputDeclaration::try_from_attribute(key, value.as_str()).is_some();
+        if known.contains(&key) || is_input {
+            continue
+        }
+        let message = format!("unknown attribute `{key}` on `<{}>` is ignored", tag.as_original());
+        let mut warning = Warning::new(message, SourceLocation::attribute(element, key));
+        if let Some(suggestion) = closest(key, known) {
+            warning = warning.with_hint(format!("did you mean `{suggestion}`?"));
+        }
+        warnings.push(warning);
+    }
+}
+
+/// A `<breakpoint>` inside a prompt’s child, e.g. a `<msg>`, is just markup.
+fn lint_nested_breakpoints(fragment: &html_ast::Fragment, warnings: &mut Vec<Warning>) {
+    for element in fragment.iter().filter_map(html_ast::Node::as_element) {
+        if BreakpointNode::matches(&element.tag) {
+            let warning = Warning::new("this breakpoint is never evaluated", SourceLocation::element(element))
+                .with_hint("only breakpoints directly inside a `<prompt>` are evaluated, move it out of its parent");
+            warnings.push(warning);
+        }
+        lint_nested_breakpoints(&element.children, warnings);
+    }
+}
+
+/// The most similar of `candidates`, if it is similar enough to be a typo.
+fn closest<'a>(given: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|x| (edit_distance(given, x), *x))
+        .filter(|(distance, x)| *distance <= 2 && *distance < x.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
+/// The Levenshtein distance, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != *y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// DOCUMENT
+// ————————————————————————————————————————————————————————————————————————————
+
+impl DocumentNode {
+    /// Warnings about the parsed document, see [`CheckReport`] for the complete check.
+    pub fn lint(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut names = BTreeMap::<&str, &PromptNode>::new();
+        for prompt in self.prompts() {
+            if let Some(first) = names.get(prompt.name()) {
+                let message = format!("prompt '{}' is shadowed by an earlier prompt with the same name", prompt.name());
+                let mut warning = Warning::new(message, SourceLocation::node(prompt.span, "prompt", Some("name")));
+                if let Some(span) = first.span {
+                    warning = warning.with_hint(format!("only the first, at {span}, can be invoked; rename one of them"));
+                }
+                warnings.push(warning);
+            } else {
+                names.insert(prompt.name(), prompt);
+            }
+            warnings.extend(prompt.lint());
+        }
+        warnings
+    }
+}
+
+impl PromptNode {
+    /// Warns about requests sent without any messages, breakpoints with nothing
+    /// new to continue from, and selections without choices.
+    fn lint(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut has_messages = false;
+        let mut follows_breakpoint = false;
+        let mut n = self.settings.n.as_ref().map_or(1, |x| x.0);
+        for child in self.children.iter() {
+            if let PromptChildNode::Breakpoint(breakpoint) = child
+                && breakpoint.select != SelectionPolicy::First
+                && n < 2 {
+                let key = if matches!(breakpoint.select, SelectionPolicy::Judge(_)) { "judge" } else { "select" };
+                let message = format!("`{key}` has nothing to choose from, this breakpoint generates a single choice");
+                let warning = Warning::new(message, SourceLocation::node(breakpoint.span, "breakpoint", Some(key)))
+                    .with_hint("set `n=\"3\"` (or more) on the `<prompt>` or a `<set>` before the breakpoint");
+                warnings.push(warning);
+            }
+            if let PromptChildNode::Set(set) = child
+                && let Some(value) = set.prompt_settings.n.as_ref() {
+                n = value.0;
+            }
+            match child {
+                PromptChildNode::Msg(_) => has_messages = true,
+                PromptChildNode::Breakpoint(breakpoint) if !has_messages => {
+                    let warning = Warning::new("this breakpoint sends an empty conversation", SourceLocation::node(breakpoint.span, "breakpoint", None))
+                        .with_hint("add a `<msg>` before it, e.g. `<msg role=\"user\">`");
+                    warnings.push(warning);
+                    // Its output is a message.
+                    has_messages = true;
+                }
+                PromptChildNode::Breakpoint(breakpoint) if follows_breakpoint => {
+                    let warning = Warning::new("this breakpoint has nothing new to continue from", SourceLocation::node(breakpoint.span, "breakpoint", None))
+                        .with_hint("the conversation ends with the previous breakpoint’s output, add a `<msg>` between the two");
+                    warnings.push(warning);
+                }
+                PromptChildNode::Breakpoint(_) | PromptChildNode::Set(_) => (),
+            }
+            match child {
+                PromptChildNode::Msg(_) => follows_breakpoint = false,
+                PromptChildNode::Breakpoint(_) => follows_breakpoint = true,
+                PromptChildNode::Set(_) => (),
+            }
+        }
+        if !has_messages {
+            let message = format!("prompt '{}' ends with an empty conversation", self.name());
+            let warning = Warning::new(message, SourceLocation::node(self.span, "prompt", None))
+                .with_hint("add a `<msg>` and a `<breakpoint role=\"assistant\">`");
+            warnings.push(warning);
+        }
+        warnings
+    }
+}
@@ -0,0 +1,200 @@
+//! Typed prompt inputs, declared via `input:NAME="of type TYPE"` on `<prompt>`.
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DECLARATIONS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Object,
+    Array,
+    /// A JSON Schema document, i.e. a JSON object.
+    Schema,
+    /// Any JSON value.
+    Json,
+}
+
+impl InputType {
+    /// The attribute prefix of input declarations.
+    pub const ATTRIBUTE_PREFIX: &'static str = "input:";
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "String",
+            Self::Number => "Number",
+            Self::Integer => "Integer",
+            Self::Boolean => "Boolean",
+            Self::Object => "Object",
+            Self::Array => "Array",
+            Self::Schema => "Schema",
+            Self::Json => "Json",
+        }
+    }
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Number => value.is_number(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Boolean => value.is_boolean(),
+            Self::Object | Self::Schema => value.is_object(),
+            Self::Array => value.is_array(),
+            Self::Json => true,
+        }
+    }
+    /// Checks the value against this type.
+    ///
+    /// Strings given for non-`String` types are parsed as JSON first, since
+    /// values passed on the command line are always strings.
+    pub fn coerce(&self, value: Value) -> Result<Value, Value> {
+        if self.matches(&value) && !(value.is_string() && *self == Self::Json) {
+            return Ok(value)
+        }
+        if let Value::String(source) = &value {
+            if let Ok(parsed) = serde_json::from_str::<Value>(source) && self.matches(&parsed) {
+                return Ok(parsed)
+            }
+            if *self == Self::Json {
+                return Ok(value)
+            }
+        }
+        Err(value)
+    }
+}
+
+impl std::fmt::Display for InputType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for InputType {
+    type Err = InvalidInputDeclaration;
+    /// Parses `of type TYPE`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidInputDeclaration { given: s.to_string() };
+        let mut words = s.split_whitespace();
+        let (Some("of"), Some("type"), Some(name), None) = (words.next(), words.next(), words.next(), words.next()) else {
+            return Err(invalid())
+        };
+        match name {
+            "String" => Ok(Self::String),
+            "Number" => Ok(Self::Number),
+            "Integer" => Ok(Self::Integer),
+            "Boolean" => Ok(Self::Boolean),
+            "Object" => Ok(Self::Object),
+            "Array" => Ok(Self::Array),
+            "Schema" => Ok(Self::Schema),
+            "Json" => Ok(Self::Json),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InputDeclaration {
+    pub name: String,
+    pub r#type: InputType,
+}
+
+impl InputDeclaration {
+    /// Parses an `input:NAME="of type TYPE"` attribute, `None` for any other attribute.
+    pub fn try_from_attribute(key: &str, value: &str) -> Option<Result<Self, InvalidInputDeclaration>> {
+        let name = key.strip_prefix(InputType::ATTRIBUTE_PREFIX)?;
+        let declaration = InputType::from_str(value).map(|r#type| Self {
+            name: name.to_string(),
+            r#type,
+        });
+        Some(declaration)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidInputDeclaration {
+    pub given: String,
+}
+
+impl std::fmt::Display for InvalidInputDeclaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected `of type TYPE` where TYPE is one of String, Number, Integer, Boolean, Object, Array, Schema or Json, given {:?}",
+            self.given,
+        )
+    }
+}
+
+impl std::error::Error for InvalidInputDeclaration {}
+
+// ————————————————————————————————————————————————————————————————————————————
+// VALUES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Input values by name.
+pub type InputValues = BTreeMap<String, Value>;
+
+/// How a `from="..."` binding renders its value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Strings verbatim, everything else as compact JSON.
+    #[default]
+    Text,
+    /// Strings verbatim, everything else as indented JSON.
+    Pretty,
+    /// Always JSON, i.e. strings are quoted.
+    Json,
+}
+
+impl InputFormat {
+    pub fn render(&self, value: &Value) -> String {
+        match (self, value) {
+            (Self::Text | Self::Pretty, Value::String(text)) => text.clone(),
+            (Self::Pretty, value) => serde_json::to_string_pretty(value).unwrap_or_default(),
+            (_, value) => value.to_string(),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = InvalidInputFormat;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "text" => Ok(Self::Text),
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            _ => Err(InvalidInputFormat { given: s.to_string() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidInputFormat {
+    pub given: String,
+}
+
+impl std::fmt::Display for InvalidInputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected `format` to be text, pretty or json, given {:?}", self.given)
+    }
+}
+
+impl std::error::Error for InvalidInputFormat {}
+
+/// The JSON type of a value, for error messages.
+pub fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
@@ -13,21 +13,26 @@ pub enum MessageRole {
 
 impl MessageRole {
     pub fn is_system(&self) -> bool {
-        match self {
-            Self::System => true,
-            _ => false,
-        }
+        matches!(self, Self::System)
     }
     pub fn is_assistant(&self) -> bool {
+        matches!(self, Self::Assistant)
+    }
+    pub fn is_user(&self) -> bool {
+        matches!(self, Self::User)
+    }
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Self::Assistant => true,
-            _ => false,
+            Self::System => "system",
+            Self::Assistant => "assistant",
+            Self::User => "user",
         }
     }
-    pub fn is_user(&self) -> bool {
+    pub fn message(&self, content: impl AsRef<str>) -> ai_client::request::Message {
         match self {
-            Self::User => true,
-            _ => false,
+            Self::System => ai_client::request::Message::system(content),
+            Self::Assistant => ai_client::request::Message::assistant(content),
+            Self::User => ai_client::request::Message::user(content),
         }
     }
 }
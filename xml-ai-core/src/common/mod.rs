@@ -3,6 +3,9 @@
 pub mod message;
 pub mod prompt;
 pub mod breakpoint;
+pub mod input;
+pub mod schema;
+pub mod selection;
 
 // ————————————————————————————————————————————————————————————————————————————
 // BASICS
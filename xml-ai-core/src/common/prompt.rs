@@ -9,6 +9,9 @@ use super::{Integer, Number};
 #[derive(Debug, Clone)]
 pub struct Model(pub String);
 
+#[derive(Debug, Clone)]
+pub struct Provider(pub String);
+
 // #[derive(Debug, Clone)]
 // pub struct Stream(pub String);
 
@@ -39,12 +42,25 @@ pub struct TopLogprobs(pub Integer);
 #[derive(Debug, Clone)]
 pub struct ResponseFormat(pub ResponseFormatType);
 
+#[derive(Debug, Clone)]
+pub struct TextFormat(pub TextFormatType);
+
 impl FromStr for Model {
     type Err = InvalidModelAttribute;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(Self(s.to_string()))
     }
 }
+impl FromStr for Provider {
+    type Err = InvalidProviderAttribute;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim();
+        if normalized.is_empty() {
+            return Err(InvalidProviderAttribute)
+        }
+        Ok(Self(normalized.to_string()))
+    }
+}
 // impl FromStr for Stream {
 //     type Err = InvalidStreamAttribute;
 //     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -105,10 +121,19 @@ impl FromStr for ResponseFormat {
         Ok(Self(ResponseFormatType::from_str(s).map_err(|_| InvalidResponseFormatAttribute)?))
     }
 }
+impl FromStr for TextFormat {
+    type Err = InvalidTextFormatAttribute;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(TextFormatType::from_str(s).map_err(|_| InvalidTextFormatAttribute)?))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct InvalidModelAttribute;
 
+#[derive(Debug, Clone)]
+pub struct InvalidProviderAttribute;
+
 #[derive(Debug, Clone)]
 pub struct InvalidStreamAttribute;
 
@@ -139,11 +164,19 @@ pub struct InvalidTopLogprobsAttribute;
 #[derive(Debug, Clone)]
 pub struct InvalidResponseFormatAttribute;
 
+#[derive(Debug, Clone)]
+pub struct InvalidTextFormatAttribute;
+
 impl std::fmt::Display for InvalidModelAttribute {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "InvalidModelAttribute")
     }
 }
+impl std::fmt::Display for InvalidProviderAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InvalidProviderAttribute")
+    }
+}
 impl std::fmt::Display for InvalidStreamAttribute {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "InvalidStreamAttribute")
@@ -194,8 +227,14 @@ impl std::fmt::Display for InvalidResponseFormatAttribute {
         write!(f, "InvalidResponseFormatAttribute")
     }
 }
+impl std::fmt::Display for InvalidTextFormatAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InvalidTextFormatAttribute")
+    }
+}
 
 impl std::error::Error for InvalidModelAttribute {}
+impl std::error::Error for InvalidProviderAttribute {}
 impl std::error::Error for InvalidStreamAttribute {}
 impl std::error::Error for InvalidTemperatureAttribute {}
 impl std::error::Error for InvalidNAttribute {}
@@ -206,6 +245,7 @@ impl std::error::Error for InvalidPresencePenaltyAttribute {}
 impl std::error::Error for InvalidLogprobsAttribute {}
 impl std::error::Error for InvalidTopLogprobsAttribute {}
 impl std::error::Error for InvalidResponseFormatAttribute {}
+impl std::error::Error for InvalidTextFormatAttribute {}
 
 // ————————————————————————————————————————————————————————————————————————————
 // ATTRIBUTE TYPES - SPECIAL
@@ -244,6 +284,37 @@ impl FromStr for ResponseFormatType {
     }
 }
 
+/// How the markup of a `<msg>` is rendered, see [`crate::ast::message_text`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextFormatType {
+    /// Structure only, e.g. list markers, without any Markdown syntax.
+    #[default]
+    Plain,
+    Markdown,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseErrorTextFormatType;
+
+impl std::fmt::Display for ParseErrorTextFormatType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid text format type")
+    }
+}
+
+impl std::error::Error for ParseErrorTextFormatType {}
+
+impl FromStr for TextFormatType {
+    type Err = ParseErrorTextFormatType;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "plain" => Ok(Self::Plain),
+            "markdown" => Ok(Self::Markdown),
+            _ => Err(ParseErrorTextFormatType),
+        }
+    }
+}
+
 // ————————————————————————————————————————————————————————————————————————————
 // ATTRIBUTE TYPES - ERROR TYPES
 // ————————————————————————————————————————————————————————————————————————————
@@ -252,6 +323,7 @@ impl FromStr for ResponseFormatType {
 pub enum InvalidAttribute {
     Name,
     Model,
+    Provider,
     Stream,
     Temperature,
     N,
@@ -262,6 +334,7 @@ pub enum InvalidAttribute {
     Logprobs,
     TopLogprobs,
     ResponseFormat,
+    TextFormat,
 }
 
 impl std::fmt::Display for InvalidAttribute {
@@ -269,6 +342,7 @@ impl std::fmt::Display for InvalidAttribute {
         match self {
             Self::Name => write!(f, "invalid `Name` attribute"),
             Self::Model => write!(f, "invalid `Model` attribute"),
+            Self::Provider => write!(f, "invalid `Provider` attribute"),
             Self::Stream => write!(f, "invalid `Stream` attribute"),
             Self::Temperature => write!(f, "invalid `Temperature` attribute"),
             Self::N => write!(f, "invalid `N` attribute"),
@@ -279,6 +353,7 @@ impl std::fmt::Display for InvalidAttribute {
             Self::Logprobs => write!(f, "invalid `Logprobs` attribute"),
             Self::TopLogprobs => write!(f, "invalid `TopLogprobs` attribute"),
             Self::ResponseFormat => write!(f, "invalid `ResponseFormat` attribute"),
+            Self::TextFormat => write!(f, "invalid `TextFormat` attribute"),
         }
     }
 }
@@ -292,6 +367,7 @@ impl std::fmt::Display for InvalidAttribute {
 pub enum PromptAttributeEntry {
     Name(String),
     Model(Model),
+    Provider(Provider),
     // Stream(Stream),
     Temperature(Temperature),
     N(N),
@@ -302,9 +378,16 @@ pub enum PromptAttributeEntry {
     Logprobs(Logprobs),
     TopLogprobs(TopLogprobs),
     ResponseFormat(ResponseFormat),
+    TextFormat(TextFormat),
 }
 
 impl PromptAttributeEntry {
+    /// The attributes [`PromptAttributeEntry::try_from`] recognizes.
+    pub const KEYS: &[&str] = &[
+        "name", "model", "provider", "temperature", "n", "max-tokens", "top-p",
+        "frequency-penalty", "presence-penalty", "logprobs", "top-logprobs", "response-format",
+        "text-format",
+    ];
     pub fn try_from(key: impl AsRef<str>, value: impl AsRef<str>) -> Option<Result<Self, InvalidAttribute>> {
         match key.as_ref() {
             "name" => {
@@ -317,6 +400,13 @@ impl PromptAttributeEntry {
                         .map_err(|_| InvalidAttribute::Model)
                 })
             }
+            "provider" => {
+                Some({
+                    Provider::from_str(value.as_ref())
+                        .map(Self::Provider)
+                        .map_err(|_| InvalidAttribute::Provider)
+                })
+            }
             // "stream" => {
             //     Some({
             //         Stream::from_str(value.as_ref())
@@ -387,6 +477,13 @@ impl PromptAttributeEntry {
                         .map_err(|_| InvalidAttribute::ResponseFormat)
                 })
             }
+            "text-format" => {
+                Some({
+                    TextFormat::from_str(value.as_ref())
+                        .map(Self::TextFormat)
+                        .map_err(|_| InvalidAttribute::TextFormat)
+                })
+            }
             _ => None,
         }
     }
@@ -396,6 +493,7 @@ impl PromptAttributeEntry {
 pub struct PromptSettings {
     pub name: Option<String>,
     pub model: Option<Model>,
+    pub provider: Option<Provider>,
     // pub stream: Option<Stream>,
     pub temperature: Option<Temperature>,
     pub n: Option<N>,
@@ -406,6 +504,7 @@ pub struct PromptSettings {
     pub logprobs: Option<Logprobs>,
     pub top_logprobs: Option<TopLogprobs>,
     pub response_format: Option<ResponseFormat>,
+    pub text_format: Option<TextFormat>,
 }
 
 impl PromptSettings {
@@ -414,18 +513,20 @@ impl PromptSettings {
     }
     pub fn merge(self, other: Self) -> Self {
         Self {
-            name: other.name.or_else(|| self.name),
-            model: other.model.or_else(|| self.model),
+            name: other.name.or(self.name),
+            model: other.model.or(self.model),
+            provider: other.provider.or(self.provider),
             // stream: other.stream.or_else(|| self.stream),
-            temperature: other.temperature.or_else(|| self.temperature),
-            n: other.n.or_else(|| self.n),
-            max_tokens: other.max_tokens.or_else(|| self.max_tokens),
-            top_p: other.top_p.or_else(|| self.top_p),
-            frequency_penalty: other.frequency_penalty.or_else(|| self.frequency_penalty),
-            presence_penalty: other.presence_penalty.or_else(|| self.presence_penalty),
-            logprobs: other.logprobs.or_else(|| self.logprobs),
-            top_logprobs: other.top_logprobs.or_else(|| self.top_logprobs),
-            response_format: other.response_format.or_else(|| self.response_format),
+            temperature: other.temperature.or(self.temperature),
+            n: other.n.or(self.n),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            top_p: other.top_p.or(self.top_p),
+            frequency_penalty: other.frequency_penalty.or(self.frequency_penalty),
+            presence_penalty: other.presence_penalty.or(self.presence_penalty),
+            logprobs: other.logprobs.or(self.logprobs),
+            top_logprobs: other.top_logprobs.or(self.top_logprobs),
+            response_format: other.response_format.or(self.response_format),
+            text_format: other.text_format.or(self.text_format),
         }
     }
     pub fn try_merge(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Option<Result<(), InvalidAttribute>> {
@@ -438,6 +539,10 @@ impl PromptSettings {
                 self.model = Some(value);
                 Some(Ok(()))
             }
+            Some(Ok(PromptAttributeEntry::Provider(value))) => {
+                self.provider = Some(value);
+                Some(Ok(()))
+            }
             // Some(Ok(PromptAttributeEntry::Stream(value))) => {
             //     self.stream = Some(value);
             //     Some(Ok(()))
@@ -478,6 +583,10 @@ impl PromptSettings {
                 self.response_format = Some(value);
                 Some(Ok(()))
             }
+            Some(Ok(PromptAttributeEntry::TextFormat(value))) => {
+                self.text_format = Some(value);
+                Some(Ok(()))
+            }
             Some(Err(error)) => Some(Err(error)),
             None => None,
         }
@@ -486,6 +595,7 @@ impl PromptSettings {
         Some(PromptArguments {
             name: self.name?,
             model: self.model,
+            provider: self.provider,
             // stream: self.stream,
             temperature: self.temperature,
             n: self.n,
@@ -496,6 +606,7 @@ impl PromptSettings {
             logprobs: self.logprobs,
             top_logprobs: self.top_logprobs,
             response_format: self.response_format,
+            text_format: self.text_format,
         })
     }
 }
@@ -504,6 +615,7 @@ impl PromptSettings {
 pub struct PromptArguments {
     pub name: String,
     pub model: Option<Model>,
+    pub provider: Option<Provider>,
     // pub stream: Option<Stream>,
     pub temperature: Option<Temperature>,
     pub n: Option<N>,
@@ -514,5 +626,27 @@ pub struct PromptArguments {
     pub logprobs: Option<Logprobs>,
     pub top_logprobs: Option<TopLogprobs>,
     pub response_format: Option<ResponseFormat>,
+    pub text_format: Option<TextFormat>,
 }
 
+
+impl PromptArguments {
+    /// The initial settings of a conversation, later `<set>` elements merge over these.
+    pub fn to_prompt_settings(&self) -> PromptSettings {
+        PromptSettings {
+            name: Some(self.name.clone()),
+            model: self.model.clone(),
+            provider: self.provider.clone(),
+            temperature: self.temperature.clone(),
+            n: self.n.clone(),
+            max_tokens: self.max_tokens.clone(),
+            top_p: self.top_p.clone(),
+            frequency_penalty: self.frequency_penalty.clone(),
+            presence_penalty: self.presence_penalty.clone(),
+            logprobs: self.logprobs.clone(),
+            top_logprobs: self.top_logprobs.clone(),
+            response_format: self.response_format.clone(),
+            text_format: self.text_format.clone(),
+        }
+    }
+}
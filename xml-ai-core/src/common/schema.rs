@@ -0,0 +1,400 @@
+//! A JSON Schema validator covering the commonly used keywords.
+//!
+//! Supported: boolean schemas, `type`, `enum`, `const`, `properties`,
+//! `required`, `additionalProperties`, `minProperties`, `maxProperties`,
+//! `items` (incl. tuple form), `prefixItems`, `minItems`, `maxItems`,
+//! `uniqueItems`, `minLength`, `maxLength`, `minimum`, `maximum`,
+//! `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`, `allOf`, `anyOf`,
+//! `oneOf`, `not` and local `$ref`s (e.g. `#/$defs/user`). Schemas with
+//! constraints it can’t check, e.g. `pattern` or `format`, are rejected when
+//! they’re loaded (see [`unsupported_keywords`]) rather than passing output
+//! that breaks them.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::input::json_type_name;
+
+// ————————————————————————————————————————————————————————————————————————————
+// VIOLATIONS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, empty for the root.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// The outcome of checking a breakpoint’s output, as recorded in the snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// The `<schema>` id or `input:NAME`, if the output was checked against a schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub valid: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<SchemaViolation>,
+}
+
+impl VerificationReport {
+    /// Parses `output` as JSON and, given a schema, validates it.
+    pub fn check(output: &str, schema: Option<(String, &Value)>) -> Self {
+        let (name, schema) = schema.unzip();
+        let errors = match parse_json_output(output) {
+            Ok(instance) => schema.map(|x| validate(x, &instance)).unwrap_or_default(),
+            Err(error) => vec![SchemaViolation { path: String::new(), message: format!("invalid JSON: {error}") }],
+        };
+        Self { schema: name, valid: errors.is_empty(), errors }
+    }
+}
+
+/// Output that failed verification and was repaired.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairAttempt {
+    pub output: String,
+    pub verification: VerificationReport,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// VALIDATION
+// ————————————————————————————————————————————————————————————————————————————
+
+/// All violations of `instance` against `schema`, empty if it’s valid.
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+    let mut validator = Validator { root: schema, violations: Vec::new(), depth: 0 };
+    validator.validate(schema, instance, "");
+    validator.violations
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    violations: Vec<SchemaViolation>,
+    /// Guards against cyclic `$ref`s.
+    depth: usize,
+}
+
+impl<'a> Validator<'a> {
+    const MAX_DEPTH: usize = 128;
+
+    fn report(&mut self, path: &str, message: impl Into<String>) {
+        self.violations.push(SchemaViolation { path: path.to_string(), message: message.into() });
+    }
+    /// Validates without reporting, for `anyOf`, `oneOf` and `not`.
+    fn is_valid(&self, schema: &'a Value, instance: &Value) -> bool {
+        let mut validator = Validator { root: self.root, violations: Vec::new(), depth: self.depth };
+        validator.validate(schema, instance, "");
+        validator.violations.is_empty()
+    }
+    fn validate(&mut self, schema: &'a Value, instance: &Value, path: &str) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return self.report(path, "no value is allowed here"),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            if self.depth >= Self::MAX_DEPTH {
+                return self.report(path, format!("`$ref` {reference:?} nests too deeply"))
+            }
+            match resolve_reference(self.root, reference) {
+                Some(target) => {
+                    self.depth += 1;
+                    self.validate(target, instance, path);
+                    self.depth -= 1;
+                }
+                None => self.report(path, format!("unresolvable `$ref` {reference:?}")),
+            }
+        }
+        if let Some(expected) = schema.get("type") {
+            let types = match expected {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|name| has_type(instance, name)) {
+                let given = json_type_name(instance);
+                self.report(path, format!("expected {}, given {given}", types.join(" or ")));
+                // Further keywords would only repeat the type mismatch.
+                return
+            }
+        }
+        if let Some(Value::Array(options)) = schema.get("enum")
+            && !options.iter().any(|x| json_eq(x, instance)) {
+            let options = options.iter().map(Value::to_string).collect::<Vec<_>>().join(", ");
+            self.report(path, format!("expected one of {options}, given {instance}"));
+        }
+        if let Some(expected) = schema.get("const")
+            && !json_eq(expected, instance) {
+            self.report(path, format!("expected {expected}, given {instance}"));
+        }
+        match instance {
+            Value::Object(object) => {
+                if let Some(Value::Array(required)) = schema.get("required") {
+                    for name in required.iter().filter_map(Value::as_str) {
+                        if !object.contains_key(name) {
+                            self.report(path, format!("missing required property {name:?}"));
+                        }
+                    }
+                }
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for (name, value) in object.iter() {
+                    let child = format!("{path}/{}", escape_pointer(name));
+                    match properties.and_then(|x| x.get(name)) {
+                        Some(property) => self.validate(property, value, &child),
+                        None => match schema.get("additionalProperties") {
+                            Some(Value::Bool(false)) => self.report(&child, "unexpected property"),
+                            Some(additional) => self.validate(additional, value, &child),
+                            None => (),
+                        },
+                    }
+                }
+                if let Some(min) = schema.get("minProperties").and_then(Value::as_u64)
+                    && (object.len() as u64) < min {
+                    self.report(path, format!("expected at least {min} properties, given {}", object.len()));
+                }
+                if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64)
+                    && (object.len() as u64) > max {
+                    self.report(path, format!("expected at most {max} properties, given {}", object.len()));
+                }
+            }
+            Value::Array(items) => {
+                let prefix = match (schema.get("prefixItems"), schema.get("items")) {
+                    (Some(Value::Array(prefix)), _) => prefix.as_slice(),
+                    (None, Some(Value::Array(prefix))) => prefix.as_slice(),
+                    _ => &[],
+                };
+                let rest = match (schema.get("prefixItems"), schema.get("items")) {
+                    (Some(_), items) => items,
+                    (None, Some(Value::Array(_))) => schema.get("additionalItems"),
+                    (None, items) => items,
+                };
+                for (index, item) in items.iter().enumerate() {
+                    let child = format!("{path}/{index}");
+                    if let Some(item_schema) = prefix.get(index).or(rest) {
+                        self.validate(item_schema, item, &child);
+                    }
+                }
+                if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                    && (items.len() as u64) < min {
+                    self.report(path, format!("expected at least {min} items, given {}", items.len()));
+                }
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                    && (items.len() as u64) > max {
+                    self.report(path, format!("expected at most {max} items, given {}", items.len()));
+                }
+                if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+                    let duplicate = items
+                        .iter()
+                        .enumerate()
+                        .any(|(index, x)| items[..index].iter().any(|y| json_eq(x, y)));
+                    if duplicate {
+                        self.report(path, "expected unique items");
+                    }
+                }
+            }
+            Value::String(text) => {
+                let length = text.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                    && length < min {
+                    self.report(path, format!("expected at least {min} characters, given {length}"));
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                    && length > max {
+                    self.report(path, format!("expected at most {max} characters, given {length}"));
+                }
+            }
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+                // Draft 4 spells exclusive bounds as booleans next to `minimum`/`maximum`.
+                let exclusive = |key: &str| schema.get(key) == Some(&Value::Bool(true));
+                if let Some(minimum) = bound("minimum")
+                    && (number < minimum || (exclusive("exclusiveMinimum") && number == minimum)) {
+                    self.report(path, format!("expected a value of at least {minimum}, given {number}"));
+                }
+                if let Some(maximum) = bound("maximum")
+                    && (number > maximum || (exclusive("exclusiveMaximum") && number == maximum)) {
+                    self.report(path, format!("expected a value of at most {maximum}, given {number}"));
+                }
+                if let Some(minimum) = bound("exclusiveMinimum")
+                    && number <= minimum {
+                    self.report(path, format!("expected a value greater than {minimum}, given {number}"));
+                }
+                if let Some(maximum) = bound("exclusiveMaximum")
+                    && number >= maximum {
+                    self.report(path, format!("expected a value less than {maximum}, given {number}"));
+                }
+                if let Some(divisor) = bound("multipleOf").filter(|x| *x > 0.0) {
+                    let quotient = number / divisor;
+                    if (quotient - quotient.round()).abs() > 1e-9 {
+                        self.report(path, format!("expected a multiple of {divisor}, given {number}"));
+                    }
+                }
+            }
+            _ => (),
+        }
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            for schema in schemas {
+                self.validate(schema, instance, path);
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("anyOf")
+            && !schemas.iter().any(|x| self.is_valid(x, instance)) {
+            self.report(path, "expected the value to match at least one schema of `anyOf`");
+        }
+        if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+            let matches = schemas.iter().filter(|x| self.is_valid(x, instance)).count();
+            if matches != 1 {
+                self.report(path, format!("expected the value to match exactly one schema of `oneOf`, matched {matches}"));
+            }
+        }
+        if let Some(not) = schema.get("not")
+            && self.is_valid(not, instance) {
+            self.report(path, "expected the value not to match the `not` schema");
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SUPPORT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A keyword of a schema the validator can’t check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedKeyword {
+    /// JSON pointer to the (sub)schema using it, empty for the root.
+    pub path: String,
+    pub keyword: String,
+}
+
+impl std::fmt::Display for UnsupportedKeyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "`{}`", self.keyword)
+        } else {
+            write!(f, "`{}` at {}", self.keyword, self.path)
+        }
+    }
+}
+
+/// Constraints [`validate`] doesn’t implement, which it would otherwise silently pass.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "pattern",
+    "format",
+    "patternProperties",
+    "propertyNames",
+    "dependencies",
+    "dependentRequired",
+    "dependentSchemas",
+    "contains",
+    "minContains",
+    "maxContains",
+    "if",
+    "then",
+    "else",
+    "unevaluatedProperties",
+    "unevaluatedItems",
+    "$dynamicRef",
+    "$recursiveRef",
+];
+
+/// Every keyword of `schema` and its subschemas that [`validate`] can’t check, empty if it supports all of them.
+///
+/// Only subschemas are searched, so e.g. an `enum` value with a `pattern` property is fine.
+pub fn unsupported_keywords(schema: &Value) -> Vec<UnsupportedKeyword> {
+    let mut unsupported = Vec::new();
+    find_unsupported(schema, "", &mut unsupported);
+    unsupported
+}
+
+fn find_unsupported(schema: &Value, path: &str, unsupported: &mut Vec<UnsupportedKeyword>) {
+    let Value::Object(schema) = schema else {
+        return
+    };
+    for (keyword, value) in schema.iter() {
+        if UNSUPPORTED_KEYWORDS.contains(&keyword.as_str()) {
+            unsupported.push(UnsupportedKeyword { path: path.to_string(), keyword: keyword.clone() });
+            continue
+        }
+        let child = format!("{path}/{}", escape_pointer(keyword));
+        match (keyword.as_str(), value) {
+            ("properties" | "$defs" | "definitions", Value::Object(schemas)) => {
+                for (name, schema) in schemas.iter() {
+                    find_unsupported(schema, &format!("{child}/{}", escape_pointer(name)), unsupported);
+                }
+            }
+            ("items" | "prefixItems" | "allOf" | "anyOf" | "oneOf", Value::Array(schemas)) => {
+                for (index, schema) in schemas.iter().enumerate() {
+                    find_unsupported(schema, &format!("{child}/{index}"), unsupported);
+                }
+            }
+            ("items" | "additionalItems" | "additionalProperties" | "not", schema) => {
+                find_unsupported(schema, &child, unsupported);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => instance.as_f64().is_some_and(|x| x.fract() == 0.0),
+        _ => false,
+    }
+}
+
+/// Equality where `1` and `1.0` are the same number.
+fn json_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(xs), Value::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| json_eq(x, y))
+        }
+        (Value::Object(xs), Value::Object(ys)) => {
+            xs.len() == ys.len() && xs.iter().all(|(key, x)| ys.get(key).is_some_and(|y| json_eq(x, y)))
+        }
+        (left, right) => left == right,
+    }
+}
+
+/// Resolves a local reference, i.e. `#` or a `#/...` JSON pointer.
+fn resolve_reference<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        return Some(root)
+    }
+    root.pointer(pointer)
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Parses model output as JSON, tolerating a surrounding Markdown code fence.
+pub fn parse_json_output(output: &str) -> Result<Value, serde_json::Error> {
+    let trimmed = output.trim();
+    let unfenced = trimmed
+        .strip_prefix("```")
+        .and_then(|x| x.strip_suffix("```"))
+        .map(|x| x.trim_start_matches(|c: char| c.is_ascii_alphanumeric()).trim());
+    match unfenced {
+        Some(unfenced) => serde_json::from_str(unfenced).or_else(|_| serde_json::from_str(trimmed)),
+        None => serde_json::from_str(trimmed),
+    }
+}
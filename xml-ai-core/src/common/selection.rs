@@ -0,0 +1,85 @@
+//! Picking one of several choices generated at a breakpoint, see `<breakpoint select="...">`.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Every choice of a breakpoint, as recorded in the snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Selection {
+    /// How the choice was selected, as given via `select` (or `judge:NAME`).
+    pub policy: String,
+    /// In the order the provider returned them.
+    pub choices: Vec<String>,
+    /// The index of the choice that continues the conversation.
+    pub selected: usize,
+}
+
+impl Selection {
+    /// The selected choice’s output.
+    pub fn output(&self) -> &str {
+        self.choices.get(self.selected).map(String::as_str).unwrap_or_default()
+    }
+}
+
+/// The index of the longest choice by characters, the first of equally long ones.
+pub fn longest(choices: &[String]) -> usize {
+    let mut best = 0;
+    for (index, choice) in choices.iter().enumerate() {
+        if choice.trim().chars().count() > choices[best].trim().chars().count() {
+            best = index;
+        }
+    }
+    best
+}
+
+/// The index of the first choice of the most common answer.
+///
+/// Choices count as the same answer if they are equal JSON values, e.g.
+/// objects with the same members in any order, or equal text otherwise,
+/// ignoring whitespace around them.
+pub fn majority(choices: &[String]) -> usize {
+    let answers = choices.iter().map(|x| Answer::new(x)).collect::<Vec<_>>();
+    let mut best = (0, 0);
+    for (index, answer) in answers.iter().enumerate() {
+        let votes = answers.iter().filter(|x| *x == answer).count();
+        if votes > best.1 {
+            best = (index, votes);
+        }
+    }
+    best.0
+}
+
+#[derive(PartialEq)]
+enum Answer<'a> {
+    Json(Value),
+    Text(&'a str),
+}
+
+impl<'a> Answer<'a> {
+    fn new(choice: &'a str) -> Self {
+        let choice = choice.trim();
+        match serde_json::from_str::<Value>(choice) {
+            Ok(value) => Self::Json(value),
+            Err(_) => Self::Text(choice),
+        }
+    }
+}
+
+/// The numbered choices as given to a judge prompt via its `choices` input.
+pub fn numbered(choices: &[String]) -> String {
+    choices
+        .iter()
+        .enumerate()
+        .map(|(index, choice)| format!("Choice {}:\n{}", index + 1, choice.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The choice a judge replied with: the first number in its reply, counting from 1.
+pub fn judgement(reply: &str, choices: usize) -> Option<usize> {
+    reply
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|x| !x.is_empty())
+        .and_then(|x| x.parse::<usize>().ok())
+        .filter(|x| (1..=choices).contains(x))
+        .map(|x| x - 1)
+}
@@ -0,0 +1,98 @@
+//! Walking a prompt without calling any model, see `xml-ai render`.
+//!
+//! The requests are built the same way [`PromptNode::invoke_into`] builds
+//! them, down to the body the client sends. Breakpoint outputs aren’t known
+//! without a model, so later requests carry a placeholder instead, see
+//! [`RenderedRequest::placeholder`].
+use serde::Serialize;
+
+use crate::ast::document::DocumentNode;
+use crate::ast::prompt::{PromptChildNode, PromptNode};
+use crate::common::input::InputValues;
+use crate::error::Error;
+use crate::runtime::{ConversationMessage, DocumentInvocation, PromptContext};
+
+/// A request the prompt would send.
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedRequest {
+    /// The breakpoint it evaluates, counting from 1; `None` for the reply
+    /// generated after the last child of a prompt that doesn’t end with a breakpoint.
+    pub breakpoint: Option<usize>,
+    pub provider: String,
+    /// The body as sent to the provider.
+    pub request: ai_client::request::Request,
+}
+
+impl RenderedRequest {
+    /// Stands in for the output of the given breakpoint in later requests.
+    pub fn placeholder(breakpoint: usize) -> String {
+        format!("{{{{output of breakpoint {breakpoint}}}}}")
+    }
+}
+
+impl DocumentNode {
+    /// The requests the invocation would send, in order; `resume_from` is ignored.
+    pub fn render(&self, document_invocation: &DocumentInvocation) -> Result<Vec<RenderedRequest>, Error> {
+        let prompt = self.find_prompt(&document_invocation.target_prompt)?;
+        let mut prompt_context = PromptContext::new(document_invocation.runtime_environment.clone());
+        self.load_into(&mut prompt_context)?;
+        prompt.render(&mut prompt_context, &document_invocation.inputs)
+    }
+}
+
+impl PromptNode {
+    /// Like [`PromptNode::invoke_into`] but only collects the requests.
+    ///
+    /// Verification and repairs depend on the output, so they aren’t rendered;
+    /// tools are offered as they would be, but never called.
+    pub fn render(&self, prompt_context: &mut PromptContext, inputs: &InputValues) -> Result<Vec<RenderedRequest>, Error> {
+        let inputs = self.bind_inputs(inputs)?;
+        prompt_context.conversation.prompt_settings = self.settings.to_prompt_settings();
+        let mut requests = Vec::new();
+        for child in self.children.iter() {
+            match child {
+                PromptChildNode::Msg(msg) => {
+                    let message = msg.role.message(msg.render(&inputs, prompt_context.conversation.prompt_settings.text_format())?);
+                    let message = ConversationMessage { evaluated: false, ..ConversationMessage::evaluated(message) };
+                    prompt_context.conversation.messages.push(message);
+                }
+                PromptChildNode::Breakpoint(breakpoint) => {
+                    let index = requests.len() + 1;
+                    let tools = breakpoint.tool_definitions(prompt_context)?;
+                    requests.push(prompt_context.render_request(Some(index), &tools, breakpoint.tool_choice.clone())?);
+                    let message = breakpoint.role.message(RenderedRequest::placeholder(index));
+                    prompt_context.conversation.messages.push(ConversationMessage::evaluated(message));
+                }
+                PromptChildNode::Set(set) => {
+                    prompt_context.conversation.prompt_settings.merge_mut(set.prompt_settings.clone());
+                }
+            }
+        }
+        if !prompt_context.conversation.already_evaluated() {
+            requests.push(prompt_context.render_request(None, &[], None)?);
+        }
+        Ok(requests)
+    }
+}
+
+impl PromptContext {
+    fn render_request(
+        &self,
+        breakpoint: Option<usize>,
+        tools: &[ai_client::request::Tool],
+        tool_choice: Option<ai_client::request::ToolChoice>,
+    ) -> Result<RenderedRequest, Error> {
+        let messages = self.conversation.messages
+            .iter()
+            .map(|x| x.message.clone())
+            .collect::<Vec<_>>();
+        let (_, provider, request_builder) = self.prepare_request(&messages, tools, tool_choice)?;
+        let provider_name = provider.name().to_string();
+        let request = ai_client::client::ClientBuilder::default()
+            .with_provider(provider)
+            .with_request_body(request_builder)
+            .build_request_body()
+            .map_err(ai_client::client::ClientError::from)?;
+        Ok(RenderedRequest { breakpoint, provider: provider_name, request })
+    }
+}
@@ -0,0 +1,162 @@
+//! The error type shared by the document loader, the runtime and the CLI.
+use std::path::PathBuf;
+
+use ai_client::client::{ApiError, ClientError};
+
+use crate::parser::DslFormatErrorList;
+use crate::runtime::InvocationError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file.
+    Io { path: PathBuf, error: std::io::Error },
+    /// The source isn’t well-formed HTML.
+    Html { path: Option<PathBuf>, errors: Vec<String> },
+    /// The source is well-formed HTML but not a valid document.
+    Dsl { path: Option<PathBuf>, errors: DslFormatErrorList },
+    /// Invalid settings, e.g. an unreadable providers file.
+    Configuration(String),
+    /// A failed request.
+    Client(ClientError),
+    Invocation(InvocationError),
+    /// Serializing a snapshot.
+    Serialize(String),
+    /// A snapshot file that couldn’t be read back in.
+    InvalidSnapshot { path: PathBuf, message: String },
+    /// A batch’s rows or results file that couldn’t be read.
+    InvalidBatch { path: PathBuf, message: String },
+    /// Some rows of a batch failed, their results carry the errors.
+    BatchFailed { failed: usize, total: usize },
+    /// Some documents didn’t pass `check`, the reports say why.
+    CheckFailed { failed: usize, total: usize },
+}
+
+impl Error {
+    /// A [sysexits](https://man.freebsd.org/cgi/man.cgi?sysexits) style process exit code.
+    pub fn exit_code(&self) -> i32 {
+        const EX_USAGE: i32 = 64;
+        const EX_DATAERR: i32 = 65;
+        const EX_UNAVAILABLE: i32 = 69;
+        const EX_SOFTWARE: i32 = 70;
+        const EX_IOERR: i32 = 74;
+        const EX_TEMPFAIL: i32 = 75;
+        const EX_PROTOCOL: i32 = 76;
+        const EX_NOPERM: i32 = 77;
+        const EX_CONFIG: i32 = 78;
+        match self {
+            Self::Io { .. } => EX_IOERR,
+            Self::Html { .. } | Self::Dsl { .. } | Self::InvalidSnapshot { .. } | Self::InvalidBatch { .. } => EX_DATAERR,
+            Self::CheckFailed { .. } => EX_DATAERR,
+            Self::BatchFailed { .. } => EX_TEMPFAIL,
+            Self::Configuration(_) => EX_CONFIG,
+            Self::Invocation(InvocationError::PromptNotFound { .. }) => EX_USAGE,
+            Self::Invocation(InvocationError::MissingInput { .. } | InvocationError::InvalidInput { .. }) => EX_USAGE,
+            Self::Invocation(InvocationError::SnapshotMismatch { .. } | InvocationError::VerificationFailed { .. }) => EX_DATAERR,
+            Self::Invocation(InvocationError::UnsupportedSchema { .. }) => EX_DATAERR,
+            Self::Invocation(InvocationError::MockExhausted { .. } | InvocationError::NoRecordedReply { .. }) => EX_DATAERR,
+            Self::Invocation(InvocationError::ToolRoundsExceeded { .. } | InvocationError::InvalidJudgement { .. }) => EX_DATAERR,
+            Self::Invocation(InvocationError::ToolFailed { .. }) => EX_UNAVAILABLE,
+            Self::Invocation(_) => EX_CONFIG,
+            Self::Serialize(_) => EX_SOFTWARE,
+            Self::Client(error) => match error {
+                ClientError::Configuration(_) => EX_CONFIG,
+                ClientError::Transport(error) if error.is_timeout() => EX_TEMPFAIL,
+                ClientError::Transport(_) => EX_UNAVAILABLE,
+                ClientError::Status(error) => match error.kind {
+                    Some(ApiError::AuthenticationError | ApiError::PermissionDeniedError) => EX_NOPERM,
+                    Some(ApiError::RateLimitError | ApiError::InternalServerError) => EX_TEMPFAIL,
+                    _ => EX_PROTOCOL,
+                },
+                ClientError::Api(_) | ClientError::Decode { .. } | ClientError::UnexpectedEnd => EX_PROTOCOL,
+                ClientError::Unrecorded { .. } => EX_DATAERR,
+            },
+        }
+    }
+    /// A suggestion for fixing the error, if there is an obvious one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Client(ClientError::Status(error)) => match error.kind.as_ref()? {
+                ApiError::AuthenticationError => Some("check the API key, e.g. `--key-file` or the provider’s `api-key-env`"),
+                ApiError::PermissionDeniedError => Some("the API key doesn’t have access to this model or endpoint"),
+                ApiError::NotFoundError => Some("check the model name and the provider’s base URL"),
+                ApiError::RateLimitError => Some("rate limited or out of quota, try again later"),
+                ApiError::InternalServerError => Some("the provider is having trouble, try again later"),
+                _ => None,
+            },
+            Self::Client(ClientError::Transport(_)) => Some("check the network connection and the provider’s base URL"),
+            Self::Client(ClientError::Unrecorded { .. }) => Some("the request changed since the cassette was recorded, record it again with `--record-cassette`"),
+            Self::Invocation(InvocationError::UnknownProvider { .. }) => Some("register it via `--providers` or `--base-url`"),
+            Self::Invocation(InvocationError::MissingInput { .. }) => Some("pass it via `--input NAME=VALUE` or `--input-file`"),
+            Self::Invocation(InvocationError::UnboundTool { .. }) => Some("add a `command=\"...\"` to the `<tool>`"),
+            Self::Invocation(InvocationError::ToolRoundsExceeded { .. }) => Some("raise `max-tool-rounds` on the breakpoint"),
+            Self::Invocation(InvocationError::InvalidJudgement { .. }) => Some("ask the judge to reply with just the number of the best choice"),
+            Self::BatchFailed { .. } => Some("rerun the same command to retry the failed rows"),
+            Self::Invocation(InvocationError::SnapshotMismatch { .. }) => Some("the prompt changed since the snapshot was taken, rerun without `--resume`"),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Html { path, errors } => {
+                if let Some(path) = path {
+                    write!(f, "{}: ", path.display())?;
+                }
+                write!(f, "invalid HTML: {}", errors.join(" ∙ "))
+            }
+            Self::Dsl { path: None, errors } => write!(f, "{errors}"),
+            // Like compilers do, `path:line:column: message`.
+            Self::Dsl { path: Some(path), errors } => {
+                let items = errors.errors
+                    .iter()
+                    .map(|x| match x.location().and_then(|x| x.span) {
+                        Some(span) => format!("{}:{span}: {x}", path.display()),
+                        None => format!("{}: {x}", path.display()),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{}", items.join(" ∙ "))
+            }
+            Self::Configuration(message) => write!(f, "invalid configuration: {message}"),
+            Self::Client(error) => write!(f, "{error}"),
+            Self::Invocation(error) => write!(f, "{error}"),
+            Self::Serialize(message) => write!(f, "{message}"),
+            Self::InvalidSnapshot { path, message } => write!(f, "{}: invalid snapshot: {message}", path.display()),
+            Self::InvalidBatch { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::BatchFailed { failed, total } => write!(f, "{failed} of {total} row(s) failed"),
+            Self::CheckFailed { failed, total } => write!(f, "{failed} of {total} document(s) failed the check"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Dsl { errors, .. } => Some(errors),
+            Self::Client(error) => Some(error),
+            Self::Invocation(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(value: ClientError) -> Self {
+        Self::Client(value)
+    }
+}
+
+impl From<InvocationError> for Error {
+    fn from(value: InvocationError) -> Self {
+        Self::Invocation(value)
+    }
+}
+
+impl From<DslFormatErrorList> for Error {
+    fn from(value: DslFormatErrorList) -> Self {
+        Self::Dsl { path: None, errors: value }
+    }
+}
@@ -2,7 +2,15 @@ extern crate super_html_ast as html_ast;
 extern crate super_ai_client as ai_client;
 
 pub mod ast;
+pub mod batch;
+pub mod cache;
+pub mod check;
+pub mod dry_run;
 pub mod common;
+pub mod error;
+pub mod mock;
 pub mod parser;
 pub mod runtime;
 pub mod snapshot;
+pub mod tool;
+pub mod usage;
@@ -0,0 +1,105 @@
+//! Answering breakpoints without a model, e.g. to test prompts offline.
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::Error;
+use crate::runtime::InvocationError;
+use crate::snapshot::ConversationSnapshot;
+
+/// Stands in for the provider when set as [`crate::runtime::RuntimeEnvironment::mock`].
+///
+/// Clones share their position in the script.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    replies: MockReplies,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Clone)]
+enum MockReplies {
+    /// Repeats the content of the last message.
+    Echo,
+    /// Replies in order, regardless of the request.
+    Script(Arc<[String]>),
+    /// The recorded reply to the same messages.
+    Replay(Arc<ConversationSnapshot>),
+}
+
+impl MockProvider {
+    /// The provider name recorded in the usage summary.
+    pub const NAME: &'static str = "mock";
+    pub fn echo() -> Self {
+        Self::new(MockReplies::Echo)
+    }
+    pub fn script(replies: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::new(MockReplies::Script(replies.into_iter().map(Into::into).collect()))
+    }
+    /// Answers each breakpoint with its output in `snapshot`, provided the messages before it match.
+    pub fn replay(snapshot: ConversationSnapshot) -> Self {
+        Self::new(MockReplies::Replay(Arc::new(snapshot)))
+    }
+    fn new(replies: MockReplies) -> Self {
+        Self { replies, next: Arc::default() }
+    }
+    /// Parses `echo`, `script:PATH` (a JSON array of strings) or `replay:PATH` (a snapshot).
+    pub fn from_spec(spec: &str) -> Result<Self, Error> {
+        let invalid = || Error::Configuration(format!("invalid mock `{spec}`, expected `echo`, `script:PATH` or `replay:PATH`"));
+        match spec.split_once(':') {
+            None if spec == "echo" => Ok(Self::echo()),
+            Some(("script", path)) => Self::load_script(path),
+            Some(("replay", path)) => Ok(Self::replay(ConversationSnapshot::load(path)?)),
+            _ => Err(invalid()),
+        }
+    }
+    pub fn load_script(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| Error::Io { path: path.to_path_buf(), error })?;
+        let replies = serde_json::from_str::<Vec<String>>(&source)
+            .map_err(|error| Error::Configuration(format!("{}: expected a JSON array of strings: {error}", path.display())))?;
+        Ok(Self::script(replies))
+    }
+    /// The reply to a request with the given messages.
+    pub fn reply(&self, messages: &[ai_client::request::Message]) -> Result<String, InvocationError> {
+        self.reply_message(messages).map(|x| x.content().to_string())
+    }
+    /// Like [`MockProvider::reply`], but a replayed reply may call tools.
+    pub fn reply_message(&self, messages: &[ai_client::request::Message]) -> Result<ai_client::request::Message, InvocationError> {
+        self.reply_choices(messages, 1).map(|mut x| x.swap_remove(0))
+    }
+    /// `n` choices (at least one) for a request with `n`: a script answers with its next `n`
+    /// replies, echoing repeats the last message and a replay returns the recorded choices, if any.
+    pub fn reply_choices(&self, messages: &[ai_client::request::Message], n: usize) -> Result<Vec<ai_client::request::Message>, InvocationError> {
+        let n = n.max(1);
+        match &self.replies {
+            MockReplies::Echo => {
+                let content = messages.last().map(|x| x.content().to_string()).unwrap_or_default();
+                Ok(vec![ai_client::request::Message::assistant(content); n])
+            }
+            MockReplies::Script(replies) => {
+                let index = self.next.fetch_add(n, Ordering::Relaxed);
+                replies
+                    .get(index..index + n)
+                    .map(|x| x.iter().map(ai_client::request::Message::assistant).collect())
+                    .ok_or(InvocationError::MockExhausted { replies: replies.len() })
+            }
+            MockReplies::Replay(snapshot) => {
+                let position = messages.len();
+                let recorded = snapshot.messages
+                    .iter()
+                    .map(|x| &x.message_payload)
+                    .take(position)
+                    .eq(messages.iter());
+                let snapshot = snapshot.messages
+                    .get(position)
+                    .filter(|x| recorded && x.evaluation_point)
+                    .ok_or(InvocationError::NoRecordedReply { position })?;
+                match snapshot.selection.as_ref().filter(|_| n > 1) {
+                    Some(selection) => Ok(selection.choices.iter().map(ai_client::request::Message::assistant).collect()),
+                    None => Ok(vec![snapshot.message_payload.clone()]),
+                }
+            }
+        }
+    }
+}
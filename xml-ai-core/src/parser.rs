@@ -1,26 +1,107 @@
+use std::collections::BTreeSet;
 use std::ops::Not;
-use std::rc::Rc;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::ast::breakpoint::BreakpointNode;
+use ai_client::request::ToolChoice;
+
+use crate::ast::breakpoint::{BreakpointNode, SchemaReference, SelectionPolicy, Verification};
 use crate::ast::document::{DocumentChildCode, DocumentNode};
 use crate::ast::message::MsgNode;
 use crate::ast::prompt::{PromptChildNode, PromptNode};
+use crate::ast::schema::{SchemaNode, SchemaSource};
 use crate::ast::set::SetNode;
+use crate::ast::tool::{ToolNode, ToolParameters};
+use crate::common::input::{InputDeclaration, InputFormat, InputType};
 use crate::common::message::MessageRole;
 use crate::common::prompt::PromptSettings;
+use crate::common::schema::{UnsupportedKeyword, unsupported_keywords};
+use crate::error::Error;
 
 // ————————————————————————————————————————————————————————————————————————————
 // ERROR HANDLING
 // ————————————————————————————————————————————————————————————————————————————
 
-pub trait DslFormatError: std::error::Error + std::fmt::Debug {
+pub trait DslFormatError: std::error::Error + std::fmt::Debug + Send + Sync {
     fn singleton(&self) -> DslFormatErrorList;
+    /// Where in the document the error is, see [`Located`].
+    fn location(&self) -> Option<&SourceLocation> {
+        None
+    }
+    /// How to fix it, if there’s something more to say than the message.
+    fn hint(&self) -> Option<String> {
+        None
+    }
+    /// The error, located at `location`.
+    fn at(&self, location: &SourceLocation) -> DslFormatErrorList {
+        self.singleton().located(location)
+    }
+}
+
+/// The element, and possibly the attribute, an error is about.
+#[derive(Debug, Clone, Default)]
+pub struct SourceLocation {
+    /// Missing for elements that weren’t parsed from source, e.g. ones the HTML parser implied.
+    pub span: Option<html_ast::Span>,
+    pub tag: Option<String>,
+    pub attribute: Option<String>,
+}
+
+impl SourceLocation {
+    pub fn element(element: &html_ast::Element) -> Self {
+        Self { span: element.span, tag: Some(element.tag.as_original().to_string()), attribute: None }
+    }
+    pub fn attribute(element: &html_ast::Element, key: impl AsRef<str>) -> Self {
+        Self {
+            span: element.attribute_span(key.as_ref()),
+            tag: Some(element.tag.as_original().to_string()),
+            attribute: Some(key.as_ref().to_string()),
+        }
+    }
+    /// An attribute of a parsed node, which only keeps the span of its element.
+    pub(crate) fn node(span: Option<html_ast::Span>, tag: &str, attribute: Option<&str>) -> Self {
+        Self { span, tag: Some(tag.to_string()), attribute: attribute.map(ToString::to_string) }
+    }
+}
+
+/// Formats as `<tag attribute>`.
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.tag.as_ref(), self.attribute.as_ref()) {
+            (Some(tag), Some(attribute)) => write!(f, "<{tag} {attribute}>"),
+            (Some(tag), None) => write!(f, "<{tag}>"),
+            (None, Some(attribute)) => write!(f, "`{attribute}`"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// An error along with where it is.
+#[derive(Debug, Clone)]
+pub struct Located {
+    pub error: Arc<dyn DslFormatError>,
+    pub location: SourceLocation,
+}
+impl std::fmt::Display for Located {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+impl std::error::Error for Located {}
+impl DslFormatError for Located {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn location(&self) -> Option<&SourceLocation> {
+        Some(&self.location)
+    }
+    fn hint(&self) -> Option<String> {
+        self.error.hint()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DslFormatErrorList {
-    pub errors: Vec<Rc<dyn DslFormatError>>,
+    pub errors: Vec<Arc<dyn DslFormatError>>,
 }
 
 impl DslFormatErrorList {
@@ -33,7 +114,7 @@ impl DslFormatErrorList {
     pub fn with_capacity(len: usize) -> Self {
         Self { errors: Vec::<_>::with_capacity(len) }
     }
-    pub fn new(item: Rc<dyn DslFormatError>) -> Self {
+    pub fn new(item: Arc<dyn DslFormatError>) -> Self {
         let errors = vec![ item ];
         Self { errors }
     }
@@ -41,19 +122,33 @@ impl DslFormatErrorList {
         self.errors.extend(other.errors);
         self
     }
-    pub fn push<T: DslFormatError + 'static>(&mut self, new: Rc<T>) {
+    pub fn push<T: DslFormatError + 'static>(&mut self, new: Arc<T>) {
         self.errors.push(new);
     }
     pub fn extend(&mut self, other: Self) {
         self.errors.extend(other.errors);
     }
-    pub fn join<T: DslFormatError + 'static>(mut self, next: Rc<T>) {
+    pub fn join<T: DslFormatError + 'static>(mut self, next: Arc<T>) {
         self.errors.push(next);
     }
+    /// Locates the errors that aren’t yet, errors of nested elements keep their own location.
+    pub fn located(mut self, location: &SourceLocation) -> Self {
+        for error in self.errors.iter_mut() {
+            if error.location().is_none() {
+                let located = Located { error: error.clone(), location: location.clone() };
+                *error = Arc::new(located);
+            }
+        }
+        self
+    }
+    /// Each error prefixed with its `line:column`, if known.
     pub fn joined(&self, separator: impl AsRef<str>) -> String {
         self.errors
             .iter()
-            .map(|x| format!("{x}"))
+            .map(|x| match x.location().and_then(|x| x.span) {
+                Some(span) => format!("{span}: {x}"),
+                None => format!("{x}"),
+            })
             .collect::<Vec<_>>()
             .join(separator.as_ref())
     }
@@ -66,6 +161,8 @@ impl std::fmt::Display for DslFormatErrorList {
     }
 }
 
+impl std::error::Error for DslFormatErrorList {}
+
 impl<T: DslFormatError> From<T> for DslFormatErrorList {
     fn from(value: T) -> Self {
         value.singleton()
@@ -79,15 +176,13 @@ impl<T: DslFormatError> From<T> for DslFormatErrorList {
 impl MsgNode {
     pub fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
         if Self::matches(&element.tag).not() {
-            return Err(DslFormatErrorList::new(Rc::new(InvalidMessageNode)))
+            return Err(DslFormatErrorList::new(Arc::new(InvalidMessageNode)))
         }
         let role = element.attributes
             .get("role")
-            .ok_or_else(|| {
-                InvalidMessageMissingRole
-            })?;
+            .ok_or_else(|| InvalidMessageMissingRole.at(&SourceLocation::element(&element)))?;
         let role = MessageRole::from_str(role.as_str())
-            .map_err(|x| InvalidMessageAttribute(x))?;
+            .map_err(|error| InvalidMessageAttribute(error).at(&SourceLocation::attribute(&element, "role")))?;
         let children = element.children;
         Ok(Self {
             role,
@@ -106,7 +201,7 @@ impl std::fmt::Display for InvalidMessageNode {
 }
 impl std::error::Error for InvalidMessageNode {}
 impl DslFormatError for InvalidMessageNode {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
 }
 
 #[derive(Debug, Clone)]
@@ -118,7 +213,10 @@ impl std::fmt::Display for InvalidMessageAttribute {
 }
 impl std::error::Error for InvalidMessageAttribute {}
 impl DslFormatError for InvalidMessageAttribute {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("the role is one of `system`, `user` or `assistant`"))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -130,7 +228,10 @@ impl std::fmt::Display for InvalidMessageMissingRole {
 }
 impl std::error::Error for InvalidMessageMissingRole {}
 impl DslFormatError for InvalidMessageMissingRole {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("add `role=\"user\"`, `role=\"system\"` or `role=\"assistant\"`"))
+    }
 }
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -138,19 +239,111 @@ impl DslFormatError for InvalidMessageMissingRole {
 // ————————————————————————————————————————————————————————————————————————————
 
 impl BreakpointNode {
-    pub fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
+    /// Parses a breakpoint of a prompt declaring the given `inputs`.
+    ///
+    /// A `schema="..."` naming a `Schema` input refers to that input, otherwise
+    /// to a `<schema>` of the document. Without `schema="..."`,
+    /// `verification="schema"` uses the prompt’s only `Schema` input.
+    pub fn from_element(element: html_ast::Element, inputs: &[InputDeclaration]) -> Result<Self, DslFormatErrorList> {
         if Self::matches(&element.tag).not() {
-            return Err(DslFormatErrorList::new(Rc::new(InvalidBreakpointNode)))
+            return Err(DslFormatErrorList::new(Arc::new(InvalidBreakpointNode)))
         }
+        let at = |key: &str| SourceLocation::attribute(&element, key);
         let role = element.attributes
             .get("role")
-            .ok_or_else(|| {
-                InvalidBreakpointAttribute
-            })?;
+            .ok_or_else(|| InvalidBreakpointAttribute { given: None }.at(&SourceLocation::element(&element)))?;
         let role = MessageRole::from_str(role.as_str())
-            .map_err(|_| InvalidBreakpointAttribute)?;
+            .map_err(|_| InvalidBreakpointAttribute { given: Some(role.as_str().to_string()) }.at(&at("role")))?;
+        let verification = element.attributes
+            .get("verification")
+            .map(|x| Verification::from_str(x.as_str()))
+            .transpose()
+            .map_err(|error| InvalidBreakpointVerification(error).at(&at("verification")))?;
+        let schema_inputs = inputs
+            .iter()
+            .filter(|x| x.r#type == InputType::Schema)
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        let schema = match element.attributes.get("schema").map(|x| x.as_str()) {
+            Some(name) if schema_inputs.contains(&name) => Some(SchemaReference::Input(name.to_string())),
+            Some(id) => Some(SchemaReference::Document(id.to_string())),
+            None if verification == Some(Verification::Schema) => match schema_inputs.as_slice() {
+                [name] => Some(SchemaReference::Input(name.to_string())),
+                _ => return Err(MissingBreakpointSchema.at(&at("verification"))),
+            },
+            None => None,
+        };
+        // A schema implies schema verification.
+        let verification = verification.or(schema.as_ref().map(|_| Verification::Schema));
+        let repair = element.attributes.get("repair").map(|x| x.as_str().to_string());
+        let max_attempts = element.attributes
+            .get("max-attempts")
+            .map(|x| x.as_str().trim().parse::<u32>().ok().filter(|x| *x > 0).ok_or_else(|| InvalidBreakpointMaxAttempts {
+                given: x.as_str().to_string(),
+            }.at(&at("max-attempts"))))
+            .transpose()?;
+        if verification.is_none() && (repair.is_some() || max_attempts.is_some()) {
+            let key = if repair.is_some() { "repair" } else { "max-attempts" };
+            return Err(RepairWithoutVerification.at(&at(key)))
+        }
+        let max_attempts = max_attempts.unwrap_or(match repair {
+            Some(_) => Self::DEFAULT_REPAIR_MAX_ATTEMPTS,
+            None => Self::DEFAULT_MAX_ATTEMPTS,
+        });
+        let tools = element.attributes
+            .get("tools")
+            .map(|x| {
+                x.as_str()
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|x| !x.is_empty())
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let tool_choice = element.attributes
+            .get("tool-choice")
+            .map(|x| match x.as_str().trim() {
+                "none" => Ok(ToolChoice::None),
+                "auto" => Ok(ToolChoice::Auto),
+                "required" => Ok(ToolChoice::Required),
+                name if tools.iter().any(|x| x == name) => Ok(ToolChoice::function(name)),
+                given => Err(InvalidBreakpointToolChoice { given: given.to_string() }.at(&at("tool-choice"))),
+            })
+            .transpose()?;
+        let max_tool_rounds = element.attributes
+            .get("max-tool-rounds")
+            .map(|x| x.as_str().trim().parse::<u32>().ok().filter(|x| *x > 0).ok_or_else(|| InvalidBreakpointMaxToolRounds {
+                given: x.as_str().to_string(),
+            }.at(&at("max-tool-rounds"))))
+            .transpose()?;
+        if tools.is_empty() && (tool_choice.is_some() || max_tool_rounds.is_some()) {
+            let key = if tool_choice.is_some() { "tool-choice" } else { "max-tool-rounds" };
+            return Err(ToolOptionsWithoutTools.at(&at(key)))
+        }
+        if !tools.is_empty() && role != MessageRole::Assistant {
+            return Err(ToolsRequireAssistant.at(&at("role")))
+        }
+        let select = match (element.attributes.get("select"), element.attributes.get("judge")) {
+            (Some(_), Some(_)) => return Err(ConflictingBreakpointSelection.at(&at("judge"))),
+            (None, Some(name)) => SelectionPolicy::Judge(name.as_str().trim().to_string()),
+            (Some(policy), None) => SelectionPolicy::from_str(policy.as_str())
+                .map_err(|error| InvalidBreakpointSelect(error).at(&at("select")))?,
+            (None, None) => SelectionPolicy::First,
+        };
+        if select == SelectionPolicy::PassesSchema && verification.is_none() {
+            return Err(SelectWithoutVerification.at(&at("select")))
+        }
         Ok(Self {
+            span: element.span,
             role,
+            verification,
+            schema,
+            max_attempts,
+            repair,
+            tools,
+            tool_choice,
+            max_tool_rounds: max_tool_rounds.unwrap_or(Self::DEFAULT_MAX_TOOL_ROUNDS),
+            select,
         })
     }
 }
@@ -164,19 +357,188 @@ impl std::fmt::Display for InvalidBreakpointNode {
 }
 impl std::error::Error for InvalidBreakpointNode {}
 impl DslFormatError for InvalidBreakpointNode {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
 }
 
 #[derive(Debug, Clone)]
-pub struct InvalidBreakpointAttribute;
+pub struct InvalidBreakpointAttribute {
+    /// The role given, if any.
+    pub given: Option<String>,
+}
 impl std::fmt::Display for InvalidBreakpointAttribute {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "invalid breakpoint attribute")
+        match self.given.as_ref() {
+            Some(given) => write!(f, "invalid breakpoint attribute `role`: unrecognized role {given:?}"),
+            None => write!(f, "invalid breakpoint: missing role attribute"),
+        }
     }
 }
 impl std::error::Error for InvalidBreakpointAttribute {}
 impl DslFormatError for InvalidBreakpointAttribute {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("a breakpoint is usually `role=\"assistant\"`, the role is one of `system`, `user` or `assistant`"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidBreakpointVerification(pub crate::ast::breakpoint::InvalidVerification);
+impl std::fmt::Display for InvalidBreakpointVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid breakpoint attribute `verification`: {}", self.0)
+    }
+}
+impl std::error::Error for InvalidBreakpointVerification {}
+impl DslFormatError for InvalidBreakpointVerification {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidBreakpointMaxAttempts {
+    pub given: String,
+}
+impl std::fmt::Display for InvalidBreakpointMaxAttempts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid breakpoint attribute `max-attempts`: expected a positive integer, given {:?}", self.given)
+    }
+}
+impl std::error::Error for InvalidBreakpointMaxAttempts {}
+impl DslFormatError for InvalidBreakpointMaxAttempts {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepairWithoutVerification;
+impl std::fmt::Display for RepairWithoutVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`<breakpoint>` attributes `repair` and `max-attempts` require `verification` or `schema`")
+    }
+}
+impl std::error::Error for RepairWithoutVerification {}
+impl DslFormatError for RepairWithoutVerification {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("add `verification=\"json\"`, or a `schema=\"...\"` to check the reply against"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MissingBreakpointSchema;
+impl std::fmt::Display for MissingBreakpointSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`<breakpoint verification=\"schema\">` needs a `schema=\"...\"` attribute unless the prompt declares exactly one input of type Schema"
+        )
+    }
+}
+impl std::error::Error for MissingBreakpointSchema {}
+impl DslFormatError for MissingBreakpointSchema {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidBreakpointToolChoice {
+    pub given: String,
+}
+impl std::fmt::Display for InvalidBreakpointToolChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid breakpoint attribute `tool-choice`: expected `auto`, `none`, `required` or one of the breakpoint’s `tools`, given {:?}",
+            self.given,
+        )
+    }
+}
+impl std::error::Error for InvalidBreakpointToolChoice {}
+impl DslFormatError for InvalidBreakpointToolChoice {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidBreakpointMaxToolRounds {
+    pub given: String,
+}
+impl std::fmt::Display for InvalidBreakpointMaxToolRounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid breakpoint attribute `max-tool-rounds`: expected a positive integer, given {:?}", self.given)
+    }
+}
+impl std::error::Error for InvalidBreakpointMaxToolRounds {}
+impl DslFormatError for InvalidBreakpointMaxToolRounds {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolOptionsWithoutTools;
+impl std::fmt::Display for ToolOptionsWithoutTools {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`<breakpoint>` attributes `tool-choice` and `max-tool-rounds` require `tools`")
+    }
+}
+impl std::error::Error for ToolOptionsWithoutTools {}
+impl DslFormatError for ToolOptionsWithoutTools {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolsRequireAssistant;
+impl std::fmt::Display for ToolsRequireAssistant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "only `<breakpoint role=\"assistant\">` may call `tools`")
+    }
+}
+impl std::error::Error for ToolsRequireAssistant {}
+impl DslFormatError for ToolsRequireAssistant {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("the model calls tools, so use `role=\"assistant\"`"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidBreakpointSelect(pub crate::ast::breakpoint::InvalidSelectionPolicy);
+impl std::fmt::Display for InvalidBreakpointSelect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid breakpoint attribute `select`: {}", self.0)
+    }
+}
+impl std::error::Error for InvalidBreakpointSelect {}
+impl DslFormatError for InvalidBreakpointSelect {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("to let another prompt pick the choice, use `judge=\"NAME\"` instead"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConflictingBreakpointSelection;
+impl std::fmt::Display for ConflictingBreakpointSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`<breakpoint>` attributes `select` and `judge` can’t be combined")
+    }
+}
+impl std::error::Error for ConflictingBreakpointSelection {}
+impl DslFormatError for ConflictingBreakpointSelection {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("`judge` is a selection policy of its own, remove `select`"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectWithoutVerification;
+impl std::fmt::Display for SelectWithoutVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`<breakpoint select=\"passes-schema\">` requires `verification` or `schema`")
+    }
+}
+impl std::error::Error for SelectWithoutVerification {}
+impl DslFormatError for SelectWithoutVerification {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("add `verification=\"json\"`, or a `schema=\"...\"` to check the choices against"))
+    }
 }
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -189,8 +551,9 @@ impl SetNode {
         for (key, value) in element.attributes.iter() {
             match prompt_settings.try_merge(key, value.as_str()) {
                 Some(Ok(())) => (),
-                Some(Err(_)) => {
-                    return Err(DslFormatErrorList::new(Rc::new(InvalidSetAttribute)))
+                Some(Err(error)) => {
+                    let invalid = InvalidSetAttribute { attribute: key.to_string(), given: value.as_str().to_string(), error };
+                    return Err(invalid.at(&SourceLocation::attribute(&element, key)))
                 },
                 None => (),
             }
@@ -210,20 +573,24 @@ impl std::fmt::Display for InvalidSetNode {
 }
 impl std::error::Error for InvalidSetNode {}
 impl DslFormatError for InvalidSetNode {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
 }
 
 
 #[derive(Debug, Clone)]
-pub struct InvalidSetAttribute;
+pub struct InvalidSetAttribute {
+    pub attribute: String,
+    pub given: String,
+    pub error: crate::common::prompt::InvalidAttribute,
+}
 impl std::fmt::Display for InvalidSetAttribute {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "invalid set attribute")
+        write!(f, "invalid set attribute `{}`: {}, given {:?}", self.attribute, self.error, self.given)
     }
 }
 impl std::error::Error for InvalidSetAttribute {}
 impl DslFormatError for InvalidSetAttribute {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
 }
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -231,31 +598,39 @@ impl DslFormatError for InvalidSetAttribute {
 // ————————————————————————————————————————————————————————————————————————————
 
 impl PromptChildNode {
-    pub fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
+    /// Errors are located at the element, unless they are more specific.
+    pub fn from_element(element: html_ast::Element, inputs: &[InputDeclaration]) -> Result<Self, DslFormatErrorList> {
+        let location = SourceLocation::element(&element);
         if MsgNode::matches(&element.tag) {
-            return MsgNode::from_element(element).map(Self::Msg)
+            return MsgNode::from_element(element).map(Self::Msg).map_err(|x| x.located(&location))
         }
         if BreakpointNode::matches(&element.tag) {
-            return BreakpointNode::from_element(element).map(Self::Breakpoint)
+            return BreakpointNode::from_element(element, inputs).map(Self::Breakpoint).map_err(|x| x.located(&location))
         }
         if SetNode::matches(&element.tag) {
-            return SetNode::from_element(element).map(Self::Set)
+            return SetNode::from_element(element).map(Self::Set).map_err(|x| x.located(&location))
         }
-        Err(DslFormatErrorList::new(Rc::new(InvalidPromptChild)))
+        let invalid = InvalidPromptChild { tag: element.tag.as_original().to_string() };
+        Err(invalid.at(&SourceLocation::element(&element)))
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct InvalidPromptChild;
+pub struct InvalidPromptChild {
+    pub tag: String,
+}
 impl std::fmt::Display for InvalidPromptChild {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "invalid prompt child")
+        write!(f, "invalid prompt child `<{}>`", self.tag)
     }
 }
 impl std::error::Error for InvalidPromptChild {}
 impl DslFormatError for InvalidPromptChild {
     fn singleton(&self) -> DslFormatErrorList {
-        DslFormatErrorList::new(Rc::new(self.clone()))
+        DslFormatErrorList::new(Arc::new(self.clone()))
+    }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("a `<prompt>` contains `<msg>`, `<breakpoint>` and `<set>` elements, wrap markup in a `<msg>`"))
     }
 }
 
@@ -266,25 +641,35 @@ impl DslFormatError for InvalidPromptChild {
 impl PromptNode {
     pub fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
         if Self::matches(&element.tag).not() {
-            return Err(DslFormatErrorList::new(Rc::new(InvalidPromptNode)))
+            return Err(DslFormatErrorList::new(Arc::new(InvalidPromptNode)))
         }
+        let span = element.span;
         let mut prompt_settings = PromptSettings::default();
+        let mut inputs = Vec::<InputDeclaration>::new();
         for (key, value) in element.attributes.iter() {
+            if let Some(input) = InputDeclaration::try_from_attribute(key.as_str(), value.as_str()) {
+                let input = input.map_err(|error| InvalidInputDeclarationAttribute {
+                    attribute: key.to_string(),
+                    error,
+                }.at(&SourceLocation::attribute(&element, key)))?;
+                inputs.push(input);
+                continue
+            }
             match prompt_settings.try_merge(key, value.as_str()) {
                 Some(Ok(())) => (),
                 Some(Err(error)) => {
-                    return Err(DslFormatErrorList::new(Rc::new(InvalidPromptAttribute(error))))
+                    return Err(InvalidPromptAttribute(error).at(&SourceLocation::attribute(&element, key)))
                 },
                 None => (),
             }
         }
         let prompt_attributes = prompt_settings
             .build_to_prompt_arguments()
-            .ok_or_else(|| InvalidPromptMissingName)?;
+            .ok_or_else(|| InvalidPromptMissingName.at(&SourceLocation::element(&element)))?;
         let mut items = Vec::<PromptChildNode>::with_capacity(element.children.len());
         let mut errors = DslFormatErrorList::with_capacity(element.children.len());
         for child in element.extract_child_elements() {
-            match PromptChildNode::from_element(child) {
+            match PromptChildNode::from_element(child, &inputs) {
                 Ok(item) => {
                     items.push(item);
                 }
@@ -293,12 +678,19 @@ impl PromptNode {
                 }
             }
         }
+        for item in items.iter() {
+            if let PromptChildNode::Msg(msg) = item {
+                errors.extend(check_input_bindings(&msg.children, &inputs));
+            }
+        }
         if !errors.is_empty() {
             return Err(errors)
         }
         Ok(Self {
             settings: prompt_attributes,
+            inputs,
             children: items,
+            span,
         })
     }
 }
@@ -312,7 +704,7 @@ impl std::fmt::Display for InvalidPromptNode {
 }
 impl std::error::Error for InvalidPromptNode {}
 impl DslFormatError for InvalidPromptNode {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
 }
 
 #[derive(Debug, Clone)]
@@ -324,7 +716,10 @@ impl std::fmt::Display for InvalidPromptMissingName {
 }
 impl std::error::Error for InvalidPromptMissingName {}
 impl DslFormatError for InvalidPromptMissingName {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("add `name=\"...\"`, which is how the prompt is invoked"))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -336,7 +731,409 @@ impl std::fmt::Display for InvalidPromptAttribute {
 }
 impl std::error::Error for InvalidPromptAttribute {}
 impl DslFormatError for InvalidPromptAttribute {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidInputDeclarationAttribute {
+    pub attribute: String,
+    pub error: crate::common::input::InvalidInputDeclaration,
+}
+impl std::fmt::Display for InvalidInputDeclarationAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid prompt attribute `{}`: {}", self.attribute, self.error)
+    }
+}
+impl std::error::Error for InvalidInputDeclarationAttribute {}
+impl DslFormatError for InvalidInputDeclarationAttribute {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INPUT BINDINGS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Checks that every `from="..."` refers to a declared input and has a valid `format`.
+fn check_input_bindings(fragment: &html_ast::Fragment, inputs: &[InputDeclaration]) -> DslFormatErrorList {
+    let mut errors = DslFormatErrorList::with_capacity(0);
+    for element in fragment.clone().extract_elements() {
+        if let Some(name) = element.attributes.get("from") {
+            let name = name.as_str();
+            if !inputs.iter().any(|x| x.name == name) {
+                errors.extend(UndeclaredInput { name: name.to_string() }.at(&SourceLocation::attribute(&element, "from")));
+            }
+            let format = element.attributes.get("format").map(|x| InputFormat::from_str(x.as_str()));
+            if let Some(Err(error)) = format {
+                errors.extend(InvalidInputBinding(error).at(&SourceLocation::attribute(&element, "format")));
+            }
+        }
+        errors.extend(check_input_bindings(&element.children, inputs));
+    }
+    errors
+}
+
+#[derive(Debug, Clone)]
+pub struct UndeclaredInput {
+    pub name: String,
+}
+impl std::fmt::Display for UndeclaredInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = &self.name;
+        write!(f, "`from=\"{name}\"` refers to an undeclared input, add `input:{name}=\"of type ...\"` to the `<prompt>`")
+    }
+}
+impl std::error::Error for UndeclaredInput {}
+impl DslFormatError for UndeclaredInput {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidInputBinding(pub crate::common::input::InvalidInputFormat);
+impl std::fmt::Display for InvalidInputBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid input binding: {}", self.0)
+    }
+}
+impl std::error::Error for InvalidInputBinding {}
+impl DslFormatError for InvalidInputBinding {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SCHEMA NODE
+// ————————————————————————————————————————————————————————————————————————————
+
+impl SchemaNode {
+    pub fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
+        if Self::matches(&element.tag).not() {
+            return Err(DslFormatErrorList::new(Arc::new(InvalidSchemaNode)))
+        }
+        let location = SourceLocation::element(&element);
+        let span = element.span;
+        let id = element.attributes
+            .get("id")
+            .ok_or_else(|| InvalidSchemaMissingId.at(&location))?
+            .as_str()
+            .to_string();
+        if let Some(src) = element.attributes.get("src") {
+            return Ok(Self { id, source: SchemaSource::External(src.as_str().to_string()), span })
+        }
+        let text = element.children
+            .extract_text_strict()
+            .map_err(|_| InvalidSchema { id: id.clone(), message: String::from("expected JSON text or a `src` attribute") }.at(&location))?
+            .concat();
+        let value = serde_json::from_str::<serde_json::Value>(&text)
+            .map_err(|error| InvalidSchema { id: id.clone(), message: error.to_string() }.at(&location))?;
+        Ok(Self { id, source: SchemaSource::Inline(value), span })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidSchemaNode;
+impl std::fmt::Display for InvalidSchemaNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid schema element")
+    }
+}
+impl std::error::Error for InvalidSchemaNode {}
+impl DslFormatError for InvalidSchemaNode {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidSchemaMissingId;
+impl std::fmt::Display for InvalidSchemaMissingId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid schema: missing id attribute")
+    }
+}
+impl std::error::Error for InvalidSchemaMissingId {}
+impl DslFormatError for InvalidSchemaMissingId {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidSchema {
+    pub id: String,
+    pub message: String,
+}
+impl std::fmt::Display for InvalidSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid schema '{}': {}", self.id, self.message)
+    }
+}
+impl std::error::Error for InvalidSchema {}
+impl DslFormatError for InvalidSchema {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnsupportedSchemaKeywords {
+    pub id: String,
+    pub keywords: Vec<UnsupportedKeyword>,
+}
+impl std::fmt::Display for UnsupportedSchemaKeywords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keywords = self.keywords.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        write!(f, "schema '{}' uses keywords output can’t be verified against: {keywords}", self.id)
+    }
+}
+impl std::error::Error for UnsupportedSchemaKeywords {}
+impl DslFormatError for UnsupportedSchemaKeywords {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("remove them, or describe the constraint in a `<msg>` instead; output is only checked against the keywords listed in the README"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateSchema {
+    pub id: String,
+}
+impl std::fmt::Display for DuplicateSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "more than one schema with id '{}'", self.id)
+    }
+}
+impl std::error::Error for DuplicateSchema {}
+impl DslFormatError for DuplicateSchema {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnknownSchema {
+    pub id: String,
+    pub prompt: String,
+}
+impl std::fmt::Display for UnknownSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "a breakpoint of prompt '{}' refers to schema '{}', which is neither a `<schema id=\"...\">` nor an input of type Schema",
+            self.prompt,
+            self.id,
+        )
+    }
+}
+impl std::error::Error for UnknownSchema {}
+impl DslFormatError for UnknownSchema {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidRepairPrompt {
+    pub name: String,
+    pub prompt: String,
+}
+impl std::fmt::Display for InvalidRepairPrompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name == self.prompt {
+            write!(f, "a breakpoint of prompt '{}' can’t be repaired by the prompt itself", self.prompt)
+        } else {
+            write!(f, "a breakpoint of prompt '{}' is repaired by '{}', which isn’t a prompt of the document", self.prompt, self.name)
+        }
+    }
+}
+impl std::error::Error for InvalidRepairPrompt {}
+impl DslFormatError for InvalidRepairPrompt {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidJudgePrompt {
+    pub name: String,
+    pub prompt: String,
+}
+impl std::fmt::Display for InvalidJudgePrompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name == self.prompt {
+            write!(f, "a breakpoint of prompt '{}' can’t be judged by the prompt itself", self.prompt)
+        } else {
+            write!(f, "a breakpoint of prompt '{}' is judged by '{}', which isn’t a prompt of the document", self.prompt, self.name)
+        }
+    }
+}
+impl std::error::Error for InvalidJudgePrompt {}
+impl DslFormatError for InvalidJudgePrompt {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct JudgeWithoutChoices {
+    pub name: String,
+    pub prompt: String,
+}
+impl std::fmt::Display for JudgeWithoutChoices {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a breakpoint of prompt '{}' is judged by '{}', which doesn’t declare the input `choices`", self.prompt, self.name)
+    }
+}
+impl std::error::Error for JudgeWithoutChoices {}
+impl DslFormatError for JudgeWithoutChoices {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(format!("the numbered choices are passed as `choices`, add `input:choices=\"of type String\"` to '{}'", self.name))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepairWithoutInputs {
+    pub name: String,
+    pub prompt: String,
+    /// Of `output` and `errors`, those the repair prompt doesn’t declare.
+    pub missing: Vec<&'static str>,
+}
+impl std::fmt::Display for RepairWithoutInputs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let missing = self.missing.iter().map(|x| format!("`{x}`")).collect::<Vec<_>>().join(" and ");
+        let inputs = if self.missing.len() == 1 { "input" } else { "inputs" };
+        write!(f, "a breakpoint of prompt '{}' is repaired by '{}', which doesn’t declare the {inputs} {missing}", self.prompt, self.name)
+    }
+}
+impl std::error::Error for RepairWithoutInputs {}
+impl DslFormatError for RepairWithoutInputs {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        let attributes = self.missing.iter().map(|x| format!("input:{x}=\"of type String\"")).collect::<Vec<_>>().join(" ");
+        Some(format!("the failed output and its errors are passed as `output` and `errors`, add `{attributes}` to '{}'", self.name))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptCycle {
+    /// The prompts in the order they invoke each other, the first one again last.
+    pub cycle: Vec<String>,
+}
+impl std::fmt::Display for PromptCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cycle = self.cycle.iter().map(|x| format!("'{x}'")).collect::<Vec<_>>().join(" → ");
+        write!(f, "repair and judge prompts invoke each other in a cycle: {cycle}")
+    }
+}
+impl std::error::Error for PromptCycle {}
+impl DslFormatError for PromptCycle {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("a prompt used for `repair` or `judge` can’t itself (even indirectly) be repaired or judged by the prompt using it"))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TOOL NODE
+// ————————————————————————————————————————————————————————————————————————————
+
+impl ToolNode {
+    pub fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
+        if Self::matches(&element.tag).not() {
+            return Err(DslFormatErrorList::new(Arc::new(InvalidToolNode)))
+        }
+        let location = SourceLocation::element(&element);
+        let span = element.span;
+        let name = element.attributes
+            .get("name")
+            .ok_or_else(|| InvalidToolMissingName.at(&location))?
+            .as_str()
+            .to_string();
+        let invalid = |message: &str| InvalidTool { name: name.clone(), message: message.to_string() }.at(&location);
+        let description = element.attributes.get("description").map(|x| x.as_str().to_string());
+        let invalid_command = |message: String| {
+            InvalidTool { name: name.clone(), message }.at(&SourceLocation::attribute(&element, "command"))
+        };
+        // Split like a shell would, e.g. `command="python3 'my tools/weather.py'"`.
+        let command = element.attributes
+            .get("command")
+            .map(|x| shell_words::split(x.as_str()))
+            .transpose()
+            .map_err(|error| invalid_command(format!("`command` isn’t valid shell syntax: {error}")))?;
+        if command.as_ref().is_some_and(Vec::is_empty) {
+            return Err(invalid_command(String::from("`command` is empty")))
+        }
+        let text = element.children
+            .extract_text_strict()
+            .map_err(|_| invalid("expected a JSON Schema of the parameters as text"))?
+            .concat();
+        let parameters = match element.attributes.get("parameters") {
+            Some(_) if !text.trim().is_empty() => {
+                return Err(invalid("give the parameters either inline or via `parameters`, not both"))
+            }
+            Some(id) => Some(ToolParameters::Schema(id.as_str().to_string())),
+            None if text.trim().is_empty() => None,
+            None => {
+                let value = serde_json::from_str::<serde_json::Value>(&text)
+                    .map_err(|error| invalid(&error.to_string()))?;
+                Some(ToolParameters::Inline(value))
+            }
+        };
+        Ok(Self { name, description, parameters, command, span })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidToolNode;
+impl std::fmt::Display for InvalidToolNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid tool element")
+    }
+}
+impl std::error::Error for InvalidToolNode {}
+impl DslFormatError for InvalidToolNode {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidToolMissingName;
+impl std::fmt::Display for InvalidToolMissingName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid tool: missing name attribute")
+    }
+}
+impl std::error::Error for InvalidToolMissingName {}
+impl DslFormatError for InvalidToolMissingName {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidTool {
+    pub name: String,
+    pub message: String,
+}
+impl std::fmt::Display for InvalidTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid tool '{}': {}", self.name, self.message)
+    }
+}
+impl std::error::Error for InvalidTool {}
+impl DslFormatError for InvalidTool {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateTool {
+    pub name: String,
+}
+impl std::fmt::Display for DuplicateTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "more than one tool named '{}'", self.name)
+    }
+}
+impl std::error::Error for DuplicateTool {}
+impl DslFormatError for DuplicateTool {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnknownTool {
+    pub name: String,
+    pub prompt: String,
+}
+impl std::fmt::Display for UnknownTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a breakpoint of prompt '{}' offers tool '{}', which isn’t a `<tool>` of the document", self.prompt, self.name)
+    }
+}
+impl std::error::Error for UnknownTool {}
+impl DslFormatError for UnknownTool {
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
 }
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -344,26 +1141,39 @@ impl DslFormatError for InvalidPromptAttribute {
 // ————————————————————————————————————————————————————————————————————————————
 
 impl DocumentChildCode {
+    /// Errors are located at the element, unless they are more specific.
     fn from_element(element: html_ast::Element) -> Result<Self, DslFormatErrorList> {
+        let location = SourceLocation::element(&element);
         if PromptNode::matches(&element.tag) {
-            return PromptNode::from_element(element).map(DocumentChildCode::Prompt)
+            return PromptNode::from_element(element).map(DocumentChildCode::Prompt).map_err(|x| x.located(&location))
+        }
+        if SchemaNode::matches(&element.tag) {
+            return SchemaNode::from_element(element).map(DocumentChildCode::Schema).map_err(|x| x.located(&location))
+        }
+        if ToolNode::matches(&element.tag) {
+            return ToolNode::from_element(element).map(DocumentChildCode::Tool).map_err(|x| x.located(&location))
         }
-        eprintln!("Failed: {element:?}");
-        Err(DslFormatErrorList::new(Rc::new(InvalidDocumentChildNode)))
+        let invalid = InvalidDocumentChildNode { tag: element.tag.as_original().to_string() };
+        Err(invalid.at(&SourceLocation::element(&element)))
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct InvalidDocumentChildNode;
+pub struct InvalidDocumentChildNode {
+    pub tag: String,
+}
 impl std::fmt::Display for InvalidDocumentChildNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "invalid document child node")
+        write!(f, "invalid document child node `<{}>`", self.tag)
     }
 }
 
 impl std::error::Error for InvalidDocumentChildNode {}
 impl DslFormatError for InvalidDocumentChildNode {
-    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Rc::new(self.clone())) }
+    fn singleton(&self) -> DslFormatErrorList { DslFormatErrorList::new(Arc::new(self.clone())) }
+    fn hint(&self) -> Option<String> {
+        Some(String::from("a document contains `<prompt>`, `<schema>` and `<tool>` elements"))
+    }
 }
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -384,13 +1194,228 @@ impl DocumentNode {
                 }
             }
         }
+        let document = Self { children: items };
+        errors.extend(document.check_references());
         if !errors.is_empty() {
             return Err(errors)
         }
-        Ok(Self { children: items })
+        Ok(document)
     }
     pub fn from_node(node: html_ast::Node) -> Result<Self, DslFormatErrorList> {
         Self::from_fragment(html_ast::Fragment::from_nodes(node.flatten()))
     }
+    /// Parses a document from source, schemas given via `src` aren’t resolved.
+    pub fn parse(source: impl AsRef<str>) -> Result<Self, Error> {
+        let html_tree = html_ast::parser::parse_from_fragment(source)
+            .into_result()
+            .map_err(|errors| Error::Html { path: None, errors })?;
+        Self::from_node(html_tree).map_err(|errors| Error::Dsl { path: None, errors })
+    }
+    /// Reads and parses the document at `path`, including the schemas it refers to via `src`.
+    ///
+    /// Tool commands given as relative paths, e.g. `./tools/weather.py`, are made relative to the document.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| Error::Io { path: path.to_path_buf(), error })?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(source)
+            .and_then(|mut document| document.resolve_schemas(base_dir).map(|_| document))
+            .map(|mut document| {
+                document.resolve_commands(base_dir);
+                document
+            })
+            .map_err(|error| match error {
+                Error::Html { path: None, errors } => Error::Html { path: Some(path.to_path_buf()), errors },
+                Error::Dsl { path: None, errors } => Error::Dsl { path: Some(path.to_path_buf()), errors },
+                error => error,
+            })
+    }
+    /// Reads the schemas given via `src`, relative to `base_dir`.
+    pub fn resolve_schemas(&mut self, base_dir: impl AsRef<Path>) -> Result<(), Error> {
+        let mut errors = DslFormatErrorList::with_capacity(0);
+        let mut resolved = false;
+        for child in self.children.iter_mut() {
+            let DocumentChildCode::Schema(schema) = child else { continue };
+            let SchemaSource::External(src) = &schema.source else { continue };
+            let path = base_dir.as_ref().join(src);
+            let source = std::fs::read_to_string(&path)
+                .map_err(|error| Error::Io { path: path.clone(), error })?;
+            match serde_json::from_str(&source) {
+                Ok(value) => {
+                    schema.source = SchemaSource::Inline(value);
+                    resolved = true;
+                }
+                Err(error) => {
+                    let message = format!("`{}`: {error}", path.display());
+                    let location = SourceLocation::node(schema.span, "schema", Some("src"));
+                    errors.extend(InvalidSchema { id: schema.id.clone(), message }.at(&location));
+                }
+            }
+        }
+        // Inline schemas were checked when the document was parsed.
+        if resolved {
+            errors.extend(self.check_schema_keywords());
+        }
+        if !errors.is_empty() {
+            return Err(Error::Dsl { path: None, errors })
+        }
+        Ok(())
+    }
+    /// Makes tool commands with a relative path relative to `base_dir`, commands found via `PATH` are kept.
+    pub fn resolve_commands(&mut self, base_dir: impl AsRef<Path>) {
+        for child in self.children.iter_mut() {
+            let DocumentChildCode::Tool(tool) = child else { continue };
+            let Some(program) = tool.command.as_mut().and_then(|x| x.first_mut()) else { continue };
+            if Path::new(program.as_str()).is_relative() && program.contains('/') {
+                *program = base_dir.as_ref().join(program.as_str()).to_string_lossy().to_string();
+            }
+        }
+    }
+    /// Checks that schema ids and tool names are unique and that breakpoints only refer to existing schemas, tools and prompts,
+    /// where repair and judge prompts declare the inputs they’re given.
+    fn check_references(&self) -> DslFormatErrorList {
+        let mut errors = DslFormatErrorList::with_capacity(0);
+        let mut ids = BTreeSet::<&str>::new();
+        for schema in self.schemas() {
+            if !ids.insert(schema.id.as_str()) {
+                let location = SourceLocation::node(schema.span, "schema", Some("id"));
+                errors.extend(DuplicateSchema { id: schema.id.clone() }.at(&location));
+            }
+        }
+        let mut tools = BTreeSet::<&str>::new();
+        for tool in self.tools() {
+            if !tools.insert(tool.name.as_str()) {
+                let location = SourceLocation::node(tool.span, "tool", Some("name"));
+                errors.extend(DuplicateTool { name: tool.name.clone() }.at(&location));
+            }
+            if let Some(ToolParameters::Schema(id)) = tool.parameters.as_ref()
+                && !ids.contains(id.as_str()) {
+                let message = format!("`parameters` refers to schema '{id}', which isn’t a `<schema id=\"...\">` of the document");
+                let location = SourceLocation::node(tool.span, "tool", Some("parameters"));
+                errors.extend(InvalidTool { name: tool.name.clone(), message }.at(&location));
+            }
+        }
+        for prompt in self.prompts() {
+            let breakpoints = prompt.children.iter().filter_map(|x| match x {
+                PromptChildNode::Breakpoint(breakpoint) => Some(breakpoint),
+                _ => None,
+            });
+            for breakpoint in breakpoints {
+                let at = |key: &str| SourceLocation::node(breakpoint.span, "breakpoint", Some(key));
+                if let Some(SchemaReference::Document(id)) = breakpoint.schema.as_ref()
+                    && !ids.contains(id.as_str()) {
+                    errors.extend(UnknownSchema { id: id.clone(), prompt: prompt.name().to_string() }.at(&at("schema")));
+                }
+                if let Some(name) = breakpoint.repair.as_ref() {
+                    let repair = self.prompts().find(|x| x.name() == name).filter(|_| name != prompt.name());
+                    match repair {
+                        None => {
+                            errors.extend(InvalidRepairPrompt { name: name.clone(), prompt: prompt.name().to_string() }.at(&at("repair")));
+                        }
+                        // Undeclared inputs aren’t bound, the repair prompt wouldn’t see what to repair.
+                        Some(repair) => {
+                            let missing = ["output", "errors"]
+                                .into_iter()
+                                .filter(|input| repair.inputs.iter().all(|x| x.name != *input))
+                                .collect::<Vec<_>>();
+                            if !missing.is_empty() {
+                                let error = RepairWithoutInputs { name: name.clone(), prompt: prompt.name().to_string(), missing };
+                                errors.extend(error.at(&at("repair")));
+                            }
+                        }
+                    }
+                }
+                if let SelectionPolicy::Judge(name) = &breakpoint.select {
+                    let judge = self.prompts().find(|x| x.name() == name).filter(|_| name != prompt.name());
+                    match judge {
+                        None => {
+                            errors.extend(InvalidJudgePrompt { name: name.clone(), prompt: prompt.name().to_string() }.at(&at("judge")));
+                        }
+                        // Undeclared inputs aren’t bound, the judge wouldn’t see the choices.
+                        Some(judge) if judge.inputs.iter().all(|x| x.name != "choices") => {
+                            errors.extend(JudgeWithoutChoices { name: name.clone(), prompt: prompt.name().to_string() }.at(&at("judge")));
+                        }
+                        Some(_) => (),
+                    }
+                }
+                for name in breakpoint.tools.iter().filter(|x| !tools.contains(x.as_str())) {
+                    errors.extend(UnknownTool { name: name.clone(), prompt: prompt.name().to_string() }.at(&at("tools")));
+                }
+            }
+        }
+        let mut done = BTreeSet::<&str>::new();
+        for prompt in self.prompts() {
+            if !done.contains(prompt.name()) {
+                self.check_cycles(prompt, &mut Vec::new(), &mut done, &mut errors);
+            }
+        }
+        errors.extend(self.check_schema_keywords());
+        errors
+    }
+    /// Reports loaded schemas that breakpoints verify output against but that use
+    /// keywords the validator can’t check; schemas only used for tool parameters are
+    /// sent to the model as they are.
+    fn check_schema_keywords(&self) -> DslFormatErrorList {
+        let verified = self.prompts()
+            .flat_map(|x| x.children.iter())
+            .filter_map(|x| match x {
+                PromptChildNode::Breakpoint(BreakpointNode { schema: Some(SchemaReference::Document(id)), .. }) => Some(id.as_str()),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+        let mut errors = DslFormatErrorList::with_capacity(0);
+        for schema in self.schemas().filter(|x| verified.contains(x.id.as_str())) {
+            let SchemaSource::Inline(value) = &schema.source else { continue };
+            let keywords = unsupported_keywords(value);
+            if !keywords.is_empty() {
+                let location = SourceLocation::node(schema.span, "schema", None);
+                errors.extend(UnsupportedSchemaKeywords { id: schema.id.clone(), keywords }.at(&location));
+            }
+        }
+        errors
+    }
+    /// Reports `repair` and `judge` prompts that end up invoking the prompt
+    /// again, which would recurse without end; `path` are the prompts invoking
+    /// this one and `done` those already checked.
+    fn check_cycles<'a>(
+        &'a self,
+        prompt: &'a PromptNode,
+        path: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+        errors: &mut DslFormatErrorList,
+    ) {
+        path.push(prompt.name());
+        for (name, breakpoint, attribute) in invoked_prompts(prompt) {
+            if let Some(position) = path.iter().position(|x| *x == name) {
+                let cycle = path[position..].iter().copied().chain([name]).map(ToString::to_string).collect();
+                let location = SourceLocation::node(breakpoint.span, "breakpoint", Some(attribute));
+                errors.extend(PromptCycle { cycle }.at(&location));
+            } else if !done.contains(name)
+                && let Some(next) = self.prompts().find(|x| x.name() == name) {
+                self.check_cycles(next, path, done, errors);
+            }
+        }
+        path.pop();
+        done.insert(prompt.name());
+    }
+}
+
+/// The prompts a prompt’s breakpoints invoke, along with the attribute naming them.
+///
+/// Prompts referring to themselves are left out, they are reported on their own.
+fn invoked_prompts(prompt: &PromptNode) -> Vec<(&str, &BreakpointNode, &'static str)> {
+    let mut invoked = Vec::new();
+    for child in prompt.children.iter() {
+        let PromptChildNode::Breakpoint(breakpoint) = child else { continue };
+        if let Some(name) = breakpoint.repair.as_deref() {
+            invoked.push((name, breakpoint, "repair"));
+        }
+        if let SelectionPolicy::Judge(name) = &breakpoint.select {
+            invoked.push((name.as_str(), breakpoint, "judge"));
+        }
+    }
+    invoked.retain(|(name, _, _)| *name != prompt.name());
+    invoked
 }
 
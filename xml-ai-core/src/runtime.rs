@@ -2,20 +2,96 @@
 // DATA MODEL — SETTINGS
 // ————————————————————————————————————————————————————————————————————————————
 
+use std::collections::BTreeMap;
 use std::ops::Not;
+use std::sync::Arc;
 
-use crate::ast::{document::{DocumentChildCode, DocumentNode}, prompt::{PromptChildNode, PromptNode}};
-use crate::common::{message::MessageRole, prompt::{PromptSettings, ResponseFormatType}};
+use ai_client::client::HttpClient;
+use ai_client::cassette::Cassette;
+use ai_client::limit::ConcurrencyLimiter;
+use ai_client::log::Logger;
+use ai_client::provider::{ChatProvider, ProviderRegistry};
+use ai_client::ratelimit::RateLimiter;
+use ai_client::retry::RetryPolicy;
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::cache::{CacheEntry, ResponseCache};
+use crate::mock::MockProvider;
+use crate::ast::{breakpoint::{BreakpointNode, SchemaReference, SelectionPolicy, Verification}, document::DocumentNode, prompt::{PromptChildNode, PromptNode}, schema::SchemaSource};
+use crate::common::{input::{InputType, InputValues, json_type_name}, message::MessageRole, prompt::{PromptSettings, ResponseFormatType, TextFormatType}};
+use crate::common::schema::{RepairAttempt, SchemaViolation, UnsupportedKeyword, VerificationReport, unsupported_keywords};
+use crate::common::selection::{self, Selection};
+use crate::error::Error;
+use crate::ast::tool::ToolNode;
+use crate::snapshot::{ConversationSnapshot, MessageSnapshot};
+use crate::tool::ToolRegistry;
+use crate::usage::{CallUsage, PriceTable};
 
 #[derive(Debug, Clone)]
 pub struct RuntimeEnvironment {
-    pub api_key: String,
+    /// Sent only to providers that accept it, e.g. `openai`, see `ChatProvider::accepts_shared_api_key`.
+    pub api_key: Option<String>,
+    /// Used when neither the `<prompt>` nor any preceding `<set>` specifies a `model`.
+    pub default_model: Option<String>,
+    pub providers: ProviderRegistry,
+    /// Used when the document doesn’t specify a provider.
+    pub default_provider: Option<String>,
+    /// Sent with every request, in addition to the provider’s own headers.
+    pub headers: Vec<(String, String)>,
+    /// Applied to every breakpoint, including streams that fail midway.
+    pub retry_policy: RetryPolicy,
+    /// Used to estimate the cost of each call.
+    pub prices: PriceTable,
+    /// Echo the generated text to stderr as it streams in, along with tool calls and their failures.
+    pub log_output: bool,
+    /// Shared by all invocations using this environment, including clones.
+    pub limiter: ConcurrencyLimiter,
+    /// Paces requests per provider and model, shared like [`RuntimeEnvironment::limiter`].
+    pub rate_limiter: RateLimiter,
+    /// Completions are reused from and stored in this cache, if any.
+    pub cache: Option<ResponseCache>,
+    /// Answers every breakpoint instead of the providers, e.g. in tests.
+    pub mock: Option<MockProvider>,
+    /// Records the HTTP exchanges, or replays them instead of sending requests.
+    pub cassette: Option<Cassette>,
+    /// Callbacks for `<tool>`s, taking precedence over their `command`.
+    pub tools: ToolRegistry,
+    /// Sends every request, so connections are reused across breakpoints and rows.
+    pub http_client: HttpClient,
+}
+
+/// The built-in providers without limits, caching or output.
+impl Default for RuntimeEnvironment {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            default_model: None,
+            providers: ProviderRegistry::with_builtin(),
+            default_provider: None,
+            headers: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            prices: PriceTable::default(),
+            log_output: false,
+            limiter: ConcurrencyLimiter::default(),
+            rate_limiter: RateLimiter::default(),
+            cache: None,
+            mock: None,
+            cassette: None,
+            tools: ToolRegistry::default(),
+            http_client: HttpClient::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DocumentInvocation {
     pub runtime_environment: RuntimeEnvironment,
     pub target_prompt: String,
+    /// Values for the inputs declared by the prompt.
+    pub inputs: InputValues,
+    /// A snapshot of an earlier, possibly interrupted, run of the same prompt.
+    pub resume_from: Option<ConversationSnapshot>,
 }
 
 // ————————————————————————————————————————————————————————————————————————————
@@ -26,12 +102,27 @@ pub struct DocumentInvocation {
 pub struct ConversationMessage {
     pub message: ai_client::request::Message,
     pub evaluated: bool,
+    /// Set for breakpoints with `verification="..."`.
+    pub verification: Option<VerificationReport>,
+    /// Earlier outputs of the breakpoint that failed verification, oldest first.
+    pub attempts: Vec<RepairAttempt>,
+    /// Every choice of the breakpoint, if the model generated several.
+    pub selection: Option<Selection>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Conversation {
     pub messages: Vec<ConversationMessage>,
     pub prompt_settings: PromptSettings,
+    /// One entry per LLM call, in order.
+    pub calls: Vec<CallUsage>,
+}
+
+impl ConversationMessage {
+    /// A message from the model (or a tool), without verification.
+    pub fn evaluated(message: ai_client::request::Message) -> Self {
+        Self { message, evaluated: true, verification: None, attempts: Vec::new(), selection: None }
+    }
 }
 
 impl Conversation {
@@ -45,6 +136,12 @@ impl Conversation {
 pub struct PromptContext {
     pub runtime_environment: RuntimeEnvironment,
     pub conversation: Conversation,
+    /// The document’s `<schema>`s by id.
+    pub schemas: BTreeMap<String, Value>,
+    /// The document’s prompts by name, for `<breakpoint repair="...">`.
+    pub prompts: BTreeMap<String, PromptNode>,
+    /// The document’s tools by name, for `<breakpoint tools="...">`.
+    pub tools: BTreeMap<String, ToolNode>,
 }
 
 impl PromptContext {
@@ -52,14 +149,215 @@ impl PromptContext {
         Self {
             runtime_environment,
             conversation: Default::default(),
+            schemas: Default::default(),
+            prompts: Default::default(),
+            tools: Default::default(),
+        }
+    }
+    /// Resolves the provider explicitly requested via `provider="..."`, if any.
+    fn explicit_provider(&self) -> Result<Option<Arc<dyn ChatProvider>>, InvocationError> {
+        let Some(provider) = self.conversation.prompt_settings.provider.as_ref() else {
+            return Ok(None)
+        };
+        self.runtime_environment.providers
+            .get(&provider.0)
+            .map(Some)
+            .ok_or_else(|| InvocationError::UnknownProvider { name: provider.0.clone() })
+    }
+    /// Resolves the model for the next request.
+    ///
+    /// In order of precedence:
+    /// 1. The most recent `<set model="...">`.
+    /// 2. The `<prompt model="...">` attribute.
+    /// 3. The runtime environment default (i.e. `--model` or `XML_AI_MODEL`).
+    /// 4. The default model of an explicitly given provider.
+    pub fn resolve_model(&self) -> Result<String, InvocationError> {
+        let explicit_provider = self.explicit_provider()?;
+        self.conversation.prompt_settings.model
+            .as_ref()
+            .map(|x| x.0.clone())
+            .or_else(|| self.runtime_environment.default_model.clone())
+            .or_else(|| explicit_provider?.default_model().map(ToString::to_string))
+            .ok_or(InvocationError::MissingModel)
+    }
+    /// Resolves the provider for the next request.
+    ///
+    /// In order of precedence:
+    /// 1. The most recent `<set provider="...">` or the `<prompt provider="...">` attribute.
+    /// 2. The runtime environment default (i.e. `--provider`, `--base-url` or their environment variables).
+    /// 3. The first provider whose model catalog lists the given model.
+    /// 4. `openai`.
+    pub fn resolve_provider(&self, model: &str) -> Result<Arc<dyn ChatProvider>, InvocationError> {
+        if let Some(provider) = self.explicit_provider()? {
+            return Ok(provider)
+        }
+        let providers = &self.runtime_environment.providers;
+        if let Some(name) = self.runtime_environment.default_provider.as_ref() {
+            return providers
+                .get(name)
+                .ok_or_else(|| InvocationError::UnknownProvider { name: name.clone() })
+        }
+        if let Some(provider) = providers.find_by_model(model) {
+            return Ok(provider)
         }
+        providers
+            .get(ProviderRegistry::DEFAULT_PROVIDER)
+            .ok_or_else(|| InvocationError::UnknownProvider { name: ProviderRegistry::DEFAULT_PROVIDER.to_string() })
     }
-    pub async fn invoke(&mut self) -> String {
+    pub async fn invoke(&mut self) -> Result<String, Error> {
         let messages = self.conversation.messages
             .iter()
             .map(|x| x.message.clone())
             .collect::<Vec<_>>();
-        invoke(&messages, &self.runtime_environment, &self.conversation.prompt_settings).await
+        self.invoke_with(&messages).await
+    }
+    /// Like [`PromptContext::invoke`] but with the given messages instead of the conversation.
+    pub async fn invoke_with(&mut self, messages: &[ai_client::request::Message]) -> Result<String, Error> {
+        let reply = self.complete(messages, &[], None).await?;
+        Ok(reply.content().to_string())
+    }
+    /// The assistant’s reply to `messages`, which may call some of the given `tools` instead of answering.
+    pub async fn complete(
+        &mut self,
+        messages: &[ai_client::request::Message],
+        tools: &[ai_client::request::Tool],
+        tool_choice: Option<ai_client::request::ToolChoice>,
+    ) -> Result<ai_client::request::Message, Error> {
+        let mut choices = self.complete_choices(messages, tools, tool_choice).await?;
+        Ok(choices.swap_remove(0))
+    }
+    /// Like [`PromptContext::complete`], but with every choice the model generated (see `n`), at least one.
+    pub async fn complete_choices(
+        &mut self,
+        messages: &[ai_client::request::Message],
+        tools: &[ai_client::request::Tool],
+        tool_choice: Option<ai_client::request::ToolChoice>,
+    ) -> Result<Vec<ai_client::request::Message>, Error> {
+        if let Some(mock) = self.runtime_environment.mock.as_ref() {
+            let n = self.conversation.prompt_settings.n.as_ref().map_or(1, |x| x.0.max(1) as usize);
+            let replies = mock.reply_choices(messages, n)?;
+            if self.runtime_environment.log_output {
+                eprintln!("{}", replies[0].content());
+                log_tool_calls(replies[0].tool_calls());
+            }
+            self.conversation.calls.push(CallUsage {
+                model: self.resolve_model().unwrap_or_else(|_| MockProvider::NAME.to_string()),
+                provider: MockProvider::NAME.to_string(),
+                usage: None,
+                latency_ms: 0,
+                cost: None,
+                cached: false,
+            });
+            return Ok(replies)
+        }
+        let (model, provider, request_builder) = self.prepare_request(messages, tools, tool_choice)?;
+        let provider_name = provider.name().to_string();
+        // The cache is keyed by the full request, an incomplete one fails below.
+        let cache = self.runtime_environment.cache.as_ref();
+        let request = cache.and_then(|_| request_builder.clone().build());
+        if let Some((cache, request)) = cache.zip(request.as_ref())
+            && let Some(entry) = cache.get(&provider_name, request) {
+            if self.runtime_environment.log_output {
+                eprintln!("{}", entry.content);
+                log_tool_calls(&entry.tool_calls);
+            }
+            let choices = entry.choices();
+            self.conversation.calls.push(CallUsage {
+                model,
+                provider: provider_name,
+                usage: entry.usage,
+                latency_ms: 0,
+                cost: None,
+                cached: true,
+            });
+            return Ok(choices)
+        }
+        let completion = invoke(
+            request_builder,
+            provider,
+            &self.runtime_environment,
+        ).await?;
+        let latency_ms = completion.latency.as_millis() as u64;
+        if let Some((cache, request)) = cache.zip(request.as_ref()) {
+            cache.put(request, &CacheEntry::new(
+                provider_name.clone(),
+                serde_json::to_value(request).unwrap_or_default(),
+                &completion.choices,
+                completion.usage,
+                latency_ms,
+            ));
+        }
+        let cost = self.runtime_environment.prices.cost(&model, completion.usage.as_ref());
+        self.conversation.calls.push(CallUsage {
+            model,
+            provider: provider_name,
+            usage: completion.usage,
+            latency_ms,
+            cost,
+            cached: false,
+        });
+        Ok(completion.choices)
+    }
+    /// The model, provider and request for `messages` under the current settings.
+    pub(crate) fn prepare_request(
+        &self,
+        messages: &[ai_client::request::Message],
+        tools: &[ai_client::request::Tool],
+        tool_choice: Option<ai_client::request::ToolChoice>,
+    ) -> Result<(String, Arc<dyn ChatProvider>, ai_client::request::RequestBuilder), InvocationError> {
+        let model = self.resolve_model()?;
+        let provider = self.resolve_provider(&model)?;
+        let mut request_builder = self.conversation.prompt_settings.request_builder()
+            .with_messages(messages.to_owned())
+            .with_model(&model)
+            .with_stream(true);
+        if !tools.is_empty() {
+            request_builder = request_builder.with_tools(tools.to_vec());
+        }
+        if let Some(tool_choice) = tool_choice {
+            request_builder = request_builder.with_tool_choice(tool_choice);
+        }
+        Ok((model, provider, request_builder))
+    }
+    /// Evaluates another prompt of the document, e.g. a `repair` or `judge` prompt,
+    /// returning its last message; its calls count towards this conversation’s usage.
+    async fn invoke_prompt(&mut self, name: &str, inputs: &InputValues) -> Result<String, Error> {
+        let prompt = self.prompts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| InvocationError::PromptNotFound { name: name.to_string(), available: self.prompts.keys().cloned().collect() })?;
+        let mut prompt_context = PromptContext {
+            runtime_environment: self.runtime_environment.clone(),
+            conversation: Default::default(),
+            schemas: self.schemas.clone(),
+            prompts: self.prompts.clone(),
+            tools: self.tools.clone(),
+        };
+        let result = Box::pin(prompt.invoke_into(&mut prompt_context, inputs, None)).await;
+        self.conversation.calls.extend(prompt_context.conversation.calls);
+        result?;
+        let output = prompt_context.conversation.messages
+            .last()
+            .map(|x| x.message.content().to_string())
+            .unwrap_or_default();
+        Ok(output)
+    }
+    /// Runs a tool call of the model, returning the content of the `tool` message answering it.
+    async fn call_tool(&self, call: &ai_client::request::ToolCall) -> Result<String, Error> {
+        let name = &call.function.name;
+        let result = match self.tools.get(name) {
+            None => Err(format!("there’s no tool named '{name}'")),
+            Some(tool) => match call.function.parse_arguments() {
+                Ok(arguments) => tool.call(&self.runtime_environment.tools, arguments).await?,
+                Err(error) => Err(format!("the arguments aren’t valid JSON: {error}")),
+            },
+        };
+        // The model is told either way, see the `tool` message.
+        if let Err(error) = result.as_ref()
+            && self.runtime_environment.log_output {
+            eprintln!("tool '{name}' failed: {error}");
+        }
+        Ok(result.unwrap_or_else(|error| format!("error: {error}")))
     }
     pub fn to_snapshot(&self) -> crate::snapshot::ConversationSnapshot {
         let messages = self.conversation.messages
@@ -68,71 +366,269 @@ impl PromptContext {
                 crate::snapshot::MessageSnapshot {
                     message_payload: x.message.clone(),
                     evaluation_point: x.evaluated,
+                    verification: x.verification.clone(),
+                    attempts: x.attempts.clone(),
+                    selection: x.selection.clone(),
                 }
             })
             .collect::<Vec<_>>();
         crate::snapshot::ConversationSnapshot {
             messages,
+            usage: crate::usage::UsageSummary::from_calls(self.conversation.calls.clone()),
         }
     }
 }
 
 
+// ————————————————————————————————————————————————————————————————————————————
+// ERRORS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+pub enum InvocationError {
+    PromptNotFound { name: String, available: Vec<String> },
+    MissingModel,
+    UnknownProvider { name: String },
+    /// The snapshot being resumed doesn’t belong to the prompt.
+    SnapshotMismatch { position: usize, reason: String },
+    MissingInput { name: String, r#type: Option<InputType> },
+    InvalidInput { name: String, expected: InputType, given: &'static str },
+    /// A breakpoint refers to a `<schema>` that isn’t available, e.g. when invoking a prompt without its document.
+    UnknownSchema { id: String },
+    /// A `<schema src="...">` that wasn’t loaded, see [`DocumentNode::resolve_schemas`].
+    UnresolvedSchema { id: String, src: String },
+    /// A schema output is verified against uses keywords the validator can’t check.
+    UnsupportedSchema { schema: String, keywords: Vec<UnsupportedKeyword> },
+    /// A breakpoint’s output isn’t valid JSON or doesn’t conform to its schema.
+    VerificationFailed { schema: Option<String>, errors: Vec<SchemaViolation> },
+    /// A scripted [`MockProvider`] ran out of replies.
+    MockExhausted { replies: usize },
+    /// A replaying [`MockProvider`] has no reply recorded for the messages before `position`.
+    NoRecordedReply { position: usize },
+    /// A breakpoint offers a `<tool>` that isn’t available, e.g. when invoking a prompt without its document.
+    UnknownTool { name: String },
+    /// A `<tool>` without a `command` or a callback in [`RuntimeEnvironment::tools`].
+    UnboundTool { name: String },
+    /// A tool’s command couldn’t be run.
+    ToolFailed { name: String, message: String },
+    /// The model still called tools after `max-tool-rounds` rounds.
+    ToolRoundsExceeded { rounds: u32 },
+    /// A `judge` prompt’s reply doesn’t name one of the choices.
+    InvalidJudgement { prompt: String, reply: String, choices: usize },
+}
+
+impl std::fmt::Display for InvocationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PromptNotFound { name, available } if available.is_empty() => {
+                write!(f, "no prompt named '{name}', the document doesn’t define any prompts")
+            }
+            Self::PromptNotFound { name, available } => {
+                write!(f, "no prompt named '{name}', available prompts: {}", available.join(", "))
+            }
+            Self::MissingModel => {
+                write!(
+                    f,
+                    "no model specified: set a `model` attribute on the `<prompt>` or a `<set>` element, or provide a default via `--model` or `XML_AI_MODEL`"
+                )
+            }
+            Self::UnknownProvider { name } => {
+                write!(f, "unknown provider '{name}'")
+            }
+            Self::SnapshotMismatch { position, reason } => {
+                write!(f, "the snapshot doesn’t match the prompt at message {position}: {reason}")
+            }
+            Self::MissingInput { name, r#type: Some(r#type) } => {
+                write!(f, "missing input '{name}' of type {type}")
+            }
+            Self::MissingInput { name, r#type: None } => {
+                write!(f, "missing input '{name}'")
+            }
+            Self::InvalidInput { name, expected, given } => {
+                write!(f, "input '{name}' should be of type {expected}, given {given}")
+            }
+            Self::UnknownSchema { id } => {
+                write!(f, "unknown schema '{id}'")
+            }
+            Self::UnsupportedSchema { schema, keywords } => {
+                let keywords = keywords.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+                write!(f, "schema '{schema}' uses keywords output can’t be verified against: {keywords}")
+            }
+            Self::UnresolvedSchema { id, src } => {
+                write!(f, "schema '{id}' refers to `{src}`, which hasn’t been loaded")
+            }
+            Self::VerificationFailed { schema, errors } => {
+                let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ∙ ");
+                match schema {
+                    Some(schema) => write!(f, "the output doesn’t conform to schema '{schema}': {errors}"),
+                    None => write!(f, "the output isn’t valid JSON: {errors}"),
+                }
+            }
+            Self::MockExhausted { replies } => {
+                write!(f, "the mock ran out of replies after {replies}")
+            }
+            Self::NoRecordedReply { position } => {
+                write!(f, "the snapshot being replayed has no reply recorded after the first {position} message(s)")
+            }
+            Self::UnknownTool { name } => {
+                write!(f, "unknown tool '{name}'")
+            }
+            Self::UnboundTool { name } => {
+                write!(f, "tool '{name}' has neither a `command` nor a registered callback")
+            }
+            Self::ToolFailed { name, message } => {
+                write!(f, "failed to run tool '{name}': {message}")
+            }
+            Self::ToolRoundsExceeded { rounds } => {
+                write!(f, "the model kept calling tools after {rounds} round(s)")
+            }
+            Self::InvalidJudgement { prompt, reply, choices } => {
+                write!(f, "judge '{prompt}' should reply with a choice from 1 to {choices}, replied {reply:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvocationError {}
+
 // ————————————————————————————————————————————————————————————————————————————
 // REQUEST HANDLER
 // ————————————————————————————————————————————————————————————————————————————
 
+struct Completion {
+    /// Every choice as an assistant message, at least one.
+    choices: Vec<ai_client::request::Message>,
+    usage: Option<ai_client::response::batch::Usage>,
+    latency: std::time::Duration,
+}
+
+/// Sends the request, retrying retryable failures according to the runtime’s retry policy.
+///
+/// Retries happen here rather than in the client, so a stream that fails
+/// midway is retried as well and all attempts share one budget.
 async fn invoke(
-    messages: &[ai_client::request::Message],
+    request_builder: ai_client::request::RequestBuilder,
+    provider: Arc<dyn ChatProvider>,
     runtime_environment: &RuntimeEnvironment,
-    prompt_settings: &PromptSettings,
-) -> String {
-    use ai_client::client::URL;
-    use ai_client::request::OpenAiModels;
-    let request_builder = prompt_settings.request_builder()
-        .with_messages(messages.to_owned())
-        .with_model(OpenAiModels::gpt_4)
-        .with_stream(true);
-    let client_builder = ai_client::client::ClientBuilder::default()
-        .with_api_url(URL::OPEN_AI_CHAT_COMPLETIONS)
-        .with_api_key(&runtime_environment.api_key)
-        .with_request_body(request_builder)
-        .with_logger(ai_client::log::StdErrLogger::default().with_colorize(true));
-    let client = client_builder.build_streaming_api_call().unwrap();
-    let output_result = client.execute_async().await;
-    let output = output_result.unwrap();
-    output.content(0).unwrap()
+) -> Result<Completion, Error> {
+    let retry_policy = &runtime_environment.retry_policy;
+    let started = std::time::Instant::now();
+    let mut attempt = 1;
+    loop {
+        let permit = runtime_environment.limiter.acquire(provider.as_ref()).await;
+        let mut client_builder = ai_client::client::ClientBuilder::default()
+            .with_provider(provider.clone())
+            .with_headers(runtime_environment.headers.clone())
+            .with_http_client(runtime_environment.http_client.clone())
+            .with_rate_limiter(runtime_environment.rate_limiter.clone())
+            .with_request_body(request_builder.clone());
+        // Other providers use their own key, see `ChatProvider::api_key`.
+        if let Some(api_key) = runtime_environment.api_key.as_ref().filter(|_| provider.accepts_shared_api_key()) {
+            client_builder = client_builder.with_api_key(api_key);
+        }
+        if let Some(cassette) = runtime_environment.cassette.as_ref() {
+            client_builder = client_builder.with_cassette(cassette.clone());
+        }
+        let client = client_builder
+            .build_streaming_api_call()
+            .map_err(ai_client::client::ClientError::from)?;
+        let error = match client.stream().await {
+            Ok(stream) => {
+                let mut stream = Box::pin(stream);
+                let mut logger = ai_client::log::StdErrLogger::default().with_colorize(true);
+                let mut chunks = Vec::new();
+                let mut interrupted = None;
+                while let Some(chunk) = stream.next().await {
+                    let chunk = match chunk {
+                        Ok(chunk) => chunk,
+                        Err(error) => {
+                            interrupted = Some(error);
+                            break
+                        }
+                    };
+                    // Render the first choice as it is generated.
+                    if runtime_environment.log_output {
+                        for delta in chunk.text_deltas().into_iter().filter(|x| x.index == 0) {
+                            logger.log(&delta.content);
+                        }
+                    }
+                    chunks.push(chunk);
+                }
+                if runtime_environment.log_output {
+                    logger.log("\n");
+                    if interrupted.is_none() {
+                        log_tool_calls(&ai_client::client::ResponseChunkCollection(chunks.clone()).tool_calls(0));
+                    }
+                }
+                let Some(error) = interrupted else {
+                    let output = ai_client::client::ResponseChunkCollection(chunks);
+                    return Ok(Completion {
+                        choices: (0..output.choice_count().max(1)).map(|index| output.message(index)).collect(),
+                        usage: output.usage(),
+                        latency: started.elapsed(),
+                    })
+                };
+                error
+            }
+            Err(error) => error,
+        };
+        drop(permit);
+        if !error.is_retryable() || !retry_policy.should_retry(attempt) {
+            return Err(error.into())
+        }
+        let delay = retry_policy.delay(attempt, error.retry_after());
+        if runtime_environment.log_output {
+            eprintln!(
+                "{error}; retrying in {:.1}s (attempt {} of {})",
+                delay.as_secs_f64(),
+                attempt + 1,
+                retry_policy.max_attempts,
+            );
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn log_tool_calls(tool_calls: &[ai_client::request::ToolCall]) {
+    for call in tool_calls {
+        eprintln!("→ {}({})", call.function.name, call.function.arguments);
+    }
 }
 
 impl PromptSettings {
+    /// How `<msg>` markup is rendered, plain text unless set.
+    pub fn text_format(&self) -> TextFormatType {
+        self.text_format.as_ref().map(|x| x.0).unwrap_or_default()
+    }
     pub fn request_builder(&self) -> ai_client::request::RequestBuilder {
         let mut builder = ai_client::request::RequestBuilder::default();
         if let Some(model) = self.model.as_ref() {
             builder = builder.with_model(model.0.clone());
         }
         if let Some(temperature) = self.temperature.as_ref() {
-            builder = builder.with_temperature(temperature.0.clone());
+            builder = builder.with_temperature(temperature.0);
         }
         if let Some(n) = self.n.as_ref() {
-            builder = builder.with_n(n.0.clone());
+            builder = builder.with_n(n.0);
         }
         if let Some(max_tokens) = self.max_tokens.as_ref() {
-            builder = builder.with_max_tokens(max_tokens.0.clone());
+            builder = builder.with_max_tokens(max_tokens.0);
         }
         if let Some(top_p) = self.top_p.as_ref() {
-            builder = builder.with_top_p(top_p.0.clone());
+            builder = builder.with_top_p(top_p.0);
         }
         if let Some(frequency_penalty) = self.frequency_penalty.as_ref() {
-            builder = builder.with_frequency_penalty(frequency_penalty.0.clone());
+            builder = builder.with_frequency_penalty(frequency_penalty.0);
         }
         if let Some(presence_penalty) = self.presence_penalty.as_ref() {
-            builder = builder.with_presence_penalty(presence_penalty.0.clone());
+            builder = builder.with_presence_penalty(presence_penalty.0);
         }
         if let Some(logprobs) = self.logprobs.as_ref() {
-            builder = builder.with_logprobs(logprobs.0.clone());
+            builder = builder.with_logprobs(logprobs.0);
         }
         if let Some(top_logprobs) = self.top_logprobs.as_ref() {
-            builder = builder.with_top_logprobs(top_logprobs.0.clone());
+            builder = builder.with_top_logprobs(top_logprobs.0);
         }
         if let Some(response_format) = self.response_format.as_ref() {
             builder = match response_format.0 {
@@ -150,61 +646,139 @@ impl PromptSettings {
 // ————————————————————————————————————————————————————————————————————————————
 
 impl DocumentNode {
-    pub async fn invoke(&self, document_invocation: &DocumentInvocation) -> Result<PromptContext, ()> {
-        for child in self.children.iter() {
-            match child {
-                DocumentChildCode::Prompt(prompt) if prompt.name() == &document_invocation.target_prompt => {
-                    let prompt_context = prompt.invoke(&document_invocation.runtime_environment).await;
-                    return Ok(prompt_context)
+    pub fn find_prompt(&self, name: &str) -> Result<&PromptNode, InvocationError> {
+        let prompt = self.prompts().find(|prompt| prompt.name() == name);
+        prompt.ok_or_else(|| {
+            let available = self.prompts()
+                .map(|prompt| prompt.name().to_string())
+                .collect();
+            InvocationError::PromptNotFound { name: name.to_string(), available }
+        })
+    }
+    pub async fn invoke(&self, document_invocation: &DocumentInvocation) -> Result<PromptContext, Error> {
+        let mut prompt_context = PromptContext::new(document_invocation.runtime_environment.clone());
+        self.invoke_into(document_invocation, &mut prompt_context).await?;
+        Ok(prompt_context)
+    }
+    /// Evaluates independent invocations concurrently, e.g. different prompts
+    /// or inputs, returning their results in order.
+    ///
+    /// Requests are bounded by each invocation’s [`RuntimeEnvironment::limiter`].
+    pub async fn invoke_all(&self, document_invocations: &[DocumentInvocation]) -> Vec<Result<PromptContext, Error>> {
+        futures::future::join_all(document_invocations.iter().map(|x| self.invoke(x))).await
+    }
+    /// Like [`DocumentNode::invoke`] but keeps the progress made before a failure in `prompt_context`.
+    pub async fn invoke_into(&self, document_invocation: &DocumentInvocation, prompt_context: &mut PromptContext) -> Result<(), Error> {
+        let prompt = self.find_prompt(&document_invocation.target_prompt)?;
+        self.load_into(prompt_context)?;
+        prompt.invoke_into(prompt_context, &document_invocation.inputs, document_invocation.resume_from.as_ref()).await
+    }
+    /// Makes the document’s schemas, prompts and tools available to `prompt_context`.
+    pub(crate) fn load_into(&self, prompt_context: &mut PromptContext) -> Result<(), Error> {
+        for schema in self.schemas() {
+            let value = match &schema.source {
+                SchemaSource::Inline(value) => value.clone(),
+                SchemaSource::External(src) => {
+                    return Err(InvocationError::UnresolvedSchema { id: schema.id.clone(), src: src.clone() }.into())
                 }
-                DocumentChildCode::Prompt(_) => (),
-            }
+            };
+            prompt_context.schemas.insert(schema.id.clone(), value);
+        }
+        for prompt in self.prompts() {
+            prompt_context.prompts.insert(prompt.name().to_string(), prompt.clone());
         }
-        Err(())
+        for tool in self.tools() {
+            prompt_context.tools.insert(tool.name.clone(), tool.clone());
+        }
+        Ok(())
     }
 }
 
 impl PromptNode {
-    pub async fn invoke(&self, runtime_environment: &RuntimeEnvironment) -> PromptContext {
+    pub async fn invoke(&self, runtime_environment: &RuntimeEnvironment, inputs: &InputValues) -> Result<PromptContext, Error> {
+        let mut prompt_context = PromptContext::new(runtime_environment.clone());
+        self.invoke_into(&mut prompt_context, inputs, None).await?;
+        Ok(prompt_context)
+    }
+    /// Continues from the first unevaluated point of the snapshot, reusing all evaluated messages.
+    pub async fn resume(&self, runtime_environment: &RuntimeEnvironment, inputs: &InputValues, snapshot: &ConversationSnapshot) -> Result<PromptContext, Error> {
         let mut prompt_context = PromptContext::new(runtime_environment.clone());
+        self.invoke_into(&mut prompt_context, inputs, Some(snapshot)).await?;
+        Ok(prompt_context)
+    }
+    /// Checks the given values against the declared inputs, returning the declared ones.
+    ///
+    /// Values for undeclared inputs are ignored.
+    pub fn bind_inputs(&self, inputs: &InputValues) -> Result<InputValues, InvocationError> {
+        let mut bound = InputValues::new();
+        for declaration in self.inputs.iter() {
+            let name = &declaration.name;
+            let value = inputs
+                .get(name)
+                .cloned()
+                .ok_or_else(|| InvocationError::MissingInput { name: name.clone(), r#type: Some(declaration.r#type) })?;
+            let value = declaration.r#type
+                .coerce(value)
+                .map_err(|value| InvocationError::InvalidInput {
+                    name: name.clone(),
+                    expected: declaration.r#type,
+                    given: json_type_name(&value),
+                })?;
+            bound.insert(name.clone(), value);
+        }
+        Ok(bound)
+    }
+    /// Evaluates the prompt into a fresh `prompt_context`, which keeps the progress made before a failure.
+    pub async fn invoke_into(&self, prompt_context: &mut PromptContext, inputs: &InputValues, resume_from: Option<&ConversationSnapshot>) -> Result<(), Error> {
+        let inputs = self.bind_inputs(inputs)?;
+        let mut replay = Replay::new(resume_from);
+        if let Some(snapshot) = resume_from {
+            prompt_context.conversation.calls.extend(snapshot.usage.calls.iter().cloned());
+        }
+        prompt_context.conversation.prompt_settings = self.settings.to_prompt_settings();
         for child in self.children.iter() {
             match child {
                 PromptChildNode::Msg(msg) => {
+                    let text_format = prompt_context.conversation.prompt_settings.text_format();
                     let message = match msg.role {
                         MessageRole::System => {
-                            ai_client::request::Message::system(msg.text_content())
+                            ai_client::request::Message::system(msg.render(&inputs, text_format)?)
                         }
                         MessageRole::User => {
-                            ai_client::request::Message::user(msg.text_content())
+                            ai_client::request::Message::user(msg.render(&inputs, text_format)?)
                         }
                         MessageRole::Assistant => {
-                            ai_client::request::Message::assistant(msg.text_content())
+                            ai_client::request::Message::assistant(msg.render(&inputs, text_format)?)
                         }
                     };
+                    replay.message(&message)?;
                     let message = ConversationMessage {
                         message,
                         evaluated: false,
+                        verification: None,
+                        attempts: Vec::new(),
+                        selection: None,
                     };
                     prompt_context.conversation.messages.push(message);
                 }
                 PromptChildNode::Breakpoint(breakpoint) => {
-                    let output = prompt_context.invoke().await;
-                    let message = match breakpoint.role {
-                        MessageRole::System => {
-                            ai_client::request::Message::system(output)
-                        }
-                        MessageRole::User => {
-                            ai_client::request::Message::user(output)
-                        }
-                        MessageRole::Assistant => {
-                            ai_client::request::Message::assistant(output)
-                        }
-                    };
-                    let message = ConversationMessage {
-                        message,
-                        evaluated: true,
-                    };
+                    if let Some(snapshots) = replay.evaluated(breakpoint.role)? {
+                        let messages = snapshots.iter().map(|snapshot| ConversationMessage {
+                            message: snapshot.message_payload.clone(),
+                            evaluated: true,
+                            verification: snapshot.verification.clone(),
+                            attempts: snapshot.attempts.clone(),
+                            selection: snapshot.selection.clone(),
+                        });
+                        prompt_context.conversation.messages.extend(messages);
+                        continue
+                    }
+                    let message = breakpoint.evaluate(prompt_context, &inputs).await?;
+                    let verification = message.verification.clone();
                     prompt_context.conversation.messages.push(message);
+                    if let Some(report) = verification.filter(|x| !x.valid) {
+                        return Err(InvocationError::VerificationFailed { schema: report.schema, errors: report.errors }.into())
+                    }
                 }
                 PromptChildNode::Set(set) => {
                     prompt_context.conversation.prompt_settings.merge_mut(set.prompt_settings.clone());
@@ -212,15 +786,301 @@ impl PromptNode {
             }
         }
         if prompt_context.conversation.already_evaluated().not() {
-            let output = prompt_context.invoke().await;
-            let message = ai_client::request::Message::assistant(output);
-            let message = ConversationMessage {
-                message,
-                evaluated: true,
+            let message = match replay.evaluated(MessageRole::Assistant)?.and_then(|x| x.last()) {
+                Some(snapshot) => snapshot.message_payload.clone(),
+                None => {
+                    let output = prompt_context.invoke().await?;
+                    ai_client::request::Message::assistant(output)
+                }
             };
+            let message = ConversationMessage::evaluated(message);
             prompt_context.conversation.messages.push(message);
         }
-        prompt_context
+        replay.finish()?;
+        Ok(())
     }
 }
 
+// ————————————————————————————————————————————————————————————————————————————
+// VERIFICATION
+// ————————————————————————————————————————————————————————————————————————————
+
+impl BreakpointNode {
+    /// Generates the breakpoint’s output, repairing output that fails verification up to `max-attempts` times.
+    ///
+    /// Of several choices (see `n`), the one `select` picks is verified and
+    /// repaired; repairs only ever generate a single output.
+    async fn evaluate(&self, prompt_context: &mut PromptContext, inputs: &InputValues) -> Result<ConversationMessage, Error> {
+        let schema = self.schema(prompt_context, inputs)?;
+        let choices = self.generate(prompt_context).await?;
+        let selection = self.select(prompt_context, inputs, schema.as_ref(), choices).await?;
+        let mut output = selection.output().to_string();
+        let selection = Some(selection).filter(|x| x.choices.len() > 1);
+        let mut attempts = Vec::<RepairAttempt>::new();
+        loop {
+            let verification = self.verification.map(|_| {
+                VerificationReport::check(&output, schema.as_ref().map(|(name, value)| (name.clone(), value)))
+            });
+            let attempt = attempts.len() as u32 + 1;
+            match verification {
+                Some(report) if !report.valid && attempt < self.max_attempts => {
+                    if prompt_context.runtime_environment.log_output {
+                        eprintln!(
+                            "output failed verification: {}; repairing (attempt {} of {})",
+                            report.errors.first().map(ToString::to_string).unwrap_or_default(),
+                            attempt + 1,
+                            self.max_attempts,
+                        );
+                    }
+                    let failed = RepairAttempt { output, verification: report };
+                    output = self.repair(prompt_context, inputs, schema.as_ref(), &failed).await?;
+                    attempts.push(failed);
+                }
+                verification => {
+                    return Ok(ConversationMessage {
+                        message: self.role.message(output),
+                        evaluated: true,
+                        verification,
+                        attempts,
+                        selection,
+                    })
+                }
+            }
+        }
+    }
+    /// The model’s answer, every choice of it, running the tools it calls in between.
+    ///
+    /// Each call and its result are appended to the conversation as they happen,
+    /// so the answer (and later breakpoints) see them. Tool calls of the first
+    /// choice are run, and choices calling tools instead of answering are dropped.
+    async fn generate(&self, prompt_context: &mut PromptContext) -> Result<Vec<String>, Error> {
+        if self.tools.is_empty() {
+            let messages = prompt_context.conversation.messages
+                .iter()
+                .map(|x| x.message.clone())
+                .collect::<Vec<_>>();
+            let choices = prompt_context.complete_choices(&messages, &[], None).await?;
+            return Ok(choices.iter().map(|x| x.content().to_string()).collect())
+        }
+        let definitions = self.tool_definitions(prompt_context)?;
+        let unbound = self.tools
+            .iter()
+            .filter_map(|name| prompt_context.tools.get(name))
+            .find(|tool| !tool.is_bound(&prompt_context.runtime_environment.tools));
+        if let Some(tool) = unbound {
+            return Err(InvocationError::UnboundTool { name: tool.name.clone() }.into())
+        }
+        let mut tool_choice = self.tool_choice.clone();
+        for round in 0..=self.max_tool_rounds {
+            let messages = prompt_context.conversation.messages
+                .iter()
+                .map(|x| x.message.clone())
+                .collect::<Vec<_>>();
+            let mut choices = prompt_context.complete_choices(&messages, &definitions, tool_choice.take()).await?;
+            let tool_calls = choices[0].tool_calls().to_vec();
+            if tool_calls.is_empty() {
+                let answers = choices.iter().filter(|x| x.tool_calls().is_empty());
+                return Ok(answers.map(|x| x.content().to_string()).collect())
+            }
+            if round == self.max_tool_rounds {
+                break
+            }
+            let reply = choices.swap_remove(0);
+            prompt_context.conversation.messages.push(ConversationMessage::evaluated(reply));
+            for call in tool_calls.iter() {
+                let content = prompt_context.call_tool(call).await?;
+                let message = ai_client::request::Message::tool(content, &call.id);
+                prompt_context.conversation.messages.push(ConversationMessage::evaluated(message));
+            }
+        }
+        Err(InvocationError::ToolRoundsExceeded { rounds: self.max_tool_rounds }.into())
+    }
+    /// Picks the choice that continues the conversation, see [`SelectionPolicy`].
+    async fn select(
+        &self,
+        prompt_context: &mut PromptContext,
+        inputs: &InputValues,
+        schema: Option<&(String, Value)>,
+        choices: Vec<String>,
+    ) -> Result<Selection, Error> {
+        let selected = match &self.select {
+            _ if choices.len() < 2 => 0,
+            SelectionPolicy::First => 0,
+            SelectionPolicy::Longest => selection::longest(&choices),
+            SelectionPolicy::Majority => selection::majority(&choices),
+            SelectionPolicy::PassesSchema => choices
+                .iter()
+                .position(|x| VerificationReport::check(x, schema.map(|(name, value)| (name.clone(), value))).valid)
+                .unwrap_or(0),
+            SelectionPolicy::Judge(name) => {
+                let mut judge_inputs = inputs.clone();
+                judge_inputs.insert(String::from("choices"), Value::String(selection::numbered(&choices)));
+                let reply = prompt_context.invoke_prompt(name, &judge_inputs).await?;
+                selection::judgement(&reply, choices.len())
+                    .ok_or_else(|| InvocationError::InvalidJudgement { prompt: name.clone(), reply, choices: choices.len() })?
+            }
+        };
+        if choices.len() > 1 && prompt_context.runtime_environment.log_output {
+            eprintln!("selected choice {} of {} ({})", selected + 1, choices.len(), self.select);
+        }
+        Ok(Selection { policy: self.select.to_string(), choices, selected })
+    }
+    /// The definitions of the tools the model may call, sent along with each request.
+    pub(crate) fn tool_definitions(&self, prompt_context: &PromptContext) -> Result<Vec<ai_client::request::Tool>, InvocationError> {
+        self.tools
+            .iter()
+            .map(|name| {
+                prompt_context.tools
+                    .get(name)
+                    .ok_or_else(|| InvocationError::UnknownTool { name: name.clone() })?
+                    .definition(&prompt_context.schemas)
+            })
+            .collect()
+    }
+    /// The schema to check the output against, along with how it is referred to.
+    fn schema(&self, prompt_context: &PromptContext, inputs: &InputValues) -> Result<Option<(String, Value)>, InvocationError> {
+        if self.verification != Some(Verification::Schema) {
+            return Ok(None)
+        }
+        let schema = match self.schema.as_ref() {
+            Some(reference @ SchemaReference::Document(id)) => {
+                let schema = prompt_context.schemas
+                    .get(id)
+                    .ok_or_else(|| InvocationError::UnknownSchema { id: id.clone() })?;
+                Some((reference.to_string(), schema.clone()))
+            }
+            Some(reference @ SchemaReference::Input(name)) => {
+                // Inputs are bound before any breakpoint is evaluated.
+                let schema = inputs
+                    .get(name)
+                    .ok_or_else(|| InvocationError::MissingInput { name: name.clone(), r#type: Some(InputType::Schema) })?;
+                Some((reference.to_string(), schema.clone()))
+            }
+            None => None,
+        };
+        // Rather than passing output that breaks constraints which aren’t checked.
+        if let Some((reference, schema)) = schema.as_ref() {
+            let keywords = unsupported_keywords(schema);
+            if !keywords.is_empty() {
+                return Err(InvocationError::UnsupportedSchema { schema: reference.clone(), keywords })
+            }
+        }
+        Ok(schema)
+    }
+    /// Asks for a corrected version of the failed output.
+    ///
+    /// With a `repair` prompt, that prompt is evaluated with the inputs `output`,
+    /// `errors` and `schema` (plus the current prompt’s inputs) and its last
+    /// message is the new output. Otherwise the failed output and its errors
+    /// are appended to the conversation for a single call.
+    async fn repair(
+        &self,
+        prompt_context: &mut PromptContext,
+        inputs: &InputValues,
+        schema: Option<&(String, Value)>,
+        failed: &RepairAttempt,
+    ) -> Result<String, Error> {
+        let errors = failed.verification.errors
+            .iter()
+            .map(|x| format!("- {x}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let Some(name) = self.repair.as_ref() else {
+            let feedback = format!("Your reply failed verification:\n{errors}\n\nReply with the corrected JSON only.");
+            let mut messages = prompt_context.conversation.messages
+                .iter()
+                .map(|x| x.message.clone())
+                .collect::<Vec<_>>();
+            messages.push(self.role.message(&failed.output));
+            messages.push(MessageRole::User.message(feedback));
+            return prompt_context.invoke_with(&messages).await
+        };
+        let mut repair_inputs = inputs.clone();
+        repair_inputs.insert(String::from("output"), Value::String(failed.output.clone()));
+        repair_inputs.insert(String::from("errors"), Value::String(errors));
+        if let Some((_, schema)) = schema {
+            repair_inputs.insert(String::from("schema"), schema.clone());
+        }
+        prompt_context.invoke_prompt(name, &repair_inputs).await
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// RESUMPTION
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Walks a snapshot alongside the prompt’s children.
+///
+/// Every message of the snapshot must line up with the prompt, so resuming
+/// with a snapshot of another (or an edited) prompt fails instead of silently
+/// mixing conversations.
+struct Replay<'a> {
+    messages: &'a [MessageSnapshot],
+    position: usize,
+}
+
+impl<'a> Replay<'a> {
+    fn new(snapshot: Option<&'a ConversationSnapshot>) -> Self {
+        let messages = snapshot.map(|x| x.messages.as_slice()).unwrap_or_default();
+        Self { messages, position: 0 }
+    }
+    fn mismatch(&self, reason: String) -> InvocationError {
+        InvocationError::SnapshotMismatch { position: self.position + 1, reason }
+    }
+    /// Checks a `<msg>` against the snapshot.
+    fn message(&mut self, message: &ai_client::request::Message) -> Result<(), InvocationError> {
+        let Some(snapshot) = self.messages.get(self.position) else {
+            return Ok(())
+        };
+        if snapshot.evaluation_point {
+            return Err(self.mismatch(String::from("expected a `<msg>`, found an evaluated message")))
+        }
+        if &snapshot.message_payload != message {
+            let role = message.role();
+            return Err(self.mismatch(format!("the `<msg role=\"{role}\">` content differs")))
+        }
+        self.position += 1;
+        Ok(())
+    }
+    /// The already evaluated output of a breakpoint, if the snapshot has it,
+    /// preceded by the tool calls and results leading up to it.
+    ///
+    /// Output that failed verification, or tool calls without an output, are
+    /// evaluated again, so the snapshot ends there.
+    fn evaluated(&mut self, role: MessageRole) -> Result<Option<&'a [MessageSnapshot]>, InvocationError> {
+        let start = self.position;
+        let is_tool_exchange = |x: &MessageSnapshot| {
+            x.evaluation_point && (x.message_payload.role() == "tool" || !x.message_payload.tool_calls().is_empty())
+        };
+        while self.messages.get(self.position).is_some_and(is_tool_exchange) {
+            self.position += 1;
+        }
+        let Some(snapshot) = self.messages.get(self.position) else {
+            self.messages = &self.messages[..start];
+            self.position = start;
+            return Ok(None)
+        };
+        if snapshot.verification.as_ref().is_some_and(|x| !x.valid) {
+            self.messages = &self.messages[..start];
+            self.position = start;
+            return Ok(None)
+        }
+        if !snapshot.evaluation_point {
+            return Err(self.mismatch(String::from("expected an evaluated message, found a `<msg>`")))
+        }
+        let expected = role.as_str();
+        let given = snapshot.message_payload.role();
+        if given != expected {
+            return Err(self.mismatch(format!("expected an evaluated `{expected}` message, found `{given}`")))
+        }
+        self.position += 1;
+        Ok(Some(&self.messages[start..self.position]))
+    }
+    fn finish(&self) -> Result<(), InvocationError> {
+        if self.position < self.messages.len() {
+            let remaining = self.messages.len() - self.position;
+            return Err(self.mismatch(format!("the snapshot has {remaining} more message(s) than the prompt")))
+        }
+        Ok(())
+    }
+}
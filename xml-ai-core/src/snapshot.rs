@@ -1,12 +1,80 @@
+use std::path::Path;
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageSnapshot {
     pub message_payload: ai_client::request::Message,
     pub evaluation_point: bool,
+    /// The outcome of `<breakpoint verification="...">`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<crate::common::schema::VerificationReport>,
+    /// Earlier outputs that failed verification and were repaired.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<crate::common::schema::RepairAttempt>,
+    /// Every choice of the breakpoint and which one was selected, if the model generated several.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selection: Option<crate::common::selection::Selection>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationSnapshot {
     pub messages: Vec<MessageSnapshot>,
+    #[serde(default)]
+    pub usage: crate::usage::UsageSummary,
+}
+
+impl ConversationSnapshot {
+    /// Whether any message came from the model, i.e. whether there’s anything worth resuming.
+    pub fn has_evaluated(&self) -> bool {
+        self.messages.iter().any(|x| x.evaluation_point)
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let format = SnapshotFormat::from_path(path)?;
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| Error::Io { path: path.to_path_buf(), error })?;
+        let invalid = |message: String| Error::InvalidSnapshot { path: path.to_path_buf(), message };
+        match format {
+            SnapshotFormat::Json => serde_json::from_str(&source).map_err(|error| invalid(error.to_string())),
+            SnapshotFormat::Toml => toml::from_str(&source).map_err(|error| invalid(error.to_string())),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let snapshot = match SnapshotFormat::from_path(path)? {
+            SnapshotFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|error| Error::Serialize(error.to_string()))?,
+            SnapshotFormat::Toml => toml::to_string_pretty(self)
+                .map_err(|error| Error::Serialize(error.to_string()))?,
+        };
+        if let Some(parent) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|error| Error::Io { path: parent.to_path_buf(), error })?;
+        }
+        std::fs::write(path, snapshot)
+            .map_err(|error| Error::Io { path: path.to_path_buf(), error })
+    }
+}
+
+/// Snapshots are stored as JSON or TOML, depending on the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Json,
+    Toml,
+}
+
+impl SnapshotFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("json") => Ok(Self::Json),
+            Some("toml") => Ok(Self::Toml),
+            _ => {
+                let message = format!("snapshot file must end in `.json` or `.toml`, given `{}`", path.display());
+                Err(Error::Configuration(message))
+            }
+        }
+    }
 }
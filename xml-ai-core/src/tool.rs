@@ -0,0 +1,123 @@
+//! Running the tools a model calls at a breakpoint, see `<tool>`.
+//!
+//! A tool is bound either to a Rust callback registered on the runtime, see
+//! [`ToolRegistry`], or to the executable given via `command="..."`, which gets
+//! the call’s arguments as JSON on stdin and answers on stdout.
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+use crate::ast::tool::{ToolNode, ToolParameters};
+use crate::runtime::InvocationError;
+
+/// What a tool returns; errors are sent to the model as well, so it can correct its call.
+pub type ToolResult = Result<String, String>;
+
+type Callback = Arc<dyn Fn(Value) -> BoxFuture<'static, ToolResult> + Send + Sync>;
+
+/// Rust callbacks for `<tool>`s by name, shared by clones.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    callbacks: BTreeMap<String, Callback>,
+}
+
+impl std::fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.callbacks.keys()).finish()
+    }
+}
+
+impl ToolRegistry {
+    /// Binds the tool `name` to `callback`, which takes precedence over the tool’s `command`.
+    pub fn register<F, R>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: Fn(Value) -> R + Send + Sync + 'static,
+        R: Future<Output = ToolResult> + Send + 'static,
+    {
+        let callback: Callback = Arc::new(move |arguments| Box::pin(callback(arguments)));
+        self.callbacks.insert(name.into(), callback);
+    }
+    pub fn with_callback<F, R>(mut self, name: impl Into<String>, callback: F) -> Self
+    where
+        F: Fn(Value) -> R + Send + Sync + 'static,
+        R: Future<Output = ToolResult> + Send + 'static,
+    {
+        self.register(name, callback);
+        self
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.callbacks.contains_key(name)
+    }
+}
+
+impl ToolNode {
+    /// The definition sent to the model, with `parameters="..."` looked up in `schemas`.
+    pub fn definition(&self, schemas: &BTreeMap<String, Value>) -> Result<ai_client::request::Tool, InvocationError> {
+        let mut function = ai_client::request::Function::new(&self.name);
+        if let Some(description) = self.description.as_ref() {
+            function = function.with_description(description);
+        }
+        let parameters = match self.parameters.as_ref() {
+            Some(ToolParameters::Inline(value)) => Some(value.clone()),
+            Some(ToolParameters::Schema(id)) => {
+                let schema = schemas
+                    .get(id)
+                    .ok_or_else(|| InvocationError::UnknownSchema { id: id.clone() })?;
+                Some(schema.clone())
+            }
+            None => None,
+        };
+        if let Some(parameters) = parameters {
+            function = function.with_parameters(parameters);
+        }
+        Ok(ai_client::request::Tool::function(function))
+    }
+    /// Whether there’s something to run, i.e. a registered callback or a `command`.
+    pub fn is_bound(&self, registry: &ToolRegistry) -> bool {
+        registry.contains(&self.name) || self.command.is_some()
+    }
+    /// Runs the tool, failing only if it can’t be run at all.
+    pub async fn call(&self, registry: &ToolRegistry, arguments: Value) -> Result<ToolResult, InvocationError> {
+        if let Some(callback) = registry.callbacks.get(&self.name) {
+            return Ok(callback(arguments).await)
+        }
+        let Some(command) = self.command.as_ref() else {
+            return Err(InvocationError::UnboundTool { name: self.name.clone() })
+        };
+        run_command(command, &arguments)
+            .await
+            .map_err(|error| InvocationError::ToolFailed { name: self.name.clone(), message: format!("`{}`: {error}", shell_words::join(command)) })
+    }
+}
+
+/// Runs `command` with `arguments` on stdin; a non-zero exit status is an error result with the command’s stderr.
+async fn run_command(command: &[String], arguments: &Value) -> std::io::Result<ToolResult> {
+    let mut child = tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that doesn’t read its arguments may exit before they are written.
+        match stdin.write_all(arguments.to_string().as_bytes()).await {
+            Err(error) if error.kind() != std::io::ErrorKind::BrokenPipe => return Err(error),
+            _ => (),
+        }
+    }
+    let output = child.wait_with_output().await?;
+    if output.status.success() {
+        return Ok(Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string()))
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    match stderr.is_empty() {
+        true => Ok(Err(format!("the command exited with {}", output.status))),
+        false => Ok(Err(stderr)),
+    }
+}
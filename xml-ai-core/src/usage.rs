@@ -0,0 +1,159 @@
+//! Token usage and cost accounting.
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+use ai_client::response::batch::Usage;
+
+// ————————————————————————————————————————————————————————————————————————————
+// PRICES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Prices in USD per one million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let input = usage.prompt_tokens as f64 * self.input;
+        let output = usage.completion_tokens as f64 * self.output;
+        (input + output) / 1_000_000.0
+    }
+}
+
+/// Per-model prices, e.g.
+///
+/// ```toml
+/// [model."gpt-4o"]
+/// input = 2.50
+/// output = 10.00
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceTable {
+    #[serde(default, rename = "model")]
+    pub models: BTreeMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    pub fn with_price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.models.insert(model.into(), price);
+        self
+    }
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        self.models.get(model)
+    }
+    /// The cost of a call, if the model has a price and the provider reported usage.
+    pub fn cost(&self, model: &str, usage: Option<&Usage>) -> Option<f64> {
+        Some(self.get(model)?.cost(usage?))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// RECORDS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A single LLM call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallUsage {
+    pub model: String,
+    pub provider: String,
+    /// `None` if the provider didn’t report usage.
+    pub usage: Option<Usage>,
+    /// Wall-clock time of the call, including retries.
+    pub latency_ms: u64,
+    /// `None` if the model isn’t in the price table or there’s no usage.
+    pub cost: Option<f64>,
+    /// Answered from the response cache, so neither its usage nor its latency count.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+/// All LLM calls of a run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub calls: Vec<CallUsage>,
+    /// Sum over all calls that reported usage.
+    pub total: Usage,
+    pub latency_ms: u64,
+    /// Sum over all priced calls, `None` if there are none.
+    pub cost: Option<f64>,
+}
+
+impl UsageSummary {
+    pub fn from_calls(calls: Vec<CallUsage>) -> Self {
+        let total = calls
+            .iter()
+            .filter(|x| !x.cached)
+            .filter_map(|x| x.usage)
+            .fold(Usage::default(), |x, y| x + y);
+        let latency_ms = calls.iter().map(|x| x.latency_ms).sum();
+        let cost = calls
+            .iter()
+            .filter_map(|x| x.cost)
+            .reduce(|x, y| x + y);
+        Self { calls, total, latency_ms, cost }
+    }
+    /// Calls without reported usage, which aren’t included in the total.
+    pub fn unreported(&self) -> usize {
+        self.calls.iter().filter(|x| !x.cached && x.usage.is_none()).count()
+    }
+    /// Calls that aren’t included in the total cost.
+    pub fn unpriced(&self) -> usize {
+        self.calls.iter().filter(|x| !x.cached && x.cost.is_none()).count()
+    }
+    /// Calls answered from the response cache.
+    pub fn cached(&self) -> usize {
+        self.calls.iter().filter(|x| x.cached).count()
+    }
+}
+
+/// One line per call followed by the totals; the alternate form (`{:#}`) only prints the totals.
+impl std::fmt::Display for UsageSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let calls = if f.alternate() { &[][..] } else { self.calls.as_slice() };
+        for (index, call) in calls.iter().enumerate() {
+            write!(f, "  #{:<3} {} ({})", index + 1, call.model, call.provider)?;
+            if call.cached {
+                writeln!(f, " · cached")?;
+                continue
+            }
+            match call.usage.as_ref() {
+                Some(usage) => write!(f, " · {} prompt + {} completion tokens", usage.prompt_tokens, usage.completion_tokens)?,
+                None => write!(f, " · usage not reported")?,
+            }
+            write!(f, " · {:.2}s", call.latency_ms as f64 / 1000.0)?;
+            if let Some(cost) = call.cost {
+                write!(f, " · ${cost:.6}")?;
+            }
+            writeln!(f)?;
+        }
+        let calls = self.calls.len();
+        write!(
+            f,
+            "  {calls} call(s) · {} prompt + {} completion = {} tokens · {:.2}s",
+            self.total.prompt_tokens,
+            self.total.completion_tokens,
+            self.total.total_tokens,
+            self.latency_ms as f64 / 1000.0,
+        )?;
+        if let Some(cost) = self.cost {
+            write!(f, " · ${cost:.6}")?;
+            let unpriced = self.unpriced();
+            if unpriced > 0 {
+                write!(f, " ({unpriced} call(s) unpriced)")?;
+            }
+        }
+        let cached = self.cached();
+        if cached > 0 {
+            write!(f, " · {cached} call(s) cached")?;
+        }
+        let unreported = self.unreported();
+        if unreported > 0 {
+            write!(f, " · {unreported} call(s) without usage")?;
+        }
+        Ok(())
+    }
+}
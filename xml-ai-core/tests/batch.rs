@@ -0,0 +1,39 @@
+//! Resuming a batch from its results file.
+use xml_ai_core::batch::BatchResult;
+
+fn result(id: &str, error: Option<&str>) -> String {
+    let result = match error {
+        None => serde_json::json!({ "id": id, "output": id, "snapshot": { "messages": [] } }),
+        Some(error) => serde_json::json!({ "id": id, "error": error, "snapshot": { "messages": [] } }),
+    };
+    result.to_string()
+}
+
+#[test]
+fn only_successful_rows_are_retained() {
+    let source = [result("a", None), result("b", Some("boom")), result("c", None), String::new()].join("\n");
+    let completed = BatchResult::completed(&source).unwrap();
+    assert_eq!(completed.ids.iter().collect::<Vec<_>>(), ["a", "c"]);
+    assert_eq!(completed.lines, format!("{}\n{}\n", result("a", None), result("c", None)));
+    assert_eq!(completed.failed, 1);
+}
+
+#[test]
+fn a_retried_row_is_retained_once() {
+    // Files written before failed rows were dropped may list an id twice.
+    let source = [result("a", Some("boom")), result("a", None), result("a", None), String::new()].join("\n");
+    let completed = BatchResult::completed(&source).unwrap();
+    assert_eq!(completed.lines, format!("{}\n", result("a", None)));
+}
+
+#[test]
+fn a_cut_off_line_is_dropped() {
+    let line = result("b", None);
+    let source = format!("{}\n{}", result("a", None), &line[..line.len() / 2]);
+    let completed = BatchResult::completed(&source).unwrap();
+    assert_eq!(completed.ids.len(), 1);
+    assert_eq!(completed.lines, format!("{}\n", result("a", None)));
+    // Unless it’s complete, or isn’t the last one.
+    assert_eq!(BatchResult::completed(&format!("{source}\n")).unwrap_err().split(':').next(), Some("line 2"));
+    assert!(BatchResult::completed(&format!("{source}\n{}\n", result("c", None))).is_err());
+}
@@ -0,0 +1,77 @@
+//! Keying cached completions by the request, independent of how it was put together.
+use std::collections::HashMap;
+
+use serde_json::json;
+use super_ai_client::request::{Function, Message, Request, RequestBuilder, Tool};
+use xml_ai_core::cache::{CacheEntry, ResponseCache};
+
+fn builder() -> RequestBuilder {
+    RequestBuilder::default()
+        .with_model("gpt-4o")
+        .with_message(Message::system("You are terse."))
+        .with_message(Message::user("Hello there"))
+        .with_temperature(0.5)
+}
+
+fn key(request: RequestBuilder) -> String {
+    ResponseCache::key("openai", &request.build().unwrap())
+}
+
+#[test]
+fn equivalent_requests_share_a_key() {
+    // The same parameters set in another order.
+    let reordered = RequestBuilder::default()
+        .with_temperature(0.5)
+        .with_message(Message::system("You are terse."))
+        .with_message(Message::user("Hello there"))
+        .with_model("gpt-4o");
+    assert_eq!(key(reordered), key(builder()));
+    // Hash maps serialize in an arbitrary order, which differs between maps.
+    let tokens = (0..64).map(|x| (x.to_string(), x)).collect::<Vec<_>>();
+    let forward = tokens.iter().cloned().collect::<HashMap<_, _>>();
+    let backward = tokens.iter().rev().cloned().collect::<HashMap<_, _>>();
+    assert_eq!(key(builder().with_logit_bias(forward)), key(builder().with_logit_bias(backward)));
+    // As do JSON objects written with their keys in another order.
+    let parameters = json!({ "type": "object", "properties": { "city": { "type": "string" }, "days": { "type": "integer" } } });
+    let reordered = json!({ "properties": { "days": { "type": "integer" }, "city": { "type": "string" } }, "type": "object" });
+    let tool = |parameters| vec![Tool::function(Function::new("get_weather").with_parameters(parameters))];
+    assert_eq!(key(builder().with_tools(tool(parameters))), key(builder().with_tools(tool(reordered))));
+}
+
+#[test]
+fn any_change_changes_the_key() {
+    let original = key(builder());
+    assert_eq!(original.len(), 64);
+    assert!(original.chars().all(|x| x.is_ascii_hexdigit()));
+    let changed = [
+        key(builder().with_model("gpt-4o-mini")),
+        key(builder().with_messages(vec![Message::system("You are terse."), Message::user("Hello here")])),
+        key(builder().with_messages(vec![Message::system("You are terse."), Message::assistant("Hello there")])),
+        key(builder().with_message(Message::user("Hello there"))),
+        key(builder().with_temperature(0.7)),
+        key(builder().with_max_tokens(100)),
+        key(builder().with_logit_bias(HashMap::from([(String::from("1"), -100)]))),
+        ResponseCache::key("azure", &builder().build().unwrap()),
+    ];
+    for (index, key) in changed.iter().enumerate() {
+        assert_ne!(key, &original, "change {index}");
+    }
+    // Message order matters, unlike object key order.
+    let swapped = builder().with_messages(vec![Message::user("Hello there"), Message::system("You are terse.")]);
+    assert_ne!(key(swapped), original);
+}
+
+#[test]
+fn entries_are_found_by_key() {
+    let directory = std::env::temp_dir().join(format!("xml-ai-cache-{}", std::process::id()));
+    let cache = ResponseCache::new(&directory);
+    let request = builder().build().unwrap();
+    assert!(cache.get("openai", &request).is_none());
+    let entry = CacheEntry::new(String::from("openai"), json!({}), &[Message::assistant("Hi")], None, 10);
+    cache.put(&request, &entry);
+    assert_eq!(cache.get("openai", &request).unwrap().content, "Hi");
+    let other: Request = builder().with_temperature(0.7).build().unwrap();
+    assert!(cache.get("openai", &other).is_none());
+    assert_eq!((cache.stats().hits, cache.stats().misses, cache.stats().stored), (1, 2, 1));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,80 @@
+//! Checking documents without evaluating them.
+use xml_ai_core::check::CheckReport;
+use xml_ai_core::parser::DslFormatError;
+
+const DOCUMENT: &str = r#"<prompt name="a" model="stand-in" temprature="0.2">
+    <breakpoint role="assistant"></breakpoint>
+    <msg role="user">Hi <breakpoint role="assistant"></breakpoint></msg>
+</prompt>
+<prompt name="a" model="stand-in" input:x="of type String">
+    <set n="2"></set>
+</prompt>
+"#;
+
+#[test]
+fn warnings_are_located_and_ordered() {
+    let report = CheckReport::check(DOCUMENT);
+    assert!(report.error.is_none(), "{:?}", report.error);
+    let warnings = report.warnings
+        .iter()
+        .map(|x| format!("{}: {}", x.location.span.unwrap(), x.message))
+        .collect::<Vec<_>>();
+    assert_eq!(warnings, [
+        "1:35: unknown attribute `temprature` on `<prompt>` is ignored",
+        "2:5: this breakpoint sends an empty conversation",
+        "3:25: this breakpoint is never evaluated",
+        "5:1: prompt 'a' is shadowed by an earlier prompt with the same name",
+        "5:1: prompt 'a' ends with an empty conversation",
+    ]);
+    assert_eq!(report.warnings[0].hint().as_deref(), Some("did you mean `temperature`?"));
+    assert!(report.passed(true));
+    assert!(!report.passed(false));
+}
+
+#[test]
+fn errors_fail_the_check() {
+    let report = CheckReport::check(r#"<prompt name="a"><foo></foo></prompt>"#);
+    assert!(report.error.is_some());
+    assert!(!report.passed(true));
+    let report = CheckReport::check(r#"<prompt name="a"><msg role="user">Hi</msg><breakpoint role="assistant"></breakpoint></prompt>"#);
+    assert!(report.passed(false), "{:?}", report.warnings);
+}
+
+#[test]
+fn selection_needs_several_choices() {
+    let source = concat!(
+        "<prompt name=\"a\" model=\"stand-in\">\n",
+        "    <msg role=\"user\">Hi</msg>\n",
+        "    <breakpoint role=\"assistant\" select=\"longest\"></breakpoint>\n",
+        "    <set n=\"3\"></set>\n",
+        "    <msg role=\"user\">Again</msg>\n",
+        "    <breakpoint role=\"assistant\" select=\"majority\"></breakpoint>\n",
+        "</prompt>\n",
+    );
+    let report = CheckReport::check(source);
+    let warnings = report.warnings.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(warnings, ["`select` has nothing to choose from, this breakpoint generates a single choice"]);
+    assert_eq!(report.warnings[0].location.span.unwrap().to_string(), "3:5");
+}
+
+#[test]
+fn breakpoints_need_something_to_continue_from() {
+    let source = concat!(
+        "<prompt name=\"a\" model=\"stand-in\">\n",
+        "    <msg role=\"user\">Hi</msg>\n",
+        "    <breakpoint role=\"assistant\"></breakpoint>\n",
+        "    <set temperature=\"0.2\"></set>\n",
+        "    <breakpoint role=\"assistant\"></breakpoint>\n",
+        "    <msg role=\"user\">And then?</msg>\n",
+        "    <breakpoint role=\"assistant\"></breakpoint>\n",
+        "</prompt>\n",
+    );
+    let report = CheckReport::check(source);
+    let warnings = report.warnings
+        .iter()
+        .map(|x| format!("{}: {}", x.location.span.unwrap(), x.message))
+        .collect::<Vec<_>>();
+    // A `<set>` changes the settings, but adds nothing to the conversation.
+    assert_eq!(warnings, ["5:5: this breakpoint has nothing new to continue from"]);
+    assert!(report.warnings[0].hint().unwrap().contains("add a `<msg>`"));
+}
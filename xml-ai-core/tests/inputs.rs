@@ -0,0 +1,95 @@
+//! Binding the values given for a prompt’s declared inputs, and rendering them via `from="..."`.
+use serde_json::{Value, json};
+use xml_ai_core::ast::document::DocumentNode;
+use xml_ai_core::ast::prompt::{PromptChildNode, PromptNode};
+use xml_ai_core::common::input::{InputType, InputValues};
+use xml_ai_core::common::prompt::TextFormatType;
+use xml_ai_core::error::Error;
+use xml_ai_core::mock::MockProvider;
+use xml_ai_core::runtime::{DocumentInvocation, InvocationError, RuntimeEnvironment};
+
+const DOCUMENT: &str = r#"
+<prompt name="count" model="m" input:word="of type String" input:times="of type Integer" input:options="of type Object">
+    <msg role="user"><span from="word"></span> × <span from="times"></span> with <span from="options"></span></msg>
+</prompt>
+"#;
+
+fn prompt(document: &DocumentNode) -> &PromptNode {
+    document.prompts().next().unwrap()
+}
+
+fn values(pairs: &[(&str, Value)]) -> InputValues {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+}
+
+#[test]
+fn values_are_coerced_to_the_declared_type() {
+    let document = DocumentNode::parse(DOCUMENT).unwrap();
+    // As given on the command line, where everything is a string.
+    let given = values(&[("word", json!("42")), ("times", json!("3")), ("options", json!(r#"{"a": 1}"#)), ("unused", json!(true))]);
+    let bound = prompt(&document).bind_inputs(&given).unwrap();
+    // A string stays a string even if it parses as JSON, undeclared values are dropped.
+    assert_eq!(bound, values(&[("word", json!("42")), ("times", json!(3)), ("options", json!({"a": 1}))]));
+    // Already typed values are taken as they are.
+    let typed = values(&[("word", json!("kettle")), ("times", json!(3)), ("options", json!({}))]);
+    assert_eq!(prompt(&document).bind_inputs(&typed).unwrap(), typed);
+}
+
+#[test]
+fn coercion_by_type() {
+    let coerce = |r#type: InputType, value: Value| r#type.coerce(value);
+    assert_eq!(coerce(InputType::Number, json!("1.5")), Ok(json!(1.5)));
+    assert_eq!(coerce(InputType::Integer, json!("1.5")), Err(json!("1.5")));
+    assert_eq!(coerce(InputType::Boolean, json!("true")), Ok(json!(true)));
+    assert_eq!(coerce(InputType::Array, json!("[1, 2]")), Ok(json!([1, 2])));
+    assert_eq!(coerce(InputType::Schema, json!("[]")), Err(json!("[]")));
+    assert_eq!(coerce(InputType::String, json!(7)), Err(json!(7)));
+    // Json takes anything, parsing strings that are JSON.
+    assert_eq!(coerce(InputType::Json, json!("{\"a\": null}")), Ok(json!({"a": null})));
+    assert_eq!(coerce(InputType::Json, json!("plain text")), Ok(json!("plain text")));
+}
+
+#[test]
+fn missing_inputs() {
+    let document = DocumentNode::parse(DOCUMENT).unwrap();
+    // Inputs have no defaults, every declared one must be given.
+    let error = prompt(&document).bind_inputs(&values(&[("word", json!("kettle")), ("options", json!({}))])).unwrap_err();
+    assert!(matches!(&error, InvocationError::MissingInput { name, r#type: Some(InputType::Integer) } if name == "times"), "{error}");
+    assert_eq!(error.to_string(), "missing input 'times' of type Integer");
+}
+
+#[test]
+fn invalid_inputs() {
+    let document = DocumentNode::parse(DOCUMENT).unwrap();
+    let given = values(&[("word", json!("kettle")), ("times", json!("three")), ("options", json!({}))]);
+    let error = prompt(&document).bind_inputs(&given).unwrap_err();
+    assert!(matches!(&error, InvocationError::InvalidInput { name, expected: InputType::Integer, given: "a string" } if name == "times"), "{error}");
+    let given = values(&[("word", json!(["kettle"])), ("times", json!(3)), ("options", json!({}))]);
+    let error = prompt(&document).bind_inputs(&given).unwrap_err();
+    assert_eq!(error.to_string(), "input 'word' should be of type String, given an array");
+}
+
+#[tokio::test]
+async fn invoking_checks_and_renders_the_inputs() {
+    let document = DocumentNode::parse(DOCUMENT).unwrap();
+    let invocation = |inputs: InputValues| DocumentInvocation {
+        runtime_environment: RuntimeEnvironment { mock: Some(MockProvider::echo()), ..Default::default() },
+        target_prompt: String::from("count"),
+        inputs,
+        resume_from: None,
+    };
+    let given = values(&[("word", json!("kettle")), ("times", json!("3")), ("options", json!(r#"{"a": 1}"#))]);
+    let snapshot = document.invoke(&invocation(given)).await.unwrap().to_snapshot();
+    assert_eq!(snapshot.messages[0].message_payload.content(), r#"kettle × 3 with {"a":1}"#);
+    let error = document.invoke(&invocation(values(&[("word", json!("kettle"))]))).await.err().unwrap();
+    assert!(matches!(error, Error::Invocation(InvocationError::MissingInput { .. })), "{error}");
+    assert_eq!(error.exit_code(), 64);
+}
+
+#[test]
+fn rendering_an_unbound_input() {
+    let document = DocumentNode::parse(DOCUMENT).unwrap();
+    let PromptChildNode::Msg(msg) = &prompt(&document).children[0] else { panic!("expected a message") };
+    let error = msg.render(&InputValues::new(), TextFormatType::Plain).unwrap_err();
+    assert!(matches!(&error, InvocationError::MissingInput { name, r#type: None } if name == "word"), "{error}");
+}
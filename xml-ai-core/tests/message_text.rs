@@ -0,0 +1,89 @@
+//! Rendering `<msg>` markup as plain text and as Markdown.
+use xml_ai_core::ast::document::DocumentNode;
+use xml_ai_core::ast::prompt::PromptChildNode;
+use xml_ai_core::common::input::InputValues;
+use xml_ai_core::common::prompt::TextFormatType;
+
+const MARKUP: &str = r#"
+    <h2>Task</h2>
+    <p>Summarize   the <b> text </b>below
+       in <code>a `single`</code> line, <i>please</i>.<br>Nothing else.</p>
+    <ul>
+        <li>be brief</li>
+        <li><p>be precise</p><ol start="3"><li>cite</li><li>quote</li></ol></li>
+    </ul>
+    <blockquote><p>To be</p><p>or not</p></blockquote>
+    <pre lang="json">
+        {
+          "a": 1
+        }
+    </pre>
+    <p from="text"></p>
+"#;
+
+fn render(markup: &str, format: TextFormatType) -> String {
+    let source = format!("<prompt name=\"a\" model=\"m\" input:text=\"of type String\"><msg role=\"user\">{markup}</msg></prompt>");
+    let document = DocumentNode::parse(&source).unwrap();
+    let prompt = document.prompts().next().unwrap();
+    let PromptChildNode::Msg(msg) = &prompt.children[0] else { panic!("expected a message") };
+    let inputs = InputValues::from([(String::from("text"), serde_json::json!("  Verbatim *value*  "))]);
+    msg.render(&inputs, format).unwrap()
+}
+
+#[test]
+fn plain_text() {
+    let expected = [
+        "Task",
+        "Summarize the text below in a `single` line, please.",
+        "Nothing else.",
+        "- be brief",
+        "- be precise",
+        "  3. cite",
+        "  4. quote",
+        "  To be",
+        "  or not",
+        "{",
+        "  \"a\": 1",
+        "}",
+        "  Verbatim *value*  ",
+    ];
+    assert_eq!(render(MARKUP, TextFormatType::Plain), expected.join("\n"));
+}
+
+#[test]
+fn markdown() {
+    let expected = [
+        "## Task",
+        "",
+        "Summarize the **text** below in `` a `single` `` line, *please*.",
+        "Nothing else.",
+        "",
+        "- be brief",
+        "- be precise",
+        "  3. cite",
+        "  4. quote",
+        "",
+        "> To be",
+        ">",
+        "> or not",
+        "",
+        "```json",
+        "{",
+        "  \"a\": 1",
+        "}",
+        "```",
+        "",
+        "  Verbatim *value*  ",
+    ];
+    assert_eq!(render(MARKUP, TextFormatType::Markdown), expected.join("\n"));
+}
+
+#[test]
+fn whitespace_rules() {
+    // Text only: dedented, without blank lines around it.
+    assert_eq!(render("\n\n    first\n      second   \n    ", TextFormatType::Plain), "first\n  second");
+    // Loose text forms paragraphs, empty elements vanish.
+    assert_eq!(render("before<p></p><p>\n  after  </p><b> </b>", TextFormatType::Markdown), "before\n\nafter");
+    // Unknown and misplaced elements render as their content.
+    assert_eq!(render("<x-note>a <p>b</p></x-note><span><i><b>c</b></i> d</span>", TextFormatType::Markdown), "a\n\nb\n\n***c*** d");
+}
@@ -0,0 +1,142 @@
+//! Parsing documents, and where their errors are reported.
+use xml_ai_core::ast::document::DocumentNode;
+use xml_ai_core::error::Error;
+use xml_ai_core::parser::DslFormatErrorList;
+
+fn parse_errors(source: &str) -> DslFormatErrorList {
+    match DocumentNode::parse(source) {
+        Err(Error::Dsl { errors, .. }) => errors,
+        result => panic!("expected DSL errors, got {result:?}"),
+    }
+}
+
+#[test]
+fn errors_are_located() {
+    let source = concat!(
+        "<prompt name=\"a\" model=\"stand-in\">\n",
+        "    <msg role=\"user\">Hi</msg>\n",
+        "    <foo>oops</foo>\n",
+        "    <breakpoint role=\"bot\"></breakpoint>\n",
+        "</prompt>\n",
+        "<bogus></bogus>\n",
+    );
+    let errors = parse_errors(source);
+    let located = errors.errors
+        .iter()
+        .map(|x| {
+            let location = x.location().expect("every error is located");
+            let span = location.span.expect("parsed from source");
+            (span.to_string(), location.tag.clone().unwrap(), location.attribute.clone())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(located, [
+        (String::from("3:5"), String::from("foo"), None),
+        (String::from("4:17"), String::from("breakpoint"), Some(String::from("role"))),
+        (String::from("6:1"), String::from("bogus"), None),
+    ]);
+    assert_eq!(errors.errors[0].to_string(), "invalid prompt child `<foo>`");
+    assert!(errors.errors[1].to_string().contains("\"bot\""));
+    assert!(errors.errors.iter().all(|x| x.hint().is_some()));
+    assert!(errors.to_string().starts_with("3:5: invalid prompt child"));
+}
+
+#[test]
+fn reference_errors_are_located() {
+    let source = concat!(
+        "<prompt name=\"a\" model=\"stand-in\">\n",
+        "    <msg role=\"user\">Hi</msg>\n",
+        "    <breakpoint role=\"assistant\" schema=\"missing\"></breakpoint>\n",
+        "</prompt>\n",
+    );
+    let errors = parse_errors(source);
+    let location = errors.errors[0].location().unwrap();
+    assert_eq!(location.span.unwrap().to_string(), "3:5");
+    assert_eq!(location.attribute.as_deref(), Some("schema"));
+}
+
+#[test]
+fn selection_errors() {
+    let prompt = |breakpoint: &str| format!(
+        "<prompt name=\"a\" model=\"stand-in\" n=\"2\"><msg role=\"user\">Hi</msg>{breakpoint}</prompt>"
+    );
+    let cases = [
+        ("<breakpoint role=\"assistant\" select=\"best\"></breakpoint>", "select"),
+        ("<breakpoint role=\"assistant\" select=\"passes-schema\"></breakpoint>", "select"),
+        ("<breakpoint role=\"assistant\" select=\"first\" judge=\"a\"></breakpoint>", "judge"),
+        ("<breakpoint role=\"assistant\" judge=\"missing\"></breakpoint>", "judge"),
+        ("<breakpoint role=\"assistant\" judge=\"a\"></breakpoint>", "judge"),
+    ];
+    for (breakpoint, attribute) in cases {
+        let errors = parse_errors(&prompt(breakpoint));
+        let location = errors.errors[0].location().unwrap();
+        assert_eq!(location.attribute.as_deref(), Some(attribute), "{breakpoint}: {errors}");
+    }
+}
+
+#[test]
+fn judges_need_the_choices_input() {
+    let source = concat!(
+        "<prompt name=\"a\" model=\"stand-in\" n=\"2\"><msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" judge=\"j\"></breakpoint></prompt>",
+        "<prompt name=\"j\" model=\"stand-in\"><msg role=\"user\">Which one?</msg></prompt>",
+    );
+    let errors = parse_errors(source);
+    assert_eq!(errors.errors.len(), 1);
+    assert!(errors.errors[0].to_string().contains("doesn’t declare the input `choices`"), "{errors}");
+    assert!(errors.errors[0].hint().unwrap().contains("input:choices"));
+    let declared = source.replace("<prompt name=\"j\"", "<prompt name=\"j\" input:choices=\"of type String\"");
+    assert!(DocumentNode::parse(&declared).is_ok());
+}
+
+#[test]
+fn repair_prompts_need_the_output_and_errors_inputs() {
+    let source = concat!(
+        "<schema id=\"s\">{}</schema>",
+        "<prompt name=\"a\" model=\"stand-in\"><msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" schema=\"s\" repair=\"r\"></breakpoint></prompt>",
+        "<prompt name=\"r\" model=\"stand-in\"><msg role=\"user\">Fix it</msg></prompt>",
+    );
+    let errors = parse_errors(source);
+    assert_eq!(errors.errors.len(), 1, "{errors}");
+    assert_eq!(
+        errors.errors[0].to_string(),
+        "a breakpoint of prompt 'a' is repaired by 'r', which doesn’t declare the inputs `output` and `errors`",
+    );
+    assert_eq!(errors.errors[0].location().unwrap().attribute.as_deref(), Some("repair"));
+    assert!(errors.errors[0].hint().unwrap().contains("input:output=\"of type String\" input:errors=\"of type String\""));
+    let output = source.replace("<prompt name=\"r\"", "<prompt name=\"r\" input:output=\"of type String\"");
+    assert!(parse_errors(&output).errors[0].to_string().ends_with("doesn’t declare the input `errors`"));
+    let declared = output.replace("<prompt name=\"r\"", "<prompt name=\"r\" input:errors=\"of type String\"");
+    assert!(DocumentNode::parse(&declared).is_ok());
+}
+
+#[test]
+fn repair_and_judge_cycles() {
+    // `a` is judged by `b`, whose output `c` repairs, which is judged by `a` again.
+    let source = concat!(
+        "<schema id=\"s\">{}</schema>",
+        "<prompt name=\"a\" model=\"m\" n=\"2\" input:choices=\"of type String\">",
+        "<msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" judge=\"b\"></breakpoint></prompt>",
+        "<prompt name=\"b\" model=\"m\" input:choices=\"of type String\">",
+        "<msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" schema=\"s\" repair=\"c\"></breakpoint></prompt>",
+        "<prompt name=\"c\" model=\"m\" n=\"2\" input:output=\"of type String\" input:errors=\"of type String\">",
+        "<msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" judge=\"a\"></breakpoint></prompt>",
+        "<prompt name=\"d\" model=\"m\" n=\"2\"><msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" judge=\"a\"></breakpoint></prompt>",
+    );
+    let errors = parse_errors(source);
+    // Reported once, where the cycle closes, `d` merely leads into it.
+    assert_eq!(errors.errors.len(), 1, "{errors}");
+    assert_eq!(errors.errors[0].to_string(), "repair and judge prompts invoke each other in a cycle: 'a' → 'b' → 'c' → 'a'");
+    assert_eq!(errors.errors[0].location().unwrap().attribute.as_deref(), Some("judge"));
+    // Without the last edge it’s a chain.
+    let chain = source.replace("judge=\"a\"></breakpoint></prompt><prompt name=\"d\"", "></breakpoint></prompt><prompt name=\"d\"");
+    assert!(DocumentNode::parse(&chain).is_ok());
+}
+
+#[test]
+fn tool_commands_are_split_like_a_shell() {
+    let document = DocumentNode::parse(r#"<tool name="t" command="python3 'my tools/t.py' --flag=&quot;a b&quot; c\ d"></tool>"#).unwrap();
+    let command = document.tools().next().unwrap().command.clone().unwrap();
+    assert_eq!(command, ["python3", "my tools/t.py", "--flag=a b", "c d"]);
+    let errors = parse_errors(r#"<tool name="t" command="python3 'unterminated"></tool>"#);
+    assert!(errors.errors[0].to_string().contains("isn’t valid shell syntax"), "{errors}");
+    assert_eq!(errors.errors[0].location().unwrap().attribute.as_deref(), Some("command"));
+}
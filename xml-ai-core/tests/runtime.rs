@@ -0,0 +1,419 @@
+//! Evaluating documents offline, against the mock provider and the local stand-in server.
+use super_ai_client::provider::{OpenAiCompatibleProvider, ProviderRegistry};
+use super_ai_client::request::{FunctionCall, ToolCall};
+use super_ai_client::retry::RetryPolicy;
+use super_ai_client::stand_in::{StandInReply, StandInServer};
+use xml_ai_core::ast::document::DocumentNode;
+use xml_ai_core::cache::ResponseCache;
+use xml_ai_core::error::Error;
+use xml_ai_core::mock::MockProvider;
+use xml_ai_core::runtime::{DocumentInvocation, InvocationError, RuntimeEnvironment};
+use xml_ai_core::snapshot::ConversationSnapshot;
+use xml_ai_core::tool::ToolRegistry;
+
+const CONVERSATION: &str = r#"
+<prompt name="conversation" model="stand-in">
+    <msg role="system">You are terse.</msg>
+    <msg role="user">First question</msg>
+    <breakpoint role="assistant"></breakpoint>
+    <msg role="user">Second question</msg>
+    <breakpoint role="assistant"></breakpoint>
+</prompt>
+"#;
+
+const GREETING: &str = r#"
+<prompt name="greeting" model="stand-in">
+    <msg role="user">Say hello</msg>
+    <breakpoint role="assistant"></breakpoint>
+</prompt>
+"#;
+
+const NAMES: &str = r#"
+<schema id="names">{"type": "array", "items": {"type": "string"}}</schema>
+<prompt name="names" model="stand-in">
+    <msg role="user">Some names please</msg>
+    <breakpoint role="assistant" schema="names" max-attempts="2"></breakpoint>
+</prompt>
+"#;
+
+const WEATHER: &str = r#"
+<tool name="get_weather" description="The current weather in a city">
+    {"type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]}
+</tool>
+<tool name="echo" command="cat"></tool>
+<prompt name="weather" model="stand-in">
+    <msg role="user">Weather in Paris?</msg>
+    <breakpoint role="assistant" tools="get_weather echo" tool-choice="required" max-tool-rounds="2"></breakpoint>
+</prompt>
+"#;
+
+fn invocation(prompt: &str, runtime_environment: RuntimeEnvironment) -> DocumentInvocation {
+    DocumentInvocation {
+        runtime_environment,
+        target_prompt: prompt.to_string(),
+        inputs: Default::default(),
+        resume_from: None,
+    }
+}
+
+fn stand_in_environment(server: &StandInServer) -> RuntimeEnvironment {
+    RuntimeEnvironment {
+        providers: ProviderRegistry::empty().with_provider(OpenAiCompatibleProvider::custom("stand-in", server.base_url())),
+        default_provider: Some(String::from("stand-in")),
+        ..Default::default()
+    }
+}
+
+async fn run(document: &str, prompt: &str, runtime_environment: RuntimeEnvironment) -> Result<ConversationSnapshot, Error> {
+    let document = DocumentNode::parse(document)?;
+    let prompt_context = document.invoke(&invocation(prompt, runtime_environment)).await?;
+    Ok(prompt_context.to_snapshot())
+}
+
+fn contents(snapshot: &ConversationSnapshot) -> Vec<&str> {
+    snapshot.messages.iter().map(|x| x.message_payload.content()).collect()
+}
+
+#[tokio::test]
+async fn scripted_mock_answers_breakpoints_in_order() {
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::script(["one", "two"])), ..Default::default() };
+    let snapshot = run(CONVERSATION, "conversation", runtime_environment).await.unwrap();
+    assert_eq!(contents(&snapshot), ["You are terse.", "First question", "one", "Second question", "two"]);
+    assert_eq!(snapshot.usage.calls.len(), 2);
+    assert!(snapshot.usage.calls.iter().all(|x| x.provider == MockProvider::NAME));
+}
+
+#[tokio::test]
+async fn echo_mock_repeats_the_last_message() {
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::echo()), ..Default::default() };
+    let snapshot = run(CONVERSATION, "conversation", runtime_environment).await.unwrap();
+    assert_eq!(contents(&snapshot)[2..], ["First question", "Second question", "Second question"]);
+}
+
+#[tokio::test]
+async fn exhausted_mock_fails() {
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::script(["one"])), ..Default::default() };
+    let error = run(CONVERSATION, "conversation", runtime_environment).await.unwrap_err();
+    assert!(matches!(error, Error::Invocation(InvocationError::MockExhausted { replies: 1 })), "{error}");
+    assert_eq!(error.exit_code(), 65);
+}
+
+#[tokio::test]
+async fn replay_mock_reuses_recorded_replies() {
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::script(["one", "two"])), ..Default::default() };
+    let recorded = run(CONVERSATION, "conversation", runtime_environment).await.unwrap();
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::replay(recorded.clone())), ..Default::default() };
+    let replayed = run(CONVERSATION, "conversation", runtime_environment).await.unwrap();
+    assert_eq!(contents(&replayed), contents(&recorded));
+    // An edited message has no recorded reply.
+    let edited = CONVERSATION.replace("Second question", "Another question");
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::replay(recorded)), ..Default::default() };
+    let error = run(&edited, "conversation", runtime_environment).await.unwrap_err();
+    assert!(matches!(error, Error::Invocation(InvocationError::NoRecordedReply { position: 4 })), "{error}");
+}
+
+async fn resume(document: &str, snapshot: &ConversationSnapshot, server: &StandInServer) -> Result<ConversationSnapshot, Error> {
+    let document = DocumentNode::parse(document)?;
+    let document_invocation = DocumentInvocation {
+        resume_from: Some(snapshot.clone()),
+        ..invocation("conversation", stand_in_environment(server))
+    };
+    Ok(document.invoke(&document_invocation).await?.to_snapshot())
+}
+
+#[tokio::test]
+async fn resuming_a_complete_snapshot_replays_it() {
+    let server = StandInServer::start().await.unwrap().with_replies([StandInReply::text("one"), StandInReply::text("two")]);
+    let snapshot = run(CONVERSATION, "conversation", stand_in_environment(&server)).await.unwrap();
+    let resumed = resume(CONVERSATION, &snapshot, &server).await.unwrap();
+    assert_eq!(contents(&resumed), contents(&snapshot));
+    assert_eq!(resumed.usage.calls.len(), 2);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn resuming_a_changed_prompt_is_a_mismatch() {
+    let server = StandInServer::start().await.unwrap().with_replies([StandInReply::text("one"), StandInReply::text("two")]);
+    let snapshot = run(CONVERSATION, "conversation", stand_in_environment(&server)).await.unwrap();
+    let mismatch = |result: Result<ConversationSnapshot, Error>| match result {
+        Err(Error::Invocation(InvocationError::SnapshotMismatch { position, .. })) => position,
+        result => panic!("expected a mismatch, got {result:?}"),
+    };
+    let edited = CONVERSATION.replace("Second question", "Another question");
+    assert_eq!(mismatch(resume(&edited, &snapshot, &server).await), 4);
+    let edited = CONVERSATION.replace("You are terse.", "You are verbose.");
+    assert_eq!(mismatch(resume(&edited, &snapshot, &server).await), 1);
+    // A breakpoint where the snapshot has a `<msg>` and the other way around.
+    let edited = CONVERSATION.replace("<msg role=\"user\">Second question</msg>", "");
+    assert_eq!(mismatch(resume(&edited, &snapshot, &server).await), 4);
+    let edited = CONVERSATION.replacen("<breakpoint role=\"assistant\"></breakpoint>", "", 1);
+    assert_eq!(mismatch(resume(&edited, &snapshot, &server).await), 3);
+    assert_eq!(server.requests().len(), 2);
+    // Without the snapshot the edited prompt is invoked from the start.
+    let edited = CONVERSATION.replace("Second question", "Another question");
+    let invoked = run(&edited, "conversation", stand_in_environment(&server)).await.unwrap();
+    assert_eq!(contents(&invoked)[3..], ["Another question", "Another question"]);
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn resuming_a_partial_snapshot_continues_at_the_next_breakpoint() {
+    let server = StandInServer::start().await.unwrap().with_replies([StandInReply::text("one"), StandInReply::text("two")]);
+    let mut snapshot = run(CONVERSATION, "conversation", stand_in_environment(&server)).await.unwrap();
+    // Cut off before the second breakpoint, with or without the `<msg>` leading up to it.
+    for evaluated in [4, 3] {
+        snapshot.messages.truncate(evaluated);
+        let server = StandInServer::start().await.unwrap().with_reply(StandInReply::text("three"));
+        let resumed = resume(CONVERSATION, &snapshot, &server).await.unwrap();
+        assert_eq!(contents(&resumed), ["You are terse.", "First question", "one", "Second question", "three"]);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["messages"][2]["content"], "one");
+    }
+    // Nothing evaluated yet, every breakpoint is invoked.
+    snapshot.messages.truncate(2);
+    let server = StandInServer::start().await.unwrap();
+    resume(CONVERSATION, &snapshot, &server).await.unwrap();
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn stand_in_end_to_end() {
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::text("The first answer"),
+        StandInReply::text("The second answer"),
+    ]);
+    let snapshot = run(CONVERSATION, "conversation", stand_in_environment(&server)).await.unwrap();
+    assert_eq!(contents(&snapshot)[2..], ["The first answer", "Second question", "The second answer"]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1]["messages"].as_array().unwrap().len(), 4);
+    assert_eq!(requests[1]["stream"], true);
+    // One token per word, see the stand-in.
+    assert_eq!(snapshot.usage.total.completion_tokens, 6);
+    assert_eq!(snapshot.usage.unreported(), 0);
+}
+
+#[tokio::test]
+async fn failures_share_one_retry_budget() {
+    let replies = || [StandInReply::status(503, "overloaded"), StandInReply::Interrupted(String::from("cut")), StandInReply::text("done")];
+    let retry_policy = RetryPolicy::default().with_initial_backoff(std::time::Duration::from_millis(1)).with_jitter(false);
+    let server = StandInServer::start().await.unwrap().with_replies(replies());
+    let runtime_environment = RuntimeEnvironment {
+        retry_policy: retry_policy.clone().with_max_attempts(3),
+        ..stand_in_environment(&server)
+    };
+    let snapshot = run(GREETING, "greeting", runtime_environment).await.unwrap();
+    assert_eq!(contents(&snapshot)[1], "done");
+    assert_eq!(server.requests().len(), 3);
+    // The client doesn’t retry on its own, so two attempts are two requests.
+    let server = StandInServer::start().await.unwrap().with_replies(replies());
+    let runtime_environment = RuntimeEnvironment {
+        retry_policy: retry_policy.with_max_attempts(2),
+        ..stand_in_environment(&server)
+    };
+    let error = run(GREETING, "greeting", runtime_environment).await.unwrap_err();
+    assert!(error.to_string().contains("ended unexpectedly"), "{error}");
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn shared_api_key_requires_opting_in() {
+    let server = StandInServer::start().await.unwrap();
+    let runtime_environment = RuntimeEnvironment { api_key: Some(String::from("sk-secret")), ..stand_in_environment(&server) };
+    run(CONVERSATION, "conversation", runtime_environment.clone()).await.unwrap();
+    assert_eq!(server.authorizations(), [None, None]);
+    let provider = OpenAiCompatibleProvider::custom("stand-in", server.base_url()).with_shared_api_key(true);
+    let runtime_environment = RuntimeEnvironment {
+        providers: ProviderRegistry::empty().with_provider(provider),
+        ..runtime_environment
+    };
+    run(CONVERSATION, "conversation", runtime_environment).await.unwrap();
+    assert_eq!(server.authorizations()[2..], [Some(String::from("Bearer sk-secret")), Some(String::from("Bearer sk-secret"))]);
+    // Only `openai` accepts it out of the box.
+    let builtin = ProviderRegistry::with_builtin();
+    let accepts = builtin.names().map(|x| (x, builtin.get(x).unwrap().accepts_shared_api_key())).collect::<Vec<_>>();
+    assert_eq!(accepts, [("openai", true), ("octoai", false), ("mistral", false)]);
+}
+
+#[tokio::test]
+async fn invalid_output_is_repaired() {
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::text("Sure, here you go: Ada, Grace"),
+        StandInReply::text(r#"["Ada", "Grace"]"#),
+    ]);
+    let snapshot = run(NAMES, "names", stand_in_environment(&server)).await.unwrap();
+    let last = snapshot.messages.last().unwrap();
+    assert_eq!(last.message_payload.content(), r#"["Ada", "Grace"]"#);
+    assert!(last.verification.as_ref().is_some_and(|x| x.valid));
+    assert_eq!(last.attempts.len(), 1);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn unrepaired_output_fails_verification() {
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::text("Ada"),
+        StandInReply::text("Grace"),
+    ]);
+    let error = run(NAMES, "names", stand_in_environment(&server)).await.unwrap_err();
+    assert!(matches!(error, Error::Invocation(InvocationError::VerificationFailed { .. })), "{error}");
+}
+
+#[tokio::test]
+async fn cached_completions_skip_the_server() {
+    let directory = std::env::temp_dir().join(format!("xml-ai-core-cache-{}", std::process::id()));
+    let server = StandInServer::start().await.unwrap();
+    let runtime_environment = RuntimeEnvironment {
+        cache: Some(ResponseCache::new(&directory)),
+        ..stand_in_environment(&server)
+    };
+    let first = run(CONVERSATION, "conversation", runtime_environment.clone()).await.unwrap();
+    let second = run(CONVERSATION, "conversation", runtime_environment.clone()).await.unwrap();
+    let _ = std::fs::remove_dir_all(&directory);
+    assert_eq!(contents(&first), contents(&second));
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(second.usage.cached(), 2);
+    let stats = runtime_environment.cache.unwrap().stats();
+    assert_eq!((stats.hits, stats.misses, stats.stored), (2, 2, 2));
+}
+
+fn tool_call(id: &str, name: &str, arguments: &str) -> ToolCall {
+    ToolCall::new(id, FunctionCall { name: name.into(), arguments: arguments.into() })
+}
+
+fn weather_tools() -> ToolRegistry {
+    ToolRegistry::default().with_callback("get_weather", |arguments| async move {
+        match arguments["city"].as_str() {
+            Some(city) => Ok(format!("Sunny in {city}")),
+            None => Err(String::from("missing city")),
+        }
+    })
+}
+
+#[tokio::test]
+async fn tool_calls_run_until_the_model_answers() {
+    let server = StandInServer::start().await.unwrap().with_replies([
+        StandInReply::ToolCalls(vec![
+            tool_call("call_1", "get_weather", r#"{"city": "Paris"}"#),
+            tool_call("call_2", "echo", r#"{"text": "hi"}"#),
+        ]),
+        StandInReply::ToolCalls(vec![tool_call("call_3", "get_weather", "{}")]),
+        StandInReply::text("Sunny, go outside"),
+    ]);
+    let runtime_environment = RuntimeEnvironment { tools: weather_tools(), ..stand_in_environment(&server) };
+    let snapshot = run(WEATHER, "weather", runtime_environment).await.unwrap();
+    let roles = snapshot.messages.iter().map(|x| x.message_payload.role()).collect::<Vec<_>>();
+    assert_eq!(roles, ["user", "assistant", "tool", "tool", "assistant", "tool", "assistant"]);
+    assert_eq!(
+        contents(&snapshot)[2..],
+        ["Sunny in Paris", r#"{"text":"hi"}"#, "", "error: missing city", "Sunny, go outside"],
+    );
+    assert!(snapshot.messages[1..].iter().all(|x| x.evaluation_point));
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0]["tools"][0]["function"]["parameters"]["required"][0], "city");
+    assert_eq!(requests[0]["tool_choice"], "required");
+    assert!(requests[1].get("tool_choice").is_none());
+    assert_eq!(requests[1]["messages"][2]["tool_call_id"], "call_1");
+    assert_eq!(requests[2]["messages"].as_array().unwrap().len(), 6);
+    // Resuming keeps the tool exchange without running anything again.
+    let document = DocumentNode::parse(WEATHER).unwrap();
+    let document_invocation = DocumentInvocation {
+        resume_from: Some(snapshot.clone()),
+        ..invocation("weather", stand_in_environment(&server))
+    };
+    let resumed = document.invoke(&document_invocation).await.unwrap().to_snapshot();
+    assert_eq!(contents(&resumed), contents(&snapshot));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn tool_rounds_are_bounded() {
+    let call = tool_call("call", "get_weather", r#"{"city": "Paris"}"#);
+    let server = StandInServer::start().await.unwrap().with_replies(vec![StandInReply::ToolCalls(vec![call]); 3]);
+    let runtime_environment = RuntimeEnvironment { tools: weather_tools(), ..stand_in_environment(&server) };
+    let error = run(WEATHER, "weather", runtime_environment).await.unwrap_err();
+    assert!(matches!(error, Error::Invocation(InvocationError::ToolRoundsExceeded { rounds: 2 })), "{error}");
+    // Without a callback `get_weather` can’t be run, which fails before any request.
+    let error = run(WEATHER, "weather", stand_in_environment(&server)).await.unwrap_err();
+    assert!(matches!(error, Error::Invocation(InvocationError::UnboundTool { .. })), "{error}");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn render_without_calling_a_model() {
+    let runtime_environment = RuntimeEnvironment {
+        // Never connected to.
+        providers: ProviderRegistry::empty().with_provider(OpenAiCompatibleProvider::custom("stand-in", "http://127.0.0.1:9")),
+        default_provider: Some(String::from("stand-in")),
+        ..Default::default()
+    };
+    let document = DocumentNode::parse(CONVERSATION).unwrap();
+    let requests = document.render(&invocation("conversation", runtime_environment.clone())).unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].breakpoint, Some(1));
+    assert_eq!(requests[0].request.messages.len(), 2);
+    let contents = requests[1].request.messages.iter().map(|x| x.content()).collect::<Vec<_>>();
+    assert_eq!(contents, ["You are terse.", "First question", "{{output of breakpoint 1}}", "Second question"]);
+    let document = DocumentNode::parse(WEATHER).unwrap();
+    let requests = document.render(&invocation("weather", runtime_environment)).unwrap();
+    let body = serde_json::to_value(&requests[0].request).unwrap();
+    assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(body["tool_choice"], "required");
+}
+
+const CHOICES: &str = r#"
+<prompt name="pick" model="stand-in" n="3">
+    <msg role="user">A fruit as JSON</msg>
+    <breakpoint role="assistant" select="SELECT"></breakpoint>
+</prompt>
+<prompt name="judge" input:choices="of type String">
+    <msg role="user"><p>Which is best?</p><p from="choices"></p></msg>
+    <breakpoint role="assistant"></breakpoint>
+</prompt>
+"#;
+
+async fn pick(select: &str, replies: &[&str]) -> Result<ConversationSnapshot, Error> {
+    let document = CHOICES.replace("select=\"SELECT\"", select);
+    let runtime_environment = RuntimeEnvironment { mock: Some(MockProvider::script(replies.to_vec())), ..Default::default() };
+    run(&document, "pick", runtime_environment).await
+}
+
+#[tokio::test]
+async fn breakpoints_select_one_of_several_choices() {
+    let replies = ["not json", r#"{"name": "fig", "colour": "purple"}"#, r#"{"colour": "purple", "name": "fig"}"#];
+    let cases = [
+        ("select=\"first\"", 0),
+        ("select=\"longest\"", 1),
+        ("select=\"passes-schema\" verification=\"json\"", 1),
+        ("select=\"majority\"", 1),
+    ];
+    for (select, selected) in cases {
+        let snapshot = pick(select, &replies).await.unwrap();
+        let last = snapshot.messages.last().unwrap();
+        let selection = last.selection.as_ref().expect("every choice is recorded");
+        assert_eq!(selection.choices, replies, "{select}");
+        assert_eq!(selection.selected, selected, "{select}");
+        assert_eq!(last.message_payload.content(), replies[selected], "{select}");
+    }
+    // The judge’s reply is the mock’s fourth.
+    let snapshot = pick("judge=\"judge\"", &[replies[0], replies[1], replies[2], "Choice 3, clearly."]).await.unwrap();
+    let selection = snapshot.messages.last().unwrap().selection.clone().unwrap();
+    assert_eq!((selection.policy.as_str(), selection.selected), ("judge:judge", 2));
+    assert_eq!(snapshot.usage.calls.len(), 2);
+    let error = pick("judge=\"judge\"", &["a", "b", "c", "I can’t decide"]).await.unwrap_err();
+    assert!(matches!(error, Error::Invocation(InvocationError::InvalidJudgement { choices: 3, .. })), "{error}");
+    assert_eq!(error.exit_code(), 65);
+}
+
+#[tokio::test]
+async fn stand_in_choices_are_recorded() {
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::choices(["short", "a longer one"]));
+    let document = CONVERSATION.replace("model=\"stand-in\"", "model=\"stand-in\" n=\"2\"").replacen("<breakpoint role=\"assistant\">", "<breakpoint role=\"assistant\" select=\"longest\">", 1);
+    let snapshot = run(&document, "conversation", stand_in_environment(&server)).await.unwrap();
+    assert_eq!(contents(&snapshot)[2], "a longer one");
+    let selection = snapshot.messages[2].selection.as_ref().unwrap();
+    assert_eq!(selection.choices, ["short", "a longer one"]);
+    assert_eq!(server.requests()[0]["n"], 2);
+}
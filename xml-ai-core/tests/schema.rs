@@ -0,0 +1,87 @@
+//! Rejecting schemas with constraints the validator can’t check, instead of passing any output.
+use serde_json::json;
+use xml_ai_core::ast::document::DocumentNode;
+use xml_ai_core::common::input::InputValues;
+use xml_ai_core::common::schema::unsupported_keywords;
+use xml_ai_core::error::Error;
+use xml_ai_core::mock::MockProvider;
+use xml_ai_core::runtime::{DocumentInvocation, InvocationError, RuntimeEnvironment};
+
+fn unsupported(schema: serde_json::Value) -> Vec<String> {
+    unsupported_keywords(&schema).iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn unsupported_keywords_are_found_in_subschemas() {
+    assert_eq!(unsupported(json!({"type": "string", "format": "email"})), ["`format`"]);
+    let schema = json!({
+        "type": "object",
+        "properties": { "id": { "type": "string", "pattern": "^[a-z]+$" } },
+        "patternProperties": { "^x-": {} },
+        "additionalProperties": { "anyOf": [{ "type": "string", "format": "date" }] },
+        "$defs": { "tags": { "type": "array", "items": { "pattern": "^#" } } },
+    });
+    assert_eq!(unsupported(schema), [
+        "`pattern` at /properties/id",
+        "`patternProperties`",
+        "`format` at /additionalProperties/anyOf/0",
+        "`pattern` at /$defs/tags/items",
+    ]);
+    // Values named like keywords aren’t schemas.
+    let schema = json!({
+        "type": "object",
+        "properties": { "pattern": { "type": "string" }, "format": { "enum": [{ "pattern": "x" }] } },
+        "required": ["pattern"],
+    });
+    assert_eq!(unsupported(schema), Vec::<String>::new());
+}
+
+#[test]
+fn documents_with_unsupported_schemas_fail_to_parse() {
+    let source = concat!(
+        "<schema id=\"user\">{\"type\": \"object\", \"properties\": {\"email\": {\"type\": \"string\", \"format\": \"email\"}}}</schema>\n",
+        "<prompt name=\"a\" model=\"m\"><msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" schema=\"user\"></breakpoint></prompt>",
+    );
+    let Err(Error::Dsl { errors, .. }) = DocumentNode::parse(source) else { panic!("expected the document to fail") };
+    assert_eq!(errors.errors.len(), 1, "{errors}");
+    assert_eq!(errors.errors[0].to_string(), "schema 'user' uses keywords output can’t be verified against: `format` at /properties/email");
+    assert_eq!(errors.errors[0].location().unwrap().span.unwrap().to_string(), "1:1");
+    // Tool parameters aren’t verified, only sent to the model.
+    let tool = source.replace("schema=\"user\"", "tools=\"t\"").replace("<prompt", "<tool name=\"t\" parameters=\"user\" command=\"cat\"></tool><prompt");
+    assert!(DocumentNode::parse(&tool).is_ok());
+}
+
+#[tokio::test]
+async fn schema_inputs_are_checked_before_verifying() {
+    let source = concat!(
+        "<prompt name=\"a\" input:shape=\"of type Schema\"><msg role=\"user\">Hi</msg>",
+        "<breakpoint role=\"assistant\" verification=\"schema\"></breakpoint></prompt>",
+    );
+    let document = DocumentNode::parse(source).unwrap();
+    let invocation = |shape| DocumentInvocation {
+        runtime_environment: RuntimeEnvironment { mock: Some(MockProvider::script([r#""Hi""#])), ..Default::default() },
+        target_prompt: String::from("a"),
+        inputs: InputValues::from([(String::from("shape"), shape)]),
+        resume_from: None,
+    };
+    let error = document.invoke(&invocation(json!({"type": "string", "pattern": "^H"}))).await.err().unwrap();
+    assert!(matches!(&error, Error::Invocation(InvocationError::UnsupportedSchema { .. })), "{error}");
+    assert_eq!(error.to_string(), "schema 'input:shape' uses keywords output can’t be verified against: `pattern`");
+    assert_eq!(error.exit_code(), 65);
+    document.invoke(&invocation(json!({"type": "string"}))).await.unwrap();
+}
+
+#[test]
+fn loaded_schemas_are_checked_too() {
+    let directory = std::env::temp_dir().join(format!("xml-ai-core-schema-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("user.json"), r#"{"type": "object", "patternProperties": {"^x-": {}}}"#).unwrap();
+    let source = concat!(
+        "<schema id=\"user\" src=\"user.json\"></schema>",
+        "<prompt name=\"a\" model=\"m\"><msg role=\"user\">Hi</msg><breakpoint role=\"assistant\" schema=\"user\"></breakpoint></prompt>",
+    );
+    let mut document = DocumentNode::parse(source).unwrap();
+    let error = document.resolve_schemas(&directory).unwrap_err();
+    assert!(error.to_string().contains("schema 'user' uses keywords output can’t be verified against: `patternProperties`"), "{error}");
+    std::fs::remove_dir_all(&directory).unwrap();
+}
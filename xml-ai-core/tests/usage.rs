@@ -0,0 +1,80 @@
+//! Summing the usage of a run’s calls and pricing them.
+use super_ai_client::response::batch::Usage;
+use xml_ai_core::usage::{CallUsage, ModelPrice, PriceTable, UsageSummary};
+
+fn usage(prompt_tokens: isize, completion_tokens: isize) -> Usage {
+    Usage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }
+}
+
+fn prices() -> PriceTable {
+    PriceTable::default().with_price("gpt-4o", ModelPrice { input: 2.5, output: 10.0 })
+}
+
+fn call(model: &str, usage: Option<Usage>, latency_ms: u64) -> CallUsage {
+    CallUsage {
+        model: model.to_string(),
+        provider: String::from("openai"),
+        usage,
+        latency_ms,
+        cost: prices().cost(model, usage.as_ref()),
+        cached: false,
+    }
+}
+
+#[test]
+fn pricing_a_known_model() {
+    let price = prices().get("gpt-4o").copied().unwrap();
+    // $2.50 per million prompt tokens and $10 per million completion tokens.
+    assert_eq!(price.cost(&usage(1_000_000, 0)), 2.5);
+    assert_eq!(prices().cost("gpt-4o", Some(&usage(2_000, 500))), Some(0.01));
+    assert_eq!(prices().cost("gpt-4o", Some(&usage(0, 0))), Some(0.0));
+    // Without reported usage there’s nothing to price.
+    assert_eq!(prices().cost("gpt-4o", None), None);
+}
+
+#[test]
+fn pricing_an_unknown_model() {
+    assert_eq!(prices().get("gpt-4o-mini"), None);
+    assert_eq!(prices().cost("gpt-4o-mini", Some(&usage(2_000, 500))), None);
+    assert_eq!(PriceTable::default().cost("gpt-4o", Some(&usage(2_000, 500))), None);
+}
+
+#[test]
+fn prices_from_toml() {
+    let table = toml::from_str::<PriceTable>("[model.\"gpt-4o\"]\ninput = 2.50\noutput = 10.00\n").unwrap();
+    assert_eq!(table.get("gpt-4o"), prices().get("gpt-4o"));
+}
+
+#[test]
+fn summing_calls() {
+    let summary = UsageSummary::from_calls(vec![
+        call("gpt-4o", Some(usage(2_000, 500)), 1_200),
+        call("gpt-4o", Some(usage(1_000, 100)), 800),
+        call("gpt-4o-mini", Some(usage(300, 30)), 500),
+        call("gpt-4o", None, 100),
+    ]);
+    assert_eq!(summary.total, usage(3_300, 630));
+    assert_eq!(summary.latency_ms, 2_600);
+    // Only the calls to gpt-4o with usage are priced.
+    assert!((summary.cost.unwrap() - 0.0135).abs() < 1e-12, "{:?}", summary.cost);
+    assert_eq!((summary.unreported(), summary.unpriced(), summary.cached()), (1, 2, 0));
+    assert!(summary.to_string().ends_with("4 call(s) · 3300 prompt + 630 completion = 3930 tokens · 2.60s · $0.013500 (2 call(s) unpriced) · 1 call(s) without usage"), "{summary}");
+}
+
+#[test]
+fn cached_calls_are_not_counted() {
+    let cached = CallUsage { cached: true, cost: None, ..call("gpt-4o", Some(usage(2_000, 500)), 0) };
+    let summary = UsageSummary::from_calls(vec![call("gpt-4o", Some(usage(1_000, 100)), 800), cached]);
+    assert_eq!(summary.total, usage(1_000, 100));
+    assert_eq!(summary.cost, Some(0.0035));
+    assert_eq!((summary.unreported(), summary.unpriced(), summary.cached()), (0, 0, 1));
+    assert_eq!(format!("{summary:#}"), "  2 call(s) · 1000 prompt + 100 completion = 1100 tokens · 0.80s · $0.003500 · 1 call(s) cached");
+}
+
+#[test]
+fn nothing_priced() {
+    let summary = UsageSummary::from_calls(vec![call("gpt-4o-mini", Some(usage(10, 1)), 10)]);
+    assert_eq!(summary.cost, None);
+    assert!(!summary.to_string().contains('$'), "{summary}");
+    assert_eq!(UsageSummary::from_calls(Vec::new()).total, Usage::default());
+}
@@ -1,6 +1,27 @@
-use std::path::PathBuf;
-use clap::{Parser, Subcommand};
-use xml_ai_core::runtime::{DocumentInvocation, RuntimeEnvironment};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use clap::{Args, Parser, Subcommand};
+use colored::Colorize;
+use futures::StreamExt;
+use ai_client::client::HttpClient;
+use ai_client::cassette::Cassette;
+use ai_client::limit::ConcurrencyLimiter;
+use ai_client::provider::{OpenAiCompatibleProvider, ProviderRegistry, ProvidersConfig};
+use ai_client::ratelimit::{RateLimitConfig, RateLimiter};
+use ai_client::retry::RetryPolicy;
+use xml_ai_core::ast::document::DocumentNode;
+use xml_ai_core::batch::{BatchInvocation, BatchResult, BatchRow};
+use xml_ai_core::cache::{CacheMode, ResponseCache};
+use xml_ai_core::check::CheckReport;
+use xml_ai_core::common::input::InputValues;
+use xml_ai_core::dry_run::RenderedRequest;
+use xml_ai_core::error::Error;
+use xml_ai_core::mock::MockProvider;
+use xml_ai_core::parser::DslFormatError;
+use xml_ai_core::runtime::{DocumentInvocation, InvocationError, PromptContext, RuntimeEnvironment};
+use xml_ai_core::snapshot::{ConversationSnapshot, SnapshotFormat};
+use xml_ai_core::usage::{PriceTable, UsageSummary};
+use crate::diagnostic::{self, Severity};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -12,6 +33,78 @@ pub struct CommandLineInterface {
 #[derive(Subcommand, Debug)]
 enum SubCommand {
     Run(RunCli),
+    /// Evaluate a prompt once per row of a JSON Lines file.
+    Batch(BatchCli),
+    /// Validate documents without calling a model.
+    Check(CheckCli),
+    /// Print the requests a prompt would send, without calling a model.
+    Render(RenderCli),
+}
+
+/// Settings shared by all commands that call a model.
+#[derive(Args, Debug)]
+struct RuntimeArgs {
+    /// API key file path.
+    #[arg(short, long)]
+    pub key_file: Option<PathBuf>,
+    /// Default model for prompts that don’t specify one.
+    #[arg(short, long, env = "XML_AI_MODEL")]
+    pub model: Option<String>,
+    /// Default provider for prompts that don’t specify one.
+    #[arg(short, long, env = "XML_AI_PROVIDER")]
+    pub provider: Option<String>,
+    /// Additional providers (TOML or JSON), registered alongside the built-in ones.
+    #[arg(long, env = "XML_AI_PROVIDERS")]
+    pub providers: Option<PathBuf>,
+    /// Base URL of an OpenAI compatible server (e.g. `http://localhost:8080/v1`), registered
+    /// under the `--provider` name (or `custom`) and used as the default provider.
+    #[arg(long, env = "XML_AI_BASE_URL")]
+    pub base_url: Option<String>,
+    /// Send the `--key-file` key to the `--base-url` server too; it’s only sent to `openai` otherwise.
+    #[arg(long, requires = "base_url")]
+    pub send_key_to_base_url: bool,
+    /// Extra request header in the form `NAME: VALUE`, may be repeated.
+    #[arg(long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+    /// Attempts per breakpoint for rate limits, server errors and dropped connections (`1` disables retries).
+    #[arg(long, env = "XML_AI_MAX_ATTEMPTS", default_value_t = RetryPolicy::default().max_attempts)]
+    pub max_attempts: u32,
+    /// Per-model prices (TOML or JSON) used to estimate the cost of the run.
+    #[arg(long, env = "XML_AI_PRICES")]
+    pub prices: Option<PathBuf>,
+    /// How many requests may be in flight at once, across all providers.
+    #[arg(long, env = "XML_AI_MAX_CONCURRENCY")]
+    pub max_concurrency: Option<usize>,
+    /// Limit for a single provider in the form `NAME=N`, may be repeated; overrides the provider’s `max-concurrency`.
+    #[arg(long = "provider-concurrency", value_parser = parse_provider_concurrency)]
+    pub provider_concurrency: Vec<(String, usize)>,
+    /// Requests-per-minute and tokens-per-minute budgets per provider or model (TOML or JSON).
+    #[arg(long, env = "XML_AI_RATE_LIMITS")]
+    pub rate_limits: Option<PathBuf>,
+    /// Reuse the completions of identical requests from the response cache and store new ones.
+    #[arg(long, env = "XML_AI_CACHE", overrides_with = "no_cache")]
+    pub cache: bool,
+    /// Don’t use the response cache, e.g. despite `XML_AI_CACHE`.
+    #[arg(long, overrides_with = "cache")]
+    pub no_cache: bool,
+    /// Request every completion again and replace its cache entry; implies `--cache`.
+    #[arg(long, conflicts_with = "no_cache")]
+    pub refresh: bool,
+    /// Where the response cache is stored.
+    #[arg(long, env = "XML_AI_CACHE_DIR", default_value = ResponseCache::DEFAULT_DIRECTORY)]
+    pub cache_dir: PathBuf,
+    /// Answer breakpoints without a model: `echo`, `script:PATH` (a JSON array of replies) or `replay:PATH` (a snapshot).
+    #[arg(long, env = "XML_AI_MOCK")]
+    pub mock: Option<String>,
+    /// Record every HTTP exchange, including streamed responses chunk by chunk, into a cassette file.
+    #[arg(long, value_name = "PATH", conflicts_with = "replay_cassette")]
+    pub record_cassette: Option<PathBuf>,
+    /// Answer requests from a cassette recorded via `--record-cassette` instead of sending them.
+    #[arg(long, value_name = "PATH")]
+    pub replay_cassette: Option<PathBuf>,
+    /// Prompt input in the form `NAME=VALUE`, may be repeated; `NAME=@PATH` reads the value from a file.
+    #[arg(long = "input", value_parser = parse_input)]
+    pub inputs: Vec<(String, String)>,
 }
 
 #[derive(Parser, Debug)]
@@ -22,71 +115,416 @@ struct RunCli {
     /// The name of the prompt.
     #[arg(short, long)]
     pub name: String,
-    /// API key file path.
-    #[arg(short, long)]
-    pub key_file: PathBuf,
     /// Path to the output log file.
     #[arg(short, long)]
     pub output: PathBuf,
+    #[command(flatten)]
+    pub runtime: RuntimeArgs,
+    /// Prompt inputs as a JSON (or TOML) object, `--input` takes precedence.
+    #[arg(long)]
+    pub input_file: Option<PathBuf>,
+    /// Snapshot of an earlier, e.g. interrupted, run of the same prompt to continue from.
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+struct BatchCli {
+    /// Path to the prompt file.
+    pub file: PathBuf,
+    /// The name of the prompt.
+    #[arg(short, long)]
+    pub name: String,
+    /// Input rows, one JSON object per line; `--input` values are shared by all rows.
+    #[arg(long = "inputs")]
+    pub rows: PathBuf,
+    /// Results, one JSON object per line; rows that already succeeded are skipped, failed ones are evaluated again.
+    #[arg(long)]
+    pub out: PathBuf,
+    /// The field identifying a row, rows without it are identified by their line number.
+    #[arg(long, default_value = BatchRow::DEFAULT_ID_FIELD)]
+    pub id_field: String,
+    /// How many rows are evaluated at once.
+    #[arg(short, long, env = "XML_AI_CONCURRENCY", default_value_t = 4)]
+    pub concurrency: usize,
+    #[command(flatten)]
+    pub runtime: RuntimeArgs,
+}
+
+#[derive(Parser, Debug)]
+struct CheckCli {
+    /// Paths to the prompt files.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    /// Fail on warnings as well, e.g. in CI.
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+/// Takes the same settings as `run`, so models and providers resolve the same way.
+#[derive(Parser, Debug)]
+struct RenderCli {
+    /// Path to the prompt file.
+    pub file: PathBuf,
+    /// The name of the prompt.
+    #[arg(short, long)]
+    pub name: String,
+    /// Print the request bodies as JSON instead of text.
+    #[arg(long)]
+    pub json: bool,
+    #[command(flatten)]
+    pub runtime: RuntimeArgs,
+    /// Prompt inputs as a JSON (or TOML) object, `--input` takes precedence.
+    #[arg(long)]
+    pub input_file: Option<PathBuf>,
 }
 
 impl CommandLineInterface {
     pub fn load() -> Self {
         Self::parse()
     }
-    pub async fn execute(self) {
+    pub async fn execute(self) -> Result<(), Error> {
         match self.command {
             SubCommand::Run(run) => run.execute().await,
+            SubCommand::Batch(batch) => batch.execute().await,
+            SubCommand::Check(check) => check.execute(),
+            SubCommand::Render(render) => render.execute(),
+        }
+    }
+}
+
+impl RuntimeArgs {
+    fn runtime_environment(&self) -> Result<RuntimeEnvironment, Error> {
+        let api_key = self.key_file
+            .as_ref()
+            .map(read_file)
+            .transpose()?;
+        let mut providers = ProviderRegistry::with_builtin();
+        if let Some(path) = self.providers.as_ref() {
+            providers = providers.with_config(load_config::<ProvidersConfig>(path, "providers")?);
+        }
+        let prices = self.prices
+            .as_ref()
+            .map(|path| load_config::<PriceTable>(path, "prices"))
+            .transpose()?
+            .unwrap_or_default();
+        let mut default_provider = self.provider.clone();
+        if let Some(base_url) = self.base_url.as_ref() {
+            let name = default_provider.get_or_insert_with(|| String::from("custom"));
+            let provider = OpenAiCompatibleProvider::custom(name.as_str(), base_url)
+                .with_shared_api_key(self.send_key_to_base_url);
+            providers = providers.with_provider(provider);
+        }
+        let mut limiter = ConcurrencyLimiter::new();
+        if let Some(limit) = self.max_concurrency {
+            limiter = limiter.with_global_limit(limit);
+        }
+        for (provider, limit) in self.provider_concurrency.iter() {
+            limiter = limiter.with_provider_limit(provider, *limit);
+        }
+        let mut rate_limiter = RateLimiter::new().with_logging(true);
+        if let Some(path) = self.rate_limits.as_ref() {
+            rate_limiter = rate_limiter.with_config(load_config::<RateLimitConfig>(path, "rate limits")?);
+        }
+        let cache = match (self.cache && !self.no_cache, self.refresh) {
+            (_, true) => Some(ResponseCache::new(&self.cache_dir).with_mode(CacheMode::Refresh)),
+            (true, false) => Some(ResponseCache::new(&self.cache_dir)),
+            (false, false) => None,
+        };
+        Ok(RuntimeEnvironment {
+            api_key,
+            default_model: self.model.clone(),
+            providers,
+            default_provider,
+            headers: self.headers.clone(),
+            retry_policy: RetryPolicy::default().with_max_attempts(self.max_attempts),
+            prices,
+            log_output: true,
+            limiter,
+            rate_limiter,
+            cache,
+            mock: self.mock.as_deref().map(MockProvider::from_spec).transpose()?,
+            cassette: self.cassette()?,
+            // The CLI runs tools via their `command`.
+            tools: Default::default(),
+            http_client: HttpClient::builder()
+                .build()
+                .map_err(|error| Error::Configuration(format!("the HTTP client couldn’t be created: {error}")))?,
+        })
+    }
+    fn cassette(&self) -> Result<Option<Cassette>, Error> {
+        if let Some(path) = self.replay_cassette.as_ref() {
+            let cassette = Cassette::replay(path)
+                .map_err(|error| Error::Configuration(format!("{}: {error}", path.display())))?;
+            return Ok(Some(cassette))
+        }
+        Ok(self.record_cassette.as_ref().map(Cassette::record))
+    }
+    /// The values of `input_file`, if any, overridden by the `--input` values.
+    fn inputs_with_file(&self, input_file: Option<&Path>) -> Result<InputValues, Error> {
+        let mut inputs = InputValues::new();
+        if let Some(path) = input_file {
+            inputs.extend(load_config::<serde_json::Map<String, serde_json::Value>>(path, "input")?);
+        }
+        inputs.extend(self.inputs()?);
+        Ok(inputs)
+    }
+    /// The `--input` values, read from files where given as `@PATH`.
+    fn inputs(&self) -> Result<InputValues, Error> {
+        let mut inputs = InputValues::new();
+        for (name, value) in self.inputs.iter() {
+            let value = match value.strip_prefix('@') {
+                Some(path) => read_file(path)?,
+                None => value.clone(),
+            };
+            inputs.insert(name.clone(), serde_json::Value::String(value));
         }
+        Ok(inputs)
     }
 }
 
 impl RunCli {
-    pub async fn execute(self) {
-        let source = std::fs::read_to_string(&self.file).expect("path to given prompt template file");
-        let html_tree = html_ast::parser::parse_from_fragment(source).expect("valid html content");
-        let document = xml_ai_core::ast::document::DocumentNode::from_node(html_tree).expect("should be a valid node");
-        let api_key = std::fs::read_to_string(&self.key_file).expect("API key file");
+    pub async fn execute(self) -> Result<(), Error> {
+        SnapshotFormat::from_path(&self.output)?;
+        let resume_from = self.resume
+            .as_ref()
+            .map(ConversationSnapshot::load)
+            .transpose()?;
+        let document = DocumentNode::load(&self.file)?;
         let document_invocation = DocumentInvocation {
-            runtime_environment: RuntimeEnvironment {
-                api_key,
-            },
+            runtime_environment: self.runtime.runtime_environment()?,
             target_prompt: String::from(&self.name),
+            inputs: self.runtime.inputs_with_file(self.input_file.as_deref())?,
+            resume_from,
         };
-        let prompt_context = document.invoke(&document_invocation).await.unwrap();
+        let mut prompt_context = PromptContext::new(document_invocation.runtime_environment.clone());
+        let result = document.invoke_into(&document_invocation, &mut prompt_context).await;
         let conversation_snapshot = prompt_context.to_snapshot();
-        std::fs::create_dir_all(self.output.parent().unwrap()).unwrap();
-        match self.output.extension().unwrap().to_str().unwrap() {
-            "json" => {
-                let json_snapshot = serde_json::to_string_pretty(&conversation_snapshot).unwrap();
-                std::fs::write(&self.output, &json_snapshot).unwrap();
-            },
-            "toml" => {
-                let toml_snapshot = toml::to_string_pretty(&conversation_snapshot).unwrap();
-                std::fs::write(&self.output, &toml_snapshot).unwrap();
-            },
-            _ => panic!("NOT A VALID OUTPUT FILE"),
+        if let Err(error) = result {
+            // Keep whatever was evaluated so the run can be continued via `--resume`.
+            let mismatch = matches!(error, Error::Invocation(InvocationError::SnapshotMismatch { .. }));
+            if !mismatch && conversation_snapshot.has_evaluated() && conversation_snapshot.save(&self.output).is_ok() {
+                let output = self.output.display();
+                eprintln!("partial snapshot written to {output}, continue with `--resume {output}`");
+            }
+            return Err(error)
         }
+        conversation_snapshot.save(&self.output)?;
         println!("DONE:");
         println!("{:#?}", conversation_snapshot);
+        println!("USAGE:");
+        println!("{}", conversation_snapshot.usage);
+        print_cache_stats(&document_invocation.runtime_environment);
+        Ok(())
     }
 }
 
-// #[derive(Debug, Clone)]
-// enum OutputFormat {
-//     Json,
-//     Toml,
-// }
+impl BatchCli {
+    pub async fn execute(self) -> Result<(), Error> {
+        let document = DocumentNode::load(&self.file)?;
+        // Fail early rather than once per row.
+        document.find_prompt(&self.name)?;
+        let rows = BatchRow::parse_jsonl(&read_file(&self.rows)?, &self.id_field)
+            .map_err(|message| Error::InvalidBatch { path: self.rows.clone(), message })?;
+        let completed = match std::fs::read_to_string(&self.out) {
+            Ok(source) => BatchResult::completed(&source)
+                .map_err(|message| Error::InvalidBatch { path: self.out.clone(), message })?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(error) => return Err(Error::Io { path: self.out.clone(), error }),
+        };
+        let total = rows.len();
+        let pending = rows
+            .into_iter()
+            .filter(|x| !completed.ids.contains(&x.id))
+            .collect::<Vec<_>>();
+        let skipped = total - pending.len();
+        if skipped > 0 {
+            eprintln!("skipping {skipped} row(s) already in {}", self.out.display());
+        }
+        if completed.failed > 0 {
+            eprintln!("retrying {} failed row(s)", completed.failed);
+        }
+        let mut runtime_environment = self.runtime.runtime_environment()?;
+        // Concurrent streams would interleave.
+        runtime_environment.log_output = false;
+        let batch_invocation = BatchInvocation {
+            runtime_environment,
+            target_prompt: self.name.clone(),
+            inputs: self.runtime.inputs()?,
+            concurrency: self.concurrency,
+        };
+        if let Some(parent) = self.out.parent().filter(|x| !x.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|error| Error::Io { path: parent.to_path_buf(), error })?;
+        }
+        // Keep only the rows that succeeded, the others are evaluated again and
+        // appended. Written aside first so an interrupted rewrite loses nothing.
+        let mut retained = self.out.clone().into_os_string();
+        retained.push(".tmp");
+        let retained = PathBuf::from(retained);
+        std::fs::write(&retained, &completed.lines)
+            .and_then(|_| std::fs::rename(&retained, &self.out))
+            .map_err(|error| Error::Io { path: self.out.clone(), error })?;
+        let mut out = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.out)
+            .map_err(|error| Error::Io { path: self.out.clone(), error })?;
+        let count = pending.len();
+        let mut results = std::pin::pin!(document.invoke_batch(&batch_invocation, pending));
+        let mut calls = Vec::new();
+        let mut failed = 0;
+        let mut done = 0;
+        while let Some(result) = results.next().await {
+            done += 1;
+            match result.error.as_ref() {
+                None => eprintln!("[{done}/{count}] {}: ok", result.id),
+                Some(error) => {
+                    failed += 1;
+                    eprintln!("[{done}/{count}] {}: {} {error}", result.id, "error:".red().bold());
+                }
+            }
+            calls.extend(result.snapshot.usage.calls.iter().cloned());
+            let line = serde_json::to_string(&result)
+                .map_err(|error| Error::Serialize(error.to_string()))?;
+            writeln!(out, "{line}")
+                .and_then(|_| out.flush())
+                .map_err(|error| Error::Io { path: self.out.clone(), error })?;
+        }
+        println!("USAGE:");
+        println!("{:#}", UsageSummary::from_calls(calls));
+        print_cache_stats(&batch_invocation.runtime_environment);
+        if failed > 0 {
+            return Err(Error::BatchFailed { failed, total: count })
+        }
+        Ok(())
+    }
+}
 
-// impl OutputFormat {
-//     pub fn from_file_ext(ext: impl AsRef<Path>) -> Option<Self> {
-//         match ext.as_ref().extension()?.to_str()? {
-//             "json" => Some(Self::Json),
-//             "toml" => Some(Self::Toml),
-//             _ => None,
-//         }
-//     }
-//     pub fn write_to()
-// }
+impl CheckCli {
+    pub fn execute(self) -> Result<(), Error> {
+        let mut failed = 0;
+        let mut warnings = 0;
+        for path in self.files.iter() {
+            let report = CheckReport::load(path);
+            if let Some(error) = report.error.as_ref() {
+                eprint!("{}", diagnostic::render_error(error));
+            }
+            let diagnostics = report.warnings.iter().map(|x| x as &dyn DslFormatError);
+            eprint!("{}", diagnostic::render_diagnostics(path, Severity::Warning, diagnostics));
+            warnings += report.warnings.len();
+            if !report.passed(!self.deny_warnings) {
+                failed += 1;
+            }
+        }
+        let total = self.files.len();
+        if failed > 0 {
+            return Err(Error::CheckFailed { failed, total })
+        }
+        println!("{total} document(s) checked, {warnings} warning(s)");
+        Ok(())
+    }
+}
 
+impl RenderCli {
+    pub fn execute(self) -> Result<(), Error> {
+        let document = DocumentNode::load(&self.file)?;
+        let document_invocation = DocumentInvocation {
+            runtime_environment: self.runtime.runtime_environment()?,
+            target_prompt: self.name.clone(),
+            inputs: self.runtime.inputs_with_file(self.input_file.as_deref())?,
+            resume_from: None,
+        };
+        let requests = document.render(&document_invocation)?;
+        if self.json {
+            let json = serde_json::to_string_pretty(&requests)
+                .map_err(|error| Error::Serialize(error.to_string()))?;
+            println!("{json}");
+            return Ok(())
+        }
+        for (index, rendered) in requests.iter().enumerate() {
+            print_rendered_request(index + 1, rendered)?;
+        }
+        Ok(())
+    }
+}
 
+/// The settings as `key: value` lines, then each message under its role.
+fn print_rendered_request(index: usize, rendered: &RenderedRequest) -> Result<(), Error> {
+    let request = &rendered.request;
+    let evaluates = match rendered.breakpoint {
+        Some(breakpoint) => format!("breakpoint {breakpoint}"),
+        None => String::from("final reply"),
+    };
+    let header = format!("━━ request {index} · {evaluates} · {}/{} ━━", rendered.provider, request.model);
+    println!("{}", header.bold());
+    // Through the text as sent, `to_value` would widen `f32` settings.
+    let mut settings = serde_json::to_string(request)
+        .and_then(|x| serde_json::from_str::<serde_json::Value>(&x))
+        .map_err(|error| Error::Serialize(error.to_string()))?;
+    if let Some(settings) = settings.as_object_mut() {
+        settings.remove("messages");
+        settings.remove("model");
+        if let Some(serde_json::Value::Array(tools)) = settings.remove("tools") {
+            let names = tools.iter().filter_map(|x| x["function"]["name"].as_str()).collect::<Vec<_>>();
+            println!("{} {}", "tools:".dimmed(), names.join(", "));
+        }
+        for (key, value) in settings.iter() {
+            println!("{} {value}", format!("{key}:").dimmed());
+        }
+    }
+    for message in request.messages.iter() {
+        println!();
+        println!("{}", format!("[{}]", message.role()).cyan().bold());
+        println!("{}", message.content());
+    }
+    println!();
+    Ok(())
+}
+
+fn print_cache_stats(runtime_environment: &RuntimeEnvironment) {
+    if let Some(cache) = runtime_environment.cache.as_ref() {
+        println!("CACHE:");
+        println!("{}", cache.stats());
+    }
+}
+
+fn read_file(path: impl AsRef<Path>) -> Result<String, Error> {
+    let path = path.as_ref();
+    std::fs::read_to_string(path).map_err(|error| Error::Io { path: path.to_path_buf(), error })
+}
+
+fn parse_input(input: &str) -> Result<(String, String), String> {
+    let (name, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected `NAME=VALUE`, given `{input}`"))?;
+    Ok((name.trim().to_string(), value.to_string()))
+}
+
+fn parse_provider_concurrency(limit: &str) -> Result<(String, usize), String> {
+    let (name, value) = parse_input(limit)?;
+    let value = value
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|x| *x > 0)
+        .ok_or_else(|| format!("expected a positive integer, given `{value}`"))?;
+    Ok((name, value))
+}
+
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("expected `NAME: VALUE`, given `{header}`"))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+/// Loads a TOML or JSON config file, `what` names it in error messages.
+fn load_config<T: serde::de::DeserializeOwned>(path: &Path, what: &str) -> Result<T, Error> {
+    let source = read_file(path)?;
+    let invalid = |error: String| Error::Configuration(format!("{}: {error}", path.display()));
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("json") => serde_json::from_str(&source).map_err(|error| invalid(error.to_string())),
+        Some("toml") => toml::from_str(&source).map_err(|error| invalid(error.to_string())),
+        _ => Err(invalid(format!("{what} file must end in `.json` or `.toml`"))),
+    }
+}
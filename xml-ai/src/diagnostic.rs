@@ -0,0 +1,93 @@
+//! Rendering DSL errors as snippets of the document, like compilers do.
+use std::path::Path;
+
+use colored::{ColoredString, Colorize};
+use html_ast::{LineIndex, Span};
+use xml_ai_core::error::Error;
+use xml_ai_core::parser::{DslFormatError, DslFormatErrorList};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn paint(self, text: &str) -> ColoredString {
+        match self {
+            Self::Error => text.red().bold(),
+            Self::Warning => text.yellow().bold(),
+        }
+    }
+}
+
+/// Renders an error for the terminal, DSL errors as snippets of the document.
+pub fn render_error(error: &Error) -> String {
+    if let Error::Dsl { path: Some(path), errors } = error {
+        return render_dsl_errors(path, errors)
+    }
+    let mut out = format!("{} {error}\n", "error:".red().bold());
+    if let Some(hint) = error.hint() {
+        out.push_str(&format!("{} {hint}\n", "hint:".cyan().bold()));
+    }
+    out
+}
+
+/// Renders each error with the line it is on, see [`render_diagnostics`].
+pub fn render_dsl_errors(path: &Path, errors: &DslFormatErrorList) -> String {
+    render_diagnostics(path, Severity::Error, errors.errors.iter().map(|x| x.as_ref()))
+}
+
+/// Renders each diagnostic with the line it is on, falling back to the plain message
+/// for diagnostics without a span or if the document can’t be read again.
+pub fn render_diagnostics<'a>(
+    path: &Path,
+    severity: Severity,
+    diagnostics: impl IntoIterator<Item = &'a dyn DslFormatError>,
+) -> String {
+    let source = std::fs::read_to_string(path).ok();
+    let index = source.as_deref().map(LineIndex::new);
+    let label = match severity {
+        Severity::Error => "error:",
+        Severity::Warning => "warning:",
+    };
+    let mut out = String::new();
+    for diagnostic in diagnostics {
+        let location = diagnostic.location();
+        let span = location.and_then(|x| x.span);
+        // The width of the line numbers, the notes line up with their bar.
+        let gutter = " ".repeat(span.map(|x| x.start.line.to_string().len()).unwrap_or(1));
+        out.push_str(&format!("{} {diagnostic}\n", severity.paint(label)));
+        match (index.as_ref(), span) {
+            (Some(index), Some(span)) => out.push_str(&snippet(path, index, span, severity)),
+            _ => out.push_str(&format!("{gutter}{} {}\n", "-->".blue().bold(), path.display())),
+        }
+        if let Some(location) = location.filter(|x| x.tag.is_some()) {
+            out.push_str(&format!("{gutter} {} in {location}\n", "=".blue().bold()));
+        }
+        if let Some(hint) = diagnostic.hint() {
+            out.push_str(&format!("{gutter} {} {hint}\n", "= hint:".cyan().bold()));
+        }
+    }
+    out
+}
+
+/// The `--> path:line:column` header and the span’s first line, underlined.
+fn snippet(path: &Path, index: &LineIndex, span: Span, severity: Severity) -> String {
+    let line = index.line(span.start.line).unwrap_or_default();
+    let number = span.start.line.to_string();
+    let gutter = " ".repeat(number.len());
+    let width = match span.end.line == span.start.line {
+        true => span.end.column.saturating_sub(span.start.column),
+        false => line.chars().count().saturating_sub(span.start.column - 1),
+    };
+    let underline = format!("{}{}", " ".repeat(span.start.column - 1), "^".repeat(width.max(1)));
+    let bar = "|".blue().bold();
+    format!(
+        "{gutter}{} {}:{span}\n{gutter} {bar}\n{} {bar} {line}\n{gutter} {bar} {}\n",
+        "-->".blue().bold(),
+        path.display(),
+        number.blue().bold(),
+        severity.paint(&underline),
+    )
+}
@@ -1,11 +1,16 @@
 use crate::cli::CommandLineInterface;
 
 extern crate super_html_ast as html_ast;
+extern crate super_ai_client as ai_client;
 
 pub mod cli;
+pub mod diagnostic;
 
 #[tokio::main]
 async fn main() {
     let cli = CommandLineInterface::load();
-    cli.execute().await
+    if let Err(error) = cli.execute().await {
+        eprint!("{}", diagnostic::render_error(&error));
+        std::process::exit(error.exit_code())
+    }
 }
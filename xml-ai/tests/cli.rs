@@ -0,0 +1,205 @@
+//! The `xml-ai` binary against the local stand-in server and the mock provider.
+use std::path::PathBuf;
+use std::process::Output;
+
+use super_ai_client::stand_in::{StandInReply, StandInServer};
+
+const DOCUMENT: &str = r#"
+<prompt name="greeting" model="stand-in">
+    <msg role="user">Say hello</msg>
+    <breakpoint role="assistant"></breakpoint>
+</prompt>
+<prompt name="row" model="stand-in" input:word="of type String">
+    <msg role="user"><span from="word"></span></msg>
+    <breakpoint role="assistant"></breakpoint>
+</prompt>
+"#;
+
+/// A fresh directory holding the document, removed when dropped.
+struct Workspace(PathBuf);
+
+impl Workspace {
+    fn new(name: &str) -> Self {
+        let directory = std::env::temp_dir().join(format!("xml-ai-cli-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("document.html"), DOCUMENT).unwrap();
+        Self(directory)
+    }
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+    fn read(&self, name: &str) -> String {
+        std::fs::read_to_string(self.path(name)).unwrap()
+    }
+    /// Runs the binary in the workspace without any `XML_AI_*` configuration from the environment.
+    async fn xml_ai(&self, args: &[&str]) -> Output {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_xml-ai"));
+        for (name, _) in std::env::vars().filter(|(name, _)| name.starts_with("XML_AI_")) {
+            command.env_remove(name);
+        }
+        command.current_dir(&self.0).args(args).output().await.unwrap()
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[tokio::test]
+async fn run_against_the_stand_in() {
+    let workspace = Workspace::new("run");
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::text("Hello, world"));
+    let output = workspace.xml_ai(&["run", "document.html", "-n", "greeting", "-o", "out.json", "--base-url", &server.base_url()]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let snapshot = serde_json::from_str::<serde_json::Value>(&workspace.read("out.json")).unwrap();
+    assert_eq!(snapshot["messages"][1]["message_payload"]["content"], "Hello, world");
+    assert_eq!(server.requests()[0]["messages"][0]["content"], "Say hello");
+}
+
+#[tokio::test]
+async fn run_with_a_scripted_mock() {
+    let workspace = Workspace::new("mock");
+    std::fs::write(workspace.path("replies.json"), r#"["Hi there"]"#).unwrap();
+    let output = workspace.xml_ai(&["run", "document.html", "-n", "greeting", "-o", "out.json", "--mock", "script:replies.json"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("out.json").contains("Hi there"));
+}
+
+#[tokio::test]
+async fn exit_codes() {
+    let workspace = Workspace::new("exit-codes");
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::status(401, "invalid api key"));
+    let output = workspace.xml_ai(&["run", "document.html", "-n", "greeting", "-o", "out.json", "--base-url", &server.base_url()]).await;
+    assert_eq!(output.status.code(), Some(77), "{}", stderr(&output));
+    let output = workspace.xml_ai(&["run", "document.html", "-n", "missing", "-o", "out.json", "--mock", "echo"]).await;
+    assert_eq!(output.status.code(), Some(64), "{}", stderr(&output));
+    let output = workspace.xml_ai(&["run", "document.html", "-n", "row", "-o", "out.json", "--mock", "echo"]).await;
+    assert_eq!(output.status.code(), Some(64), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn batch_skips_completed_rows() {
+    let workspace = Workspace::new("batch");
+    let rows = ["alpha", "beta", "gamma"]
+        .iter()
+        .map(|x| format!("{{\"id\": \"{x}\", \"word\": \"{x}\"}}\n"))
+        .collect::<String>();
+    std::fs::write(workspace.path("rows.jsonl"), rows).unwrap();
+    let args = ["batch", "document.html", "-n", "row", "--inputs", "rows.jsonl", "--out", "results.jsonl", "--mock", "echo"];
+    let output = workspace.xml_ai(&args).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let results = workspace.read("results.jsonl")
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .map(|x| (x["id"].as_str().unwrap().to_string(), x["output"].as_str().unwrap().to_string()))
+        .collect::<std::collections::BTreeMap<_, _>>();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results["beta"], "beta");
+    let output = workspace.xml_ai(&args).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("skipping 3 row(s)"), "{}", stderr(&output));
+    assert_eq!(workspace.read("results.jsonl").lines().count(), 3);
+}
+
+#[tokio::test]
+async fn batch_retries_failed_rows() {
+    let workspace = Workspace::new("batch-retry");
+    let rows = ["alpha", "beta", "gamma"]
+        .iter()
+        .map(|x| format!("{{\"id\": \"{x}\", \"word\": \"{x}\"}}\n"))
+        .collect::<String>();
+    std::fs::write(workspace.path("rows.jsonl"), rows).unwrap();
+    // One reply short, so the last row fails.
+    std::fs::write(workspace.path("replies.json"), r#"["one", "two"]"#).unwrap();
+    let args = ["batch", "document.html", "-n", "row", "--inputs", "rows.jsonl", "--out", "results.jsonl", "-c", "1"];
+    let output = workspace.xml_ai(&[&args[..], &["--mock", "script:replies.json"]].concat()).await;
+    assert_eq!(output.status.code(), Some(75), "{}", stderr(&output));
+    assert!(workspace.read("results.jsonl").lines().nth(2).unwrap().contains(r#""error":"#));
+    // An interrupted run leaves a line cut off.
+    let mut results = workspace.read("results.jsonl");
+    results.push_str(r#"{"id": "delta", "out"#);
+    std::fs::write(workspace.path("results.jsonl"), results).unwrap();
+    let output = workspace.xml_ai(&[&args[..], &["--mock", "echo"]].concat()).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("skipping 2 row(s)"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("retrying 1 failed row(s)"), "{}", stderr(&output));
+    let results = workspace.read("results.jsonl")
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .map(|x| (x["id"].as_str().unwrap().to_string(), x["output"].as_str().unwrap().to_string()))
+        .collect::<Vec<_>>();
+    let expected = [("alpha", "one"), ("beta", "two"), ("gamma", "gamma")].map(|(id, output)| (id.to_string(), output.to_string()));
+    assert_eq!(results, expected);
+}
+
+#[tokio::test]
+async fn replay_a_recorded_cassette() {
+    let workspace = Workspace::new("cassette");
+    let server = StandInServer::start().await.unwrap().with_reply(StandInReply::text("Recorded hello"));
+    let base_url = server.base_url();
+    let args = ["run", "document.html", "-n", "greeting", "--base-url", &base_url];
+    let output = workspace.xml_ai(&[&args[..], &["-o", "recorded.json", "--record-cassette", "cassette.json"]].concat()).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    drop(server);
+    let output = workspace.xml_ai(&[&args[..], &["-o", "replayed.json", "--replay-cassette", "cassette.json"]].concat()).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("replayed.json").contains("Recorded hello"));
+    // The prompt asks something else now.
+    std::fs::write(workspace.path("document.html"), DOCUMENT.replace("Say hello", "Say goodbye")).unwrap();
+    let output = workspace.xml_ai(&[&args[..], &["-o", "replayed.json", "--replay-cassette", "cassette.json"]].concat()).await;
+    assert_eq!(output.status.code(), Some(65), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn dsl_errors_point_at_the_source() {
+    let workspace = Workspace::new("dsl-errors");
+    let document = DOCUMENT.replace("<msg role=\"user\">Say hello</msg>", "<msg role=\"usr\">Say hello</msg>");
+    std::fs::write(workspace.path("document.html"), document).unwrap();
+    let output = workspace.xml_ai(&["run", "document.html", "-n", "greeting", "-o", "out.json", "--mock", "echo"]).await;
+    assert_eq!(output.status.code(), Some(65), "{}", stderr(&output));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("--> document.html:3:10"), "{stderr}");
+    assert!(stderr.contains("<msg role=\"usr\">Say hello</msg>"), "{stderr}");
+    assert!(stderr.contains("^^^^^^^^^^"), "{stderr}");
+    assert!(stderr.contains("= hint:"), "{stderr}");
+}
+
+#[tokio::test]
+async fn check_documents() {
+    let workspace = Workspace::new("check");
+    let output = workspace.xml_ai(&["check", "document.html"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    std::fs::write(workspace.path("warning.html"), DOCUMENT.replace("<prompt name=\"row\"", "<prompt name=\"row\" colour=\"red\"")).unwrap();
+    let output = workspace.xml_ai(&["check", "document.html", "warning.html"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("unknown attribute `colour`"), "{}", stderr(&output));
+    let output = workspace.xml_ai(&["check", "--deny-warnings", "document.html", "warning.html"]).await;
+    assert_eq!(output.status.code(), Some(65), "{}", stderr(&output));
+    std::fs::write(workspace.path("error.html"), DOCUMENT.replace("<msg", "<message").replace("</msg>", "</message>")).unwrap();
+    let output = workspace.xml_ai(&["check", "document.html", "error.html"]).await;
+    assert_eq!(output.status.code(), Some(65), "{}", stderr(&output));
+    assert!(stderr(&output).contains("1 of 2 document(s) failed the check"), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn render_prints_the_requests() {
+    let workspace = Workspace::new("render");
+    // Nothing listens there, rendering never connects.
+    let args = ["render", "document.html", "-n", "row", "--input", "word=kettle", "--base-url", "http://127.0.0.1:9"];
+    let output = workspace.xml_ai(&args).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("request 1 · breakpoint 1"), "{stdout}");
+    assert!(stdout.contains("kettle"), "{stdout}");
+    let output = workspace.xml_ai(&[&args[..], &["--json"]].concat()).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    assert_eq!(requests[0]["request"]["messages"][0]["content"], "kettle");
+}